@@ -1,11 +1,25 @@
+use std::mem;
+
 use glam::Vec3;
 
 use crate::{
     camera::CameraTyp,
-    renderer::{pbr_settings::PbrSettings, PbrMaterial},
-    window::AppWindow,
+    ogl::sampler,
+    renderer::{
+        bloom::BloomSettings,
+        furnace::FurnaceSettings,
+        lighting::{self, Light},
+        pbr_settings::PbrSettings,
+        screenshot::ScreenshotSettings,
+        stats::RenderStats,
+        wireframe::WireframeSettings,
+        PbrMaterial,
+    },
 };
 
+/// Anisotropic filtering is enabled by default if it's supported
+const DEFAULT_ANISOTROPY: f32 = 16.;
+
 pub struct AppSettings {
     // Index into resources scene vector
     pub selected_scene: usize,
@@ -28,15 +42,47 @@ pub struct AppSettings {
     pub model_translation: Vec3,
     pub model_scale: f32,
     pub model_rotation: Vec3,
+
+    /// Maximum anisotropy of the texture filtering, 1.0 disables it
+    pub anisotropy: f32,
+    /// Maximum anisotropy supported by the GPU
+    pub max_anisotropy: f32,
+
+    pub lights: Vec<Light>,
+
+    pub bloom: BloomSettings,
+    pub tonemapping: Tonemapping,
+    /// Exposure compensation in stops (EV)
+    pub exposure: f32,
+    pub antialiasing: Antialiasing,
+
+    /// Fill the depth buffer before shading, so that only the visible fragments are shaded
+    pub depth_prepass: bool,
+    /// Skip the primitives outside of the camera frustum
+    pub frustum_culling: bool,
+    /// Draw the world-space bounding boxes of the visible primitives
+    pub show_bounds: bool,
+    /// Draw the scene with multi-draw indirect calls, requires bindless textures
+    pub multi_draw: bool,
+    /// Written by the renderer every frame
+    pub render_stats: RenderStats,
+
+    pub screenshot: ScreenshotSettings,
+    pub wireframe: WireframeSettings,
+    /// A/B comparison, the left side uses the settings above
+    pub split: SplitView,
+    pub furnace: FurnaceSettings,
 }
 
 impl AppSettings {
-    pub fn new(window: &AppWindow) -> Self {
+    pub fn new(viewport_dim: ViewportDim) -> Self {
+        let max_anisotropy = sampler::max_anisotropy();
+
         Self {
             selected_scene: 0,
             selected_envmap: 0,
             blur_background: true,
-            viewport_dim: ViewportDim::new(window),
+            viewport_dim,
             material_src: MaterialSrc::Gltf,
             camera_typ: CameraTyp::Orbital,
             pbr_material_override: PbrMaterial::new(),
@@ -46,10 +92,93 @@ impl AppSettings {
             model_translation: Vec3::splat(0.),
             model_scale: 1.,
             model_rotation: Vec3::splat(0.),
+            anisotropy: max_anisotropy.min(DEFAULT_ANISOTROPY),
+            max_anisotropy,
+            lights: lighting::default_lights(),
+            bloom: BloomSettings::new(),
+            tonemapping: Tonemapping::Uncharted,
+            exposure: 0.,
+            antialiasing: Antialiasing::Taa,
+            depth_prepass: true,
+            frustum_culling: true,
+            show_bounds: false,
+            multi_draw: false,
+            render_stats: RenderStats::default(),
+            screenshot: ScreenshotSettings::new(),
+            wireframe: WireframeSettings::new(),
+            split: SplitView::new(),
+            furnace: FurnaceSettings::new(),
+        }
+    }
+
+    /// Exchanges the settings of the left side with the right side of the split view
+    pub fn swap_split_side(&mut self) {
+        let side = &mut self.split.right;
+
+        mem::swap(&mut self.material_src, &mut side.material_src);
+        mem::swap(&mut self.selected_merl_brdf, &mut side.selected_merl_brdf);
+        mem::swap(&mut self.selected_utia_brdf, &mut side.selected_utia_brdf);
+        mem::swap(&mut self.pbr_settings, &mut side.pbr_settings);
+    }
+
+    /// The debug views of the left and the right side
+    pub fn debug_views(&self) -> [DebugView; 2] {
+        let left = self.pbr_settings.debug_view;
+        match self.split.enabled {
+            true => [left, self.split.right.pbr_settings.debug_view],
+            false => [left, left],
+        }
+    }
+
+    /// Shadows are enabled on any side of the split view
+    pub fn shadows_enabled(&self) -> bool {
+        self.pbr_settings.shadows_enabled()
+            || (self.split.enabled && self.split.right.pbr_settings.shadows_enabled())
+    }
+
+    /// Screen-space reflections are enabled on any side of the split view
+    pub fn ssr_enabled(&self) -> bool {
+        self.pbr_settings.ssr_enabled()
+            || (self.split.enabled && self.split.right.pbr_settings.ssr_enabled())
+    }
+}
+
+/// Renders the right part of the viewport with different material settings, with the same camera
+pub struct SplitView {
+    pub enabled: bool,
+    /// Position of the divider as a fraction of the viewport width
+    pub divider: f32,
+    /// The GUI shows the settings of the right side in place of the main settings
+    pub editing_right: bool,
+    pub right: SplitSide,
+}
+
+impl SplitView {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            divider: 0.5,
+            editing_right: false,
+            right: SplitSide {
+                material_src: MaterialSrc::PbrOverride,
+                selected_merl_brdf: 0,
+                selected_utia_brdf: 0,
+                pbr_settings: PbrSettings::new(),
+            },
         }
     }
 }
 
+/// The settings that can differ between the sides of the split view
+pub struct SplitSide {
+    pub material_src: MaterialSrc,
+    // Index into the resources merl_brdfs vector
+    pub selected_merl_brdf: usize,
+    // Index into the resources utia_brdfs vector
+    pub selected_utia_brdf: usize,
+    pub pbr_settings: PbrSettings,
+}
+
 #[repr(u32)]
 #[derive(PartialEq, Clone, Copy, bytemuck::NoUninit)]
 pub enum DiffuseType {
@@ -68,7 +197,170 @@ impl DiffuseType {
     }
 }
 
+/// Output of the PBR shaders, must match tools/debug_view.glsl
+#[repr(u32)]
+#[derive(PartialEq, Clone, Copy, bytemuck::NoUninit)]
+pub enum DebugView {
+    None = 0,
+    BaseColor = 1,
+    Metallic = 2,
+    Roughness = 3,
+    Occlusion = 4,
+    Emissive = 5,
+    ShadingNormal = 6,
+    GeometricNormal = 7,
+    Tangent = 8,
+    Bitangent = 9,
+    Uv = 10,
+    NoV = 11,
+    DiffuseOnly = 12,
+    SpecularOnly = 13,
+    IblOnly = 14,
+    DirectOnly = 15,
+    Clearcoat = 16,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 17] = [
+        DebugView::None,
+        DebugView::BaseColor,
+        DebugView::Metallic,
+        DebugView::Roughness,
+        DebugView::Occlusion,
+        DebugView::Emissive,
+        DebugView::ShadingNormal,
+        DebugView::GeometricNormal,
+        DebugView::Tangent,
+        DebugView::Bitangent,
+        DebugView::Uv,
+        DebugView::NoV,
+        DebugView::DiffuseOnly,
+        DebugView::SpecularOnly,
+        DebugView::IblOnly,
+        DebugView::DirectOnly,
+        DebugView::Clearcoat,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            DebugView::None => "None",
+            DebugView::BaseColor => "Base color",
+            DebugView::Metallic => "Metallic",
+            DebugView::Roughness => "Roughness",
+            DebugView::Occlusion => "Occlusion",
+            DebugView::Emissive => "Emissive",
+            DebugView::ShadingNormal => "Shading normal",
+            DebugView::GeometricNormal => "Geometric normal",
+            DebugView::Tangent => "Tangent",
+            DebugView::Bitangent => "Bitangent",
+            DebugView::Uv => "UV",
+            DebugView::NoV => "NoV",
+            DebugView::DiffuseOnly => "Diffuse only",
+            DebugView::SpecularOnly => "Specular only",
+            DebugView::IblOnly => "IBL only",
+            DebugView::DirectOnly => "Direct light only",
+            DebugView::Clearcoat => "Clearcoat layer",
+        }
+    }
+
+    /// The views of lighting are HDR radiance and go through the post-processing as usual,
+    /// the other views are shown as they are
+    pub fn is_radiance(self) -> bool {
+        matches!(
+            self,
+            DebugView::None
+                | DebugView::DiffuseOnly
+                | DebugView::SpecularOnly
+                | DebugView::IblOnly
+                | DebugView::DirectOnly
+                | DebugView::Clearcoat
+        )
+    }
+
+    /// The measured BRDFs have no material inputs and aren't split into lobes,
+    /// only the views of the geometry, of the occlusion texture and of the whole direct light work with them
+    pub fn is_supported_by(self, material_src: MaterialSrc) -> bool {
+        match material_src {
+            MaterialSrc::Gltf | MaterialSrc::PbrOverride => true,
+            MaterialSrc::MerlBrdf | MaterialSrc::UtiaBrdf => !matches!(
+                self,
+                DebugView::BaseColor
+                    | DebugView::Metallic
+                    | DebugView::Roughness
+                    | DebugView::Emissive
+                    | DebugView::DiffuseOnly
+                    | DebugView::SpecularOnly
+                    | DebugView::IblOnly
+                    | DebugView::Clearcoat
+            ),
+        }
+    }
+}
+
+/// Tonemapping operator of the post-processing pass, must match tools/tonemap.glsl
+#[repr(u32)]
+#[derive(PartialEq, Clone, Copy)]
+pub enum Tonemapping {
+    Reinhard = 0,
+    Uncharted = 1,
+    AcesFitted = 2,
+    AcesRrtOdt = 3,
+    Agx = 4,
+    PbrNeutral = 5,
+}
+
+impl Tonemapping {
+    pub const ALL: [Tonemapping; 6] = [
+        Tonemapping::Reinhard,
+        Tonemapping::Uncharted,
+        Tonemapping::AcesFitted,
+        Tonemapping::AcesRrtOdt,
+        Tonemapping::Agx,
+        Tonemapping::PbrNeutral,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Tonemapping::Reinhard => "Reinhard",
+            Tonemapping::Uncharted => "Uncharted 2",
+            Tonemapping::AcesFitted => "ACES (fitted)",
+            Tonemapping::AcesRrtOdt => "ACES (RRT/ODT)",
+            Tonemapping::Agx => "AgX",
+            Tonemapping::PbrNeutral => "Khronos PBR Neutral",
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
+pub enum Antialiasing {
+    None,
+    /// Multisampled HDR target, resolved before the post-processing
+    Msaa,
+    /// Fast approximate anti-aliasing of the tonemapped image
+    Fxaa,
+    /// Temporal anti-aliasing with a jittered projection
+    Taa,
+}
+
+impl Antialiasing {
+    pub const ALL: [Antialiasing; 4] = [
+        Antialiasing::None,
+        Antialiasing::Msaa,
+        Antialiasing::Fxaa,
+        Antialiasing::Taa,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Antialiasing::None => "None",
+            Antialiasing::Msaa => "MSAA 4x",
+            Antialiasing::Fxaa => "FXAA",
+            Antialiasing::Taa => "TAA",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, clap::ValueEnum)]
 pub enum MaterialSrc {
     Gltf,
     PbrOverride,
@@ -85,8 +377,14 @@ impl MaterialSrc {
             MaterialSrc::UtiaBrdf => "UTIA BRDF database",
         }
     }
+
+    /// The material comes from a measured BRDF database instead of the PBR model
+    pub fn is_measured(self) -> bool {
+        matches!(self, MaterialSrc::MerlBrdf | MaterialSrc::UtiaBrdf)
+    }
 }
 
+#[derive(Clone, Copy)]
 pub struct ViewportDim {
     pub min_x: f32,
     pub min_y: f32,
@@ -95,15 +393,21 @@ pub struct ViewportDim {
 }
 
 impl ViewportDim {
-    pub fn new(window: &AppWindow) -> Self {
-        let width = window.width as f32;
-        let height = window.height as f32;
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            min_x: 0.,
+            min_y: 0.,
+            width: width as f32,
+            height: height as f32,
+        }
+    }
 
+    /// The same size, but with the origin in the corner, for rendering into offscreen targets
+    pub fn at_origin(&self) -> Self {
         Self {
             min_x: 0.,
             min_y: 0.,
-            width,
-            height,
+            ..*self
         }
     }
 }
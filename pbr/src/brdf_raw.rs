@@ -2,7 +2,10 @@ use std::{fs::File, io::Read};
 
 use eyre::{eyre, Result};
 
-use crate::ogl::{shader::shader_permutations::ShaderDefines, ssbo::Ssbo};
+use crate::{
+    ogl::{shader::shader_permutations::ShaderDefines, ssbo::Ssbo},
+    resources::LoadProgress,
+};
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
 pub enum BrdfType {
@@ -28,13 +31,33 @@ impl ShaderDefines for BrdfType {
 pub struct BrdfRaw<const BINDING: u32> {
     pub typ: BrdfType,
     pub ssbo: Ssbo<BINDING>,
+    /// Size of the SSBO in bytes
+    pub memory_size: usize,
 }
 
 impl<const BINDING: u32> BrdfRaw<BINDING> {
+    pub fn from_data(data: BrdfData) -> Self {
+        let ssbo = Ssbo::new(&data.raw);
+
+        Self {
+            typ: data.typ,
+            ssbo,
+            memory_size: std::mem::size_of_val(data.raw.as_slice()),
+        }
+    }
+}
+
+/// Raw BRDF table read from the file
+pub struct BrdfData {
+    pub typ: BrdfType,
+    pub raw: Vec<f64>,
+}
+
+impl BrdfData {
     /// A Data-Driven Reflectance Model
     /// Wojciech Matusik, Hanspeter Pfister, Matt Brand and Leonard McMillan
     /// ACM Transactions on Graphics 22, 3(2003), 759-769
-    pub fn merl_from_path(path: &str) -> Result<Self> {
+    pub fn merl_from_path(path: &str, progress: &LoadProgress) -> Result<Self> {
         const RES_THETA_H: i32 = 90;
         const RES_THETA_D: i32 = 90;
         const RES_PHI_D: i32 = 360;
@@ -50,20 +73,18 @@ impl<const BINDING: u32> BrdfRaw<BINDING> {
         }
 
         let mut raw = vec![0f64; 3 * samples as usize];
-        file.read_exact(bytemuck::cast_slice_mut(&mut raw))?;
-
-        let ssbo = Ssbo::new(&raw);
+        read_with_progress(&mut file, &mut raw, progress)?;
 
         Ok(Self {
             typ: BrdfType::Merl,
-            ssbo,
+            raw,
         })
     }
 
     /// Template-Based Sampling of Anisotropic BRDFs
     /// Filip J., Vavra R.
     /// Computer Graphics Forum (Proceedings of Pacific Graphics 2014, Seoul, Korea), Eurographics 2014
-    pub fn utia_from_path(path: &str) -> Result<Self> {
+    pub fn utia_from_path(path: &str, progress: &LoadProgress) -> Result<Self> {
         const STEP_P: f32 = 7.5;
         const NTI: i32 = 6;
         const NTV: i32 = 6;
@@ -75,13 +96,24 @@ impl<const BINDING: u32> BrdfRaw<BINDING> {
 
         let dim = PLANES * NTI * NPI * NTV * NPV;
         let mut raw = vec![0f64; dim as usize];
-        file.read_exact(bytemuck::cast_slice_mut(&mut raw))?;
-
-        let ssbo = Ssbo::new(&raw);
+        read_with_progress(&mut file, &mut raw, progress)?;
 
         Ok(Self {
             typ: BrdfType::Utia,
-            ssbo,
+            raw,
         })
     }
 }
+
+/// Fills the whole buffer from the file in chunks, so the progress can be reported
+fn read_with_progress(file: &mut File, buf: &mut [f64], progress: &LoadProgress) -> Result<()> {
+    const CHUNK_SIZE: usize = 1024 * 1024;
+
+    let len = buf.len();
+    for (i, chunk) in buf.chunks_mut(CHUNK_SIZE).enumerate() {
+        file.read_exact(bytemuck::cast_slice_mut(chunk))?;
+        progress.set(((i + 1) * CHUNK_SIZE).min(len) as f32 / len as f32);
+    }
+
+    Ok(())
+}
@@ -153,6 +153,36 @@ impl Camera for Flycam {
     }
 }
 
+/// Camera with a fixed position and target, used for the headless rendering
+pub struct FixedCamera {
+    pos: Vec3,
+    target: Vec3,
+}
+
+impl FixedCamera {
+    pub fn new(pos: Vec3, target: Vec3) -> Self {
+        Self { pos, target }
+    }
+}
+
+impl Camera for FixedCamera {
+    fn view_mat(&mut self) -> Mat4 {
+        // Looking straight up or down would make the up vector parallel to the view direction
+        let dir = (self.target - self.pos).normalize_or_zero();
+        let up = if dir.cross(Vec3::Y).length_squared() < 1e-6 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+
+        Mat4::look_at_rh(self.pos, self.target, up)
+    }
+
+    fn get_pos(&self) -> Vec3 {
+        self.pos
+    }
+}
+
 pub struct Orbitalcam {
     /// Distance from the origin
     dist: f32,
@@ -0,0 +1,327 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use clap::{Parser, Subcommand};
+use eyre::{eyre, Context, Result};
+use glam::Vec3;
+use serde::Deserialize;
+
+use crate::{app_settings::MaterialSrc, renderer::screenshot::MAX_SCREENSHOT_SIZE};
+
+/// Config file that is used if no other file is specified on the command line
+const DEFAULT_CONFIG_PATH: &str = "pbr.toml";
+
+const DEFAULT_SHADER_DIR: &str = "shaders_stitched";
+
+const DEFAULT_IBL_CACHE_DIR: &str = "ibl_cache";
+
+const DEFAULT_MEMORY_BUDGET_MIB: usize = 2048;
+
+/// Directory of the stitched shaders, set once at startup
+static SHADER_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Directory of the precomputed IBL maps, None if the cache is disabled
+static IBL_CACHE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Command line arguments.
+///
+/// Resource paths can be directories (searched recursively) or individual files.
+/// The paths are combined with the ones from the config file.
+#[derive(Parser)]
+#[command(about = "Physically Based Rendering experiments")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the config file [default: pbr.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Paths to gltf scenes
+    #[arg(long, global = true, num_args = 1..)]
+    scenes: Vec<PathBuf>,
+
+    /// Paths to HDR environment maps
+    #[arg(long, global = true, num_args = 1..)]
+    envmaps: Vec<PathBuf>,
+
+    /// Paths to MERL BRDFs
+    #[arg(long, global = true, num_args = 1..)]
+    merl_brdfs: Vec<PathBuf>,
+
+    /// Paths to UTIA BRDFs
+    #[arg(long, global = true, num_args = 1..)]
+    utia_brdfs: Vec<PathBuf>,
+
+    /// Directory with the stitched shaders [default: shaders_stitched]
+    #[arg(long, global = true)]
+    shader_dir: Option<PathBuf>,
+
+    /// Directory for caching the precomputed IBL maps [default: ibl_cache]
+    #[arg(long, global = true)]
+    ibl_cache_dir: Option<PathBuf>,
+
+    /// Always compute the IBL maps, don't read or write the cache
+    #[arg(long, global = true)]
+    no_ibl_cache: bool,
+
+    /// Memory budget for the loaded resources in MiB [default: 2048]
+    #[arg(long, global = true)]
+    memory_budget: Option<usize>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a single image without a window and exit
+    ///
+    /// Mesa's software rasterizer can be used with LIBGL_ALWAYS_SOFTWARE=1. It doesn't expose OpenGL 4.6 yet,
+    /// so it also needs MESA_GL_VERSION_OVERRIDE=4.6 and MESA_GLSL_VERSION_OVERRIDE=460.
+    Render(RenderArgs),
+}
+
+/// Arguments of the headless rendering.
+///
+/// Resources are either paths to files or names of resources found in the configured directories.
+#[derive(clap::Args)]
+pub struct RenderArgs {
+    /// The gltf scene
+    #[arg(long)]
+    pub scene: String,
+
+    /// The HDR environment map
+    #[arg(long)]
+    pub envmap: String,
+
+    #[arg(long, value_enum, default_value_t = MaterialSrc::Gltf)]
+    pub material_src: MaterialSrc,
+
+    /// The measured BRDF, required by the MERL and UTIA material sources
+    #[arg(long)]
+    pub brdf: Option<String>,
+
+    /// Position of the camera as "x,y,z"
+    #[arg(long, value_parser = parse_vec3, default_value = "0,0,2")]
+    pub camera_pos: Vec3,
+
+    /// Point the camera is looking at as "x,y,z"
+    #[arg(long, value_parser = parse_vec3, default_value = "0,0,0")]
+    pub camera_target: Vec3,
+
+    #[arg(long, default_value_t = 1920)]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..=MAX_SCREENSHOT_SIZE as i64))]
+    pub width: u32,
+
+    #[arg(long, default_value_t = 1080)]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..=MAX_SCREENSHOT_SIZE as i64))]
+    pub height: u32,
+
+    /// The image is saved as linear HDR OpenEXR with the .exr extension and as tonemapped PNG otherwise
+    #[arg(long)]
+    pub output: PathBuf,
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let components = s
+        .split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|e| format!("'{c}': {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err("expected 3 comma-separated numbers".to_string()),
+    }
+}
+
+/// Contents of the config file.
+///
+/// Relative paths are relative to the directory of the config file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    shader_dir: Option<PathBuf>,
+    ibl_cache_dir: Option<PathBuf>,
+    memory_budget: Option<usize>,
+    resources: ResourceRoots,
+}
+
+/// Resource paths of every resource kind
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourceRoots {
+    pub scenes: Vec<PathBuf>,
+    pub envmaps: Vec<PathBuf>,
+    pub merl_brdfs: Vec<PathBuf>,
+    pub utia_brdfs: Vec<PathBuf>,
+}
+
+impl ResourceRoots {
+    fn resolve_relative(&mut self, base: &Path) {
+        for path in self.iter_mut() {
+            *path = base.join(&*path);
+        }
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut PathBuf> {
+        self.scenes
+            .iter_mut()
+            .chain(self.envmaps.iter_mut())
+            .chain(self.merl_brdfs.iter_mut())
+            .chain(self.utia_brdfs.iter_mut())
+    }
+
+    /// Use the default directories for resource kinds that don't have any paths
+    fn fill_defaults(&mut self) {
+        let defaults = [
+            (&mut self.scenes, "resources/gltf"),
+            (&mut self.envmaps, "resources/IBL"),
+            (&mut self.merl_brdfs, "resources/BRDFDatabase"),
+            (&mut self.utia_brdfs, "resources/UTIA"),
+        ];
+
+        for (roots, default) in defaults {
+            if roots.is_empty() {
+                roots.push(PathBuf::from(default));
+            }
+        }
+    }
+}
+
+/// Startup configuration from the command line and the config file
+pub struct Config {
+    pub resources: ResourceRoots,
+    pub shader_dir: PathBuf,
+    pub ibl_cache_dir: Option<PathBuf>,
+    /// Memory budget for the loaded resources in bytes
+    pub memory_budget: usize,
+    /// Render a single image without a window if present
+    pub render: Option<RenderArgs>,
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let args = Args::parse();
+
+        let mut file = match &args.config {
+            Some(path) => ConfigFile::load(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                ConfigFile::load(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => ConfigFile::default(),
+        };
+
+        let mut resources = std::mem::take(&mut file.resources);
+        resources.scenes.extend(args.scenes);
+        resources.envmaps.extend(args.envmaps);
+        resources.merl_brdfs.extend(args.merl_brdfs);
+        resources.utia_brdfs.extend(args.utia_brdfs);
+
+        let render = match args.command {
+            Some(Command::Render(render)) => {
+                render.add_resource_files(&mut resources);
+                Some(render)
+            }
+            None => None,
+        };
+
+        resources.fill_defaults();
+
+        let shader_dir = args
+            .shader_dir
+            .or(file.shader_dir)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SHADER_DIR));
+
+        let ibl_cache_dir = if args.no_ibl_cache {
+            None
+        } else {
+            let dir = args.ibl_cache_dir.or(file.ibl_cache_dir);
+            Some(dir.unwrap_or_else(|| PathBuf::from(DEFAULT_IBL_CACHE_DIR)))
+        };
+
+        let memory_budget = args
+            .memory_budget
+            .or(file.memory_budget)
+            .unwrap_or(DEFAULT_MEMORY_BUDGET_MIB)
+            * 1024
+            * 1024;
+
+        Ok(Self {
+            resources,
+            shader_dir,
+            ibl_cache_dir,
+            memory_budget,
+            render,
+        })
+    }
+}
+
+impl RenderArgs {
+    /// Resources that are given as files don't have to be in the configured directories
+    fn add_resource_files(&self, resources: &mut ResourceRoots) {
+        let brdfs = match self.material_src {
+            MaterialSrc::MerlBrdf => Some(&mut resources.merl_brdfs),
+            MaterialSrc::UtiaBrdf => Some(&mut resources.utia_brdfs),
+            _ => None,
+        };
+
+        let files = [
+            (Some(&mut resources.scenes), Some(&self.scene)),
+            (Some(&mut resources.envmaps), Some(&self.envmap)),
+            (brdfs, self.brdf.as_ref()),
+        ];
+
+        for (roots, resource) in files {
+            if let (Some(roots), Some(resource)) = (roots, resource) {
+                if Path::new(resource).is_file() {
+                    roots.push(PathBuf::from(resource));
+                }
+            }
+        }
+    }
+}
+
+impl ConfigFile {
+    fn load(path: &Path) -> Result<Self> {
+        let src = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Couldn't read the config file '{}'", path.display()))?;
+        let mut file: ConfigFile = toml::from_str(&src)
+            .wrap_err_with(|| format!("Invalid config file '{}'", path.display()))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        file.resources.resolve_relative(base);
+        file.shader_dir = file.shader_dir.map(|dir| base.join(dir));
+        file.ibl_cache_dir = file.ibl_cache_dir.map(|dir| base.join(dir));
+
+        Ok(file)
+    }
+}
+
+/// Sets the directory of the stitched shaders, can only be called once
+pub fn set_shader_dir(dir: PathBuf) -> Result<()> {
+    SHADER_DIR
+        .set(dir)
+        .map_err(|_| eyre!("The shader directory was already set"))
+}
+
+/// Returns the path of a stitched shader file
+pub fn shader_path(file: &str) -> PathBuf {
+    SHADER_DIR
+        .get_or_init(|| PathBuf::from(DEFAULT_SHADER_DIR))
+        .join(file)
+}
+
+/// Sets the directory of the IBL cache, can only be called once
+pub fn set_ibl_cache_dir(dir: Option<PathBuf>) -> Result<()> {
+    IBL_CACHE_DIR
+        .set(dir)
+        .map_err(|_| eyre!("The IBL cache directory was already set"))
+}
+
+/// Returns the directory of the IBL cache, None if the cache is disabled
+pub fn ibl_cache_dir() -> Option<&'static Path> {
+    IBL_CACHE_DIR
+        .get_or_init(|| Some(PathBuf::from(DEFAULT_IBL_CACHE_DIR)))
+        .as_deref()
+}
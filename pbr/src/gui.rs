@@ -1,9 +1,15 @@
-use egui::{CtxRef, DragValue, RichText, Ui};
+use egui::{pos2, vec2, CtxRef, CursorIcon, DragValue, Rect, RichText, Sense, Ui};
+use glam::Vec3;
 
 use crate::{
-    app_settings::{self, MaterialSrc},
+    app_settings::{self, Antialiasing, DebugView, MaterialSrc, SplitView, Tonemapping},
     camera::CameraTyp,
-    resources::Resources,
+    ogl::bindless,
+    renderer::{
+        lighting::{Light, LightType},
+        screenshot::{ScreenshotFormat, MAX_SCREENSHOT_SIZE},
+    },
+    resources::{format_size, LoadResource, LoadStatus, ResourceList, Resources},
     util::RcMut,
     AppSettings,
 };
@@ -20,6 +26,15 @@ impl Gui {
     ///
     /// Immediate mode GUI - is called every frame.
     pub fn create_gui(&mut self, egui_ctx: &mut CtxRef) {
+        // The widgets edit the main settings, so the settings of the right side are swapped in for the frame
+        let editing_right = {
+            let split = &self.app_settings.get().split;
+            split.enabled && split.editing_right
+        };
+        if editing_right {
+            self.app_settings.get_mut().swap_split_side();
+        }
+
         egui::SidePanel::left("left_panel")
             .resizable(true)
             .max_width(400.0)
@@ -34,6 +49,19 @@ impl Gui {
                 self.right_panel(ui);
             });
 
+        egui::Window::new("Lights")
+            .default_width(280.0)
+            .vscroll(true)
+            .show(egui_ctx, |ui| {
+                self.lights_window(ui);
+            });
+
+        egui::Window::new("Render stats")
+            .default_width(240.0)
+            .show(egui_ctx, |ui| {
+                self.stats_window(ui);
+            });
+
         let ppp = egui_ctx.pixels_per_point();
         let rect = egui_ctx.available_rect();
 
@@ -42,6 +70,35 @@ impl Gui {
         app_settings.viewport_dim.min_y = ppp * rect.top();
         app_settings.viewport_dim.width = ppp * rect.width();
         app_settings.viewport_dim.height = ppp * rect.height();
+
+        if editing_right {
+            app_settings.swap_split_side();
+        }
+
+        if app_settings.split.enabled {
+            Self::split_divider(egui_ctx, rect, &mut app_settings.split);
+        }
+    }
+
+    /// Draggable line over the viewport that separates the sides of the split view
+    fn split_divider(egui_ctx: &CtxRef, viewport: Rect, split: &mut SplitView) {
+        const HANDLE_WIDTH: f32 = 8.;
+
+        let x = viewport.left() + viewport.width() * split.divider;
+        egui::Area::new("split_divider")
+            .fixed_pos(pos2(x - HANDLE_WIDTH / 2., viewport.top()))
+            .show(egui_ctx, |ui| {
+                let size = vec2(HANDLE_WIDTH, viewport.height());
+                let (rect, response) = ui.allocate_exact_size(size, Sense::drag());
+                let response = response.on_hover_cursor(CursorIcon::ResizeHorizontal);
+
+                let divider = split.divider + response.drag_delta().x / viewport.width();
+                split.divider = divider.clamp(0., 1.);
+
+                let stroke = ui.visuals().widgets.noninteractive.fg_stroke;
+                ui.painter()
+                    .line_segment([rect.center_top(), rect.center_bottom()], stroke);
+            });
     }
 
     fn right_panel(&mut self, ui: &mut Ui) {
@@ -98,6 +155,22 @@ impl Gui {
             });
         });
 
+        ui.group(|ui| {
+            ui.add(egui::Label::new(
+                RichText::new("Split view").heading().strong(),
+            ));
+            ui.separator();
+
+            let split = &mut app_settings.split;
+            ui.checkbox(&mut split.enabled, "Compare two material settings");
+            ui.add_enabled_ui(split.enabled, |ui| {
+                ui.label("Edit the material source, BRDFs and render settings of:");
+                ui.radio_value(&mut split.editing_right, false, "Left side");
+                ui.radio_value(&mut split.editing_right, true, "Right side");
+                ui.add(egui::Slider::new(&mut split.divider, 0.0..=1.0).text("Divider"));
+            });
+        });
+
         ui.group(|ui| {
             ui.add(egui::Label::new(
                 RichText::new("PBR Material").heading().strong(),
@@ -161,6 +234,13 @@ impl Gui {
                 .pbr_settings
                 .set_energycomp_enabled(energycomp_enabled);
 
+            let max_anisotropy = app_settings.max_anisotropy;
+            ui.add(
+                egui::Slider::new(&mut app_settings.anisotropy, 1.0..=max_anisotropy)
+                    .text("Anisotropic filtering")
+                    .integer(),
+            );
+
             ui.group(|ui| {
                 ui.label("Diffuse BRDF");
                 ui.separator();
@@ -182,9 +262,418 @@ impl Gui {
                     CodWWII.to_str(),
                 );
             });
+
+            ui.group(|ui| {
+                ui.label("Debug view");
+                ui.separator();
+
+                let material_src = app_settings.material_src;
+                let debug_view = &mut app_settings.pbr_settings.debug_view;
+                // The material source could have changed to one that doesn't support the view
+                if !debug_view.is_supported_by(material_src) {
+                    *debug_view = DebugView::None;
+                }
+
+                egui::ComboBox::from_id_source("debug_view")
+                    .selected_text(debug_view.to_str())
+                    .show_ui(ui, |ui| {
+                        for view in DebugView::ALL {
+                            let label =
+                                egui::SelectableLabel::new(*debug_view == view, view.to_str());
+                            if ui
+                                .add_enabled(view.is_supported_by(material_src), label)
+                                .clicked()
+                            {
+                                *debug_view = view;
+                            }
+                        }
+                    });
+            });
+
+            ui.group(|ui| {
+                ui.label("Wireframe");
+                ui.separator();
+
+                let wireframe = &mut app_settings.wireframe;
+                ui.checkbox(&mut wireframe.enabled, "Wireframe overlay");
+                ui.add_enabled_ui(wireframe.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        let mut color = wireframe.color.to_array();
+                        ui.color_edit_button_rgb(&mut color);
+                        wireframe.color = Vec3::from(color);
+
+                        ui.label("Line color");
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut wireframe.line_width, 0.5..=5.0)
+                            .text("Line width (px)"),
+                    );
+                });
+
+                ui.separator();
+
+                ui.checkbox(&mut wireframe.show_normals, "Normals (blue)");
+                ui.checkbox(&mut wireframe.show_tangents, "Tangents (red)");
+                ui.checkbox(&mut wireframe.show_bitangents, "Bitangents (green)");
+                ui.add_enabled(
+                    wireframe.shows_vectors(),
+                    egui::Slider::new(&mut wireframe.vector_length, 0.001..=1.0)
+                        .text("Vector length")
+                        .logarithmic(true),
+                );
+            });
+
+            ui.group(|ui| {
+                ui.label("Shadows");
+                ui.separator();
+
+                let mut shadows_enabled = app_settings.pbr_settings.shadows_enabled();
+                ui.checkbox(&mut shadows_enabled, "Shadows enabled");
+                app_settings
+                    .pbr_settings
+                    .set_shadows_enabled(shadows_enabled);
+
+                ui.add(
+                    egui::Slider::new(&mut app_settings.pbr_settings.pcf_radius, 0..=4)
+                        .text("PCF radius"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut app_settings.pbr_settings.shadow_bias, 0.0..=0.01)
+                        .text("Depth bias")
+                        .logarithmic(true),
+                );
+                ui.add(
+                    egui::Slider::new(&mut app_settings.pbr_settings.normal_bias, 0.0..=0.1)
+                        .text("Normal offset"),
+                );
+            });
+
+            ui.group(|ui| {
+                ui.label("Ambient occlusion");
+                ui.separator();
+
+                let mut ssao_enabled = app_settings.pbr_settings.ssao_enabled();
+                ui.checkbox(&mut ssao_enabled, "SSAO enabled");
+                app_settings.pbr_settings.set_ssao_enabled(ssao_enabled);
+
+                ui.add(
+                    egui::Slider::new(&mut app_settings.pbr_settings.ssao_radius, 0.05..=5.0)
+                        .text("Radius")
+                        .logarithmic(true),
+                );
+                ui.add(
+                    egui::Slider::new(&mut app_settings.pbr_settings.ssao_intensity, 0.1..=4.0)
+                        .text("Intensity"),
+                );
+            });
+
+            ui.group(|ui| {
+                ui.label("Screen-space reflections");
+                ui.separator();
+
+                let mut ssr_enabled = app_settings.pbr_settings.ssr_enabled();
+                ui.checkbox(&mut ssr_enabled, "SSR enabled");
+                app_settings.pbr_settings.set_ssr_enabled(ssr_enabled);
+
+                ui.add(
+                    egui::Slider::new(&mut app_settings.pbr_settings.ssr_max_distance, 0.5..=50.0)
+                        .text("Max distance")
+                        .logarithmic(true),
+                );
+                ui.add(
+                    egui::Slider::new(&mut app_settings.pbr_settings.ssr_thickness, 0.01..=2.0)
+                        .text("Thickness")
+                        .logarithmic(true),
+                );
+            });
+
+            ui.group(|ui| {
+                ui.label("Bloom");
+                ui.separator();
+
+                let bloom = &mut app_settings.bloom;
+                ui.checkbox(&mut bloom.enabled, "Bloom enabled");
+                ui.add(egui::Slider::new(&mut bloom.threshold, 0.0..=10.0).text("Threshold"));
+                ui.add(
+                    egui::Slider::new(&mut bloom.intensity, 0.0..=1.0)
+                        .text("Intensity")
+                        .logarithmic(true),
+                );
+                ui.add(egui::Slider::new(&mut bloom.radius, 0.5..=4.0).text("Radius"));
+            });
+
+            ui.group(|ui| {
+                ui.label("Tonemapping");
+                ui.separator();
+
+                for tonemapping in Tonemapping::ALL {
+                    ui.radio_value(
+                        &mut app_settings.tonemapping,
+                        tonemapping,
+                        tonemapping.to_str(),
+                    );
+                }
+
+                ui.add(
+                    egui::Slider::new(&mut app_settings.exposure, -10.0..=10.0)
+                        .text("Exposure (EV)"),
+                );
+            });
+
+            ui.group(|ui| {
+                ui.label("Anti-aliasing");
+                ui.separator();
+
+                for antialiasing in Antialiasing::ALL {
+                    ui.radio_value(
+                        &mut app_settings.antialiasing,
+                        antialiasing,
+                        antialiasing.to_str(),
+                    );
+                }
+            });
+
+            ui.group(|ui| {
+                ui.label("White furnace test");
+                ui.separator();
+
+                let furnace = &mut app_settings.furnace;
+                ui.checkbox(&mut furnace.enabled, "Uniform white environment");
+                ui.add_enabled(
+                    furnace.enabled,
+                    egui::Slider::new(&mut furnace.range, 0.001..=0.5)
+                        .text("False color range")
+                        .logarithmic(true),
+                );
+                ui.label("Blue loses energy, red gains energy. Use a white base color.");
+            });
+
+            ui.group(|ui| {
+                ui.label("Screenshot");
+                ui.separator();
+
+                let screenshot = &mut app_settings.screenshot;
+
+                ui.horizontal(|ui| {
+                    ui.label("Resolution");
+                    ui.add(
+                        DragValue::new(&mut screenshot.width).clamp_range(1..=MAX_SCREENSHOT_SIZE),
+                    );
+                    ui.label("x");
+                    ui.add(
+                        DragValue::new(&mut screenshot.height).clamp_range(1..=MAX_SCREENSHOT_SIZE),
+                    );
+                });
+
+                for format in ScreenshotFormat::ALL {
+                    if ui
+                        .radio_value(&mut screenshot.format, format, format.to_str())
+                        .changed()
+                    {
+                        screenshot.path.set_extension(format.extension());
+                    }
+                }
+
+                let mut path = screenshot.path.display().to_string();
+                if ui.text_edit_singleline(&mut path).changed() {
+                    screenshot.path = path.into();
+                }
+
+                if ui.button("Save screenshot").clicked() {
+                    screenshot.requested = true;
+                }
+
+                if let Some(status) = &screenshot.status {
+                    ui.label(status);
+                }
+            });
         });
     }
 
+    fn stats_window(&mut self, ui: &mut Ui) {
+        let mut app_settings = self.app_settings.get_mut();
+
+        ui.checkbox(&mut app_settings.depth_prepass, "Depth prepass");
+        ui.checkbox(&mut app_settings.frustum_culling, "Frustum culling");
+        ui.checkbox(&mut app_settings.show_bounds, "Show bounding boxes");
+        ui.add_enabled(
+            bindless::is_supported(),
+            egui::Checkbox::new(&mut app_settings.multi_draw, "Multi-draw indirect"),
+        )
+        .on_disabled_hover_text("Requires GL_ARB_bindless_texture");
+        ui.separator();
+
+        let stats = app_settings.render_stats;
+        ui.label(format!(
+            "Culled primitives: {} / {}",
+            stats.culled_primitives, stats.primitives
+        ));
+        ui.label(format!("Draw calls: {}", stats.draw_calls));
+        ui.label(format!("Shader switches: {}", stats.shader_switches));
+
+        let format_overdraw = |overdraw: Option<f32>| match overdraw {
+            Some(overdraw) => format!("{overdraw:.2}"),
+            None => "not measured".to_string(),
+        };
+
+        ui.label("Shaded fragments per pixel");
+        ui.label(format!(
+            "Without prepass: {}",
+            format_overdraw(stats.overdraw)
+        ));
+        ui.label(format!(
+            "With prepass: {}",
+            format_overdraw(stats.overdraw_prepass)
+        ));
+
+        if let Some(reduction) = stats.overdraw_reduction() {
+            ui.label(format!("Overdraw reduction: {:.1} %", reduction * 100.));
+        }
+    }
+
+    fn lights_window(&mut self, ui: &mut Ui) {
+        let mut app_settings = self.app_settings.get_mut();
+        let lights = &mut app_settings.lights;
+
+        ui.horizontal_wrapped(|ui| {
+            for typ in LightType::ALL {
+                if ui.button(format!("Add {}", typ.to_str())).clicked() {
+                    let mut light = Light::new(typ, Vec3::new(0., 2., 2.));
+                    // Every shadow costs a shadow map, the user opts into them
+                    light.set_casts_shadows(false);
+                    lights.push(light);
+                }
+            }
+        });
+        ui.separator();
+
+        let mut removed = None;
+        for (i, light) in lights.iter_mut().enumerate() {
+            egui::CollapsingHeader::new(format!("{} light {}", light.typ().to_str(), i))
+                .id_source(i)
+                .default_open(true)
+                .show(ui, |ui| {
+                    Self::light_editor(ui, light);
+
+                    if ui.button("Remove").clicked() {
+                        removed = Some(i);
+                    }
+                });
+        }
+
+        if let Some(i) = removed {
+            lights.remove(i);
+        }
+    }
+
+    fn light_editor(ui: &mut Ui, light: &mut Light) {
+        let mut typ = light.typ();
+        ui.horizontal_wrapped(|ui| {
+            for t in LightType::ALL {
+                ui.radio_value(&mut typ, t, t.to_str());
+            }
+        });
+        light.set_typ(typ);
+
+        ui.horizontal(|ui| {
+            let mut color = light.color.to_array();
+            ui.color_edit_button_rgb(&mut color);
+            light.color = Vec3::from(color);
+
+            ui.label("Color");
+        });
+
+        ui.add(
+            egui::Slider::new(&mut light.intensity, 0.0..=1000.0)
+                .text("Intensity")
+                .logarithmic(true),
+        );
+
+        if typ != LightType::Directional {
+            let pos = &mut light.position;
+            ui.label("Position");
+            ui.horizontal(|ui| {
+                ui.add(DragValue::new(&mut pos.x).prefix("x: ").speed(0.05));
+                ui.add(DragValue::new(&mut pos.y).prefix("y: ").speed(0.05));
+                ui.add(DragValue::new(&mut pos.z).prefix("z: ").speed(0.05));
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(
+                    DragValue::new(&mut light.range)
+                        .speed(0.1)
+                        .clamp_range(0.0..=f32::INFINITY),
+                );
+                ui.label("Range (0 is infinite)");
+            });
+        }
+
+        if typ != LightType::Point {
+            let dir = &mut light.direction;
+            ui.label("Direction");
+            ui.horizontal(|ui| {
+                ui.add(DragValue::new(&mut dir.x).prefix("x: ").speed(0.01));
+                ui.add(DragValue::new(&mut dir.y).prefix("y: ").speed(0.01));
+                ui.add(DragValue::new(&mut dir.z).prefix("z: ").speed(0.01));
+            });
+        }
+
+        match typ {
+            LightType::Rect => {
+                ui.add(
+                    egui::Slider::new(&mut light.width, 0.01..=10.0)
+                        .text("Width")
+                        .logarithmic(true),
+                );
+                ui.add(
+                    egui::Slider::new(&mut light.height, 0.01..=10.0)
+                        .text("Height")
+                        .logarithmic(true),
+                );
+            }
+            LightType::Disk => {
+                ui.add(
+                    egui::Slider::new(&mut light.width, 0.01..=10.0)
+                        .text("Diameter")
+                        .logarithmic(true),
+                );
+            }
+            LightType::Tube => {
+                ui.add(
+                    egui::Slider::new(&mut light.width, 0.01..=10.0)
+                        .text("Length")
+                        .logarithmic(true),
+                );
+                ui.add(
+                    egui::Slider::new(&mut light.height, 0.001..=1.0)
+                        .text("Diameter")
+                        .logarithmic(true),
+                );
+            }
+            LightType::Point | LightType::Directional | LightType::Spot => (),
+        }
+
+        if typ == LightType::Spot {
+            let mut inner = light.inner_cone_angle();
+            let mut outer = light.outer_cone_angle();
+
+            ui.horizontal(|ui| {
+                ui.drag_angle(&mut inner);
+                ui.label("Inner cone angle");
+            });
+            ui.horizontal(|ui| {
+                ui.drag_angle(&mut outer);
+                ui.label("Outer cone angle");
+            });
+
+            light.set_cone_angles(inner, outer);
+        }
+
+        let mut casts_shadows = light.casts_shadows();
+        ui.checkbox(&mut casts_shadows, "Casts shadows");
+        light.set_casts_shadows(casts_shadows);
+    }
+
     fn right_panel_material_override(
         ui: &mut Ui,
         app_settings: &mut std::cell::RefMut<AppSettings>,
@@ -264,16 +753,13 @@ impl Gui {
             ui.add(egui::Label::new(RichText::new("Scenes").heading().strong()));
             ui.separator();
 
-            egui::ScrollArea::vertical()
-                .max_height(height)
-                .id_source("scenes_scroll")
-                .show(ui, |ui| {
-                    for (i, scene) in resources.scenes.iter().enumerate() {
-                        if ui.button(scene.name()).clicked() {
-                            app_settings.selected_scene = i;
-                        }
-                    }
-                });
+            Self::resource_list(
+                ui,
+                &mut resources.scenes,
+                &mut app_settings.selected_scene,
+                height,
+                "scenes_scroll",
+            );
         });
 
         ui.group(|ui| {
@@ -282,16 +768,13 @@ impl Gui {
             ));
             ui.separator();
 
-            egui::ScrollArea::vertical()
-                .max_height(height)
-                .id_source("envmaps_scroll")
-                .show(ui, |ui| {
-                    for (i, envmap) in resources.envmaps.iter().enumerate() {
-                        if ui.button(envmap.name()).clicked() {
-                            app_settings.selected_envmap = i;
-                        }
-                    }
-                });
+            Self::resource_list(
+                ui,
+                &mut resources.envmaps,
+                &mut app_settings.selected_envmap,
+                height,
+                "envmaps_scroll",
+            );
         });
 
         ui.group(|ui| {
@@ -300,16 +783,13 @@ impl Gui {
             ));
             ui.separator();
 
-            egui::ScrollArea::vertical()
-                .max_height(height)
-                .id_source("merl_scroll")
-                .show(ui, |ui| {
-                    for (i, brdf) in resources.merl_brdfs.iter().enumerate() {
-                        if ui.button(brdf.name()).clicked() {
-                            app_settings.selected_merl_brdf = i;
-                        }
-                    }
-                });
+            Self::resource_list(
+                ui,
+                &mut resources.merl_brdfs,
+                &mut app_settings.selected_merl_brdf,
+                height,
+                "merl_scroll",
+            );
         });
 
         ui.group(|ui| {
@@ -318,20 +798,103 @@ impl Gui {
             ));
             ui.separator();
 
-            egui::ScrollArea::vertical()
-                .max_height(height)
-                .id_source("utia_scroll")
-                .show(ui, |ui| {
-                    for (i, brdf) in resources.utia_brdfs.iter().enumerate() {
-                        if ui.button(brdf.name()).clicked() {
-                            app_settings.selected_utia_brdf = i;
+            Self::resource_list(
+                ui,
+                &mut resources.utia_brdfs,
+                &mut app_settings.selected_utia_brdf,
+                height,
+                "utia_scroll",
+            );
+        });
+
+        ui.group(|ui| {
+            ui.add(egui::Label::new(RichText::new("Memory").heading().strong()));
+            ui.separator();
+
+            let used = resources.memory_size();
+            let budget = resources.memory_budget;
+            ui.add(
+                egui::ProgressBar::new(used as f32 / budget.max(1) as f32).text(format!(
+                    "{} / {}",
+                    format_size(used),
+                    format_size(budget)
+                )),
+            );
+
+            ui.label(format!(
+                "Scenes: {}",
+                format_size(resources.scenes.memory_size())
+            ));
+            ui.label(format!(
+                "Environment maps: {}",
+                format_size(resources.envmaps.memory_size())
+            ));
+            ui.label(format!(
+                "MERL BRDFs: {}",
+                format_size(resources.merl_brdfs.memory_size())
+            ));
+            ui.label(format!(
+                "UTIA BRDFs: {}",
+                format_size(resources.utia_brdfs.memory_size())
+            ));
+
+            let mut budget_mib = budget / (1024 * 1024);
+            ui.horizontal(|ui| {
+                ui.label("Budget");
+                ui.add(
+                    DragValue::new(&mut budget_mib)
+                        .suffix(" MiB")
+                        .speed(16)
+                        .clamp_range(64..=usize::MAX),
+                );
+            });
+            resources.memory_budget = budget_mib * 1024 * 1024;
+
+            if ui.button("Unload resources").clicked() {
+                resources.unload();
+            }
+        });
+    }
+
+    /// Shows a button for each resource, with a progress bar if the resource is being loaded
+    fn resource_list<T: LoadResource>(
+        ui: &mut Ui,
+        list: &mut ResourceList<T>,
+        selected: &mut usize,
+        height: f32,
+        id: &str,
+    ) {
+        egui::ScrollArea::vertical()
+            .max_height(height)
+            .id_source(id)
+            .show(ui, |ui| {
+                for (i, res) in list.iter_mut().enumerate() {
+                    let clicked = ui.button(res.name()).clicked();
+
+                    match res.status() {
+                        LoadStatus::Loading(progress) => {
+                            ui.add(
+                                egui::ProgressBar::new(progress)
+                                    .desired_width(150.)
+                                    .show_percentage()
+                                    .animate(true),
+                            );
                         }
+                        LoadStatus::Failed(e) => {
+                            ui.colored_label(egui::Color32::RED, format!("Failed: {e}"));
+                        }
+                        LoadStatus::Unloaded | LoadStatus::Loaded => (),
                     }
-                });
-        });
 
-        if ui.button("Unload resources").clicked() {
-            resources.unload();
-        }
+                    if clicked {
+                        // Clicking on a resource that failed to load tries to load it again
+                        if matches!(res.status(), LoadStatus::Failed(_)) {
+                            res.unload();
+                        }
+
+                        *selected = i;
+                    }
+                }
+            });
     }
 }
@@ -0,0 +1,69 @@
+use eyre::{eyre, Result};
+
+use crate::{
+    app_settings::{AppSettings, MaterialSrc, ViewportDim},
+    camera::FixedCamera,
+    config::{RenderArgs, ResourceRoots},
+    ogl,
+    renderer::{screenshot::ScreenshotFormat, RenderCtx, Renderer},
+    resources::{LoadResource, ResourceList, Resources},
+};
+
+mod egl;
+
+/// Renders a single image without a window and saves it, used for batch jobs on machines without a display
+pub fn render(args: RenderArgs, roots: ResourceRoots, memory_budget: usize) -> Result<()> {
+    let context = egl::SurfacelessContext::new()?;
+
+    gl::load_with(|name| context.get_proc_address(name));
+    ogl::bindless::load_with(|name| context.get_proc_address(name));
+    ogl::init_debug();
+
+    let mut app_settings = AppSettings::new(ViewportDim::new(args.width, args.height));
+    let mut resources = Resources::init(roots, memory_budget)?;
+
+    app_settings.selected_scene = load_resource(&mut resources.scenes, &args.scene)?;
+    app_settings.selected_envmap = load_resource(&mut resources.envmaps, &args.envmap)?;
+    app_settings.material_src = args.material_src;
+
+    let brdf = || {
+        args.brdf
+            .as_deref()
+            .ok_or_else(|| eyre!("The measured material sources require a --brdf"))
+    };
+
+    match args.material_src {
+        MaterialSrc::MerlBrdf => {
+            app_settings.selected_merl_brdf = load_resource(&mut resources.merl_brdfs, brdf()?)?;
+        }
+        MaterialSrc::UtiaBrdf => {
+            app_settings.selected_utia_brdf = load_resource(&mut resources.utia_brdfs, brdf()?)?;
+        }
+        MaterialSrc::Gltf | MaterialSrc::PbrOverride => (),
+    }
+
+    let mut renderer = Renderer::new()?;
+    let mut camera = FixedCamera::new(args.camera_pos, args.camera_target);
+
+    let mut rctx = RenderCtx {
+        app_settings: &mut app_settings,
+        res: &mut resources,
+        camera: &mut camera,
+    };
+
+    let screenshot = renderer.render_screenshot(&mut rctx, args.width, args.height)?;
+    screenshot.save(&args.output, ScreenshotFormat::from_path(&args.output))?;
+
+    println!("Saved '{}'", args.output.display());
+    Ok(())
+}
+
+/// Finds the resource by its path or name and waits until it's loaded
+fn load_resource<T: LoadResource>(list: &mut ResourceList<T>, resource: &str) -> Result<usize> {
+    let index = list
+        .find(resource)
+        .ok_or_else(|| eyre!("Couldn't find the resource '{resource}'"))?;
+
+    list.load_blocking(index)?;
+    Ok(index)
+}
@@ -0,0 +1,253 @@
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    mem, ptr,
+};
+
+use cstr::cstr;
+use eyre::{eyre, Result};
+
+type EglDisplay = *mut c_void;
+type EglConfig = *mut c_void;
+type EglContext = *mut c_void;
+type EglSurface = *mut c_void;
+type EglBoolean = u32;
+
+const EGL_NONE: i32 = 0x3038;
+const EGL_EXTENSIONS: i32 = 0x3055;
+const EGL_RENDERABLE_TYPE: i32 = 0x3040;
+const EGL_OPENGL_BIT: i32 = 0x0008;
+const EGL_OPENGL_API: u32 = 0x30A2;
+const EGL_CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: i32 = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: i32 = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: i32 = 0x0001;
+const EGL_CONTEXT_OPENGL_DEBUG: i32 = 0x31B0;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+const EGL_TRUE: i32 = 1;
+
+/// The entry points are loaded at runtime, so that libEGL is only needed in the headless mode
+struct EglFns {
+    get_proc_address: extern "C" fn(*const c_char) -> *const c_void,
+    get_display: extern "C" fn(*mut c_void) -> EglDisplay,
+    get_platform_display_ext: Option<extern "C" fn(u32, *mut c_void, *const i32) -> EglDisplay>,
+    initialize: extern "C" fn(EglDisplay, *mut i32, *mut i32) -> EglBoolean,
+    terminate: extern "C" fn(EglDisplay) -> EglBoolean,
+    query_string: extern "C" fn(EglDisplay, i32) -> *const c_char,
+    bind_api: extern "C" fn(u32) -> EglBoolean,
+    choose_config:
+        extern "C" fn(EglDisplay, *const i32, *mut EglConfig, i32, *mut i32) -> EglBoolean,
+    create_context: extern "C" fn(EglDisplay, EglConfig, EglContext, *const i32) -> EglContext,
+    destroy_context: extern "C" fn(EglDisplay, EglContext) -> EglBoolean,
+    make_current: extern "C" fn(EglDisplay, EglSurface, EglSurface, EglContext) -> EglBoolean,
+    get_error: extern "C" fn() -> i32,
+}
+
+/// An OpenGL 4.6 core context without any surface.
+///
+/// Uses the surfaceless platform of Mesa when it's available, so it works without a display server
+/// and with the software rasterizer (LIBGL_ALWAYS_SOFTWARE=1).
+pub struct SurfacelessContext {
+    library: *mut c_void,
+    fns: EglFns,
+    display: EglDisplay,
+    context: EglContext,
+}
+
+impl SurfacelessContext {
+    pub fn new() -> Result<Self> {
+        let library = unsafe { libc::dlopen(cstr!("libEGL.so.1").as_ptr(), libc::RTLD_NOW) };
+        if library.is_null() {
+            return Err(eyre!("Couldn't load libEGL.so.1"));
+        }
+
+        let fns = match unsafe { Self::load_fns(library) } {
+            Ok(fns) => fns,
+            Err(e) => {
+                unsafe { libc::dlclose(library) };
+                return Err(e);
+            }
+        };
+
+        // Dropping the partially created context releases everything if one of the steps fails
+        let mut ctx = Self {
+            library,
+            fns,
+            display: ptr::null_mut(),
+            context: ptr::null_mut(),
+        };
+
+        ctx.create()?;
+        Ok(ctx)
+    }
+
+    unsafe fn load_fns(library: *mut c_void) -> Result<EglFns> {
+        unsafe fn sym<T>(library: *mut c_void, name: &CStr) -> Result<T> {
+            let ptr = libc::dlsym(library, name.as_ptr());
+            if ptr.is_null() {
+                return Err(eyre!("libEGL doesn't export {name:?}"));
+            }
+
+            Ok(mem::transmute_copy::<*mut c_void, T>(&ptr))
+        }
+
+        let get_proc_address: extern "C" fn(*const c_char) -> *const c_void =
+            sym(library, cstr!("eglGetProcAddress"))?;
+
+        let platform_display = get_proc_address(cstr!("eglGetPlatformDisplayEXT").as_ptr());
+        let get_platform_display_ext = (!platform_display.is_null())
+            .then(|| mem::transmute_copy::<*const c_void, _>(&platform_display));
+
+        Ok(EglFns {
+            get_proc_address,
+            get_display: sym(library, cstr!("eglGetDisplay"))?,
+            get_platform_display_ext,
+            initialize: sym(library, cstr!("eglInitialize"))?,
+            terminate: sym(library, cstr!("eglTerminate"))?,
+            query_string: sym(library, cstr!("eglQueryString"))?,
+            bind_api: sym(library, cstr!("eglBindAPI"))?,
+            choose_config: sym(library, cstr!("eglChooseConfig"))?,
+            create_context: sym(library, cstr!("eglCreateContext"))?,
+            destroy_context: sym(library, cstr!("eglDestroyContext"))?,
+            make_current: sym(library, cstr!("eglMakeCurrent"))?,
+            get_error: sym(library, cstr!("eglGetError"))?,
+        })
+    }
+
+    fn create(&mut self) -> Result<()> {
+        let fns = &self.fns;
+
+        self.display = match fns.get_platform_display_ext {
+            // Client extensions are queried without a display
+            Some(get_platform_display)
+                if self.has_extension(ptr::null_mut(), "EGL_MESA_platform_surfaceless") =>
+            {
+                get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null())
+            }
+            _ => (fns.get_display)(ptr::null_mut()),
+        };
+
+        if self.display.is_null() {
+            return Err(self.error("eglGetDisplay"));
+        }
+
+        let (mut major, mut minor) = (0, 0);
+        if (fns.initialize)(self.display, &mut major, &mut minor) == 0 {
+            // Terminating a display that wasn't initialized is an error
+            self.display = ptr::null_mut();
+            return Err(self.error("eglInitialize"));
+        }
+
+        if (fns.bind_api)(EGL_OPENGL_API) == 0 {
+            return Err(self.error("eglBindAPI"));
+        }
+
+        // There is no surface, so the context doesn't need a config if the display supports it
+        let config = if self.has_extension(self.display, "EGL_KHR_no_config_context") {
+            ptr::null_mut()
+        } else {
+            self.choose_config()?
+        };
+
+        let context_attribs = [
+            EGL_CONTEXT_MAJOR_VERSION,
+            4,
+            EGL_CONTEXT_MINOR_VERSION,
+            6,
+            EGL_CONTEXT_OPENGL_PROFILE_MASK,
+            EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            EGL_CONTEXT_OPENGL_DEBUG,
+            EGL_TRUE,
+            EGL_NONE,
+        ];
+        self.context = (fns.create_context)(
+            self.display,
+            config,
+            ptr::null_mut(),
+            context_attribs.as_ptr(),
+        );
+        if self.context.is_null() {
+            return Err(self.error("eglCreateContext (OpenGL 4.6 core is required)"));
+        }
+
+        // Requires EGL_KHR_surfaceless_context
+        let current =
+            (fns.make_current)(self.display, ptr::null_mut(), ptr::null_mut(), self.context);
+        if current == 0 {
+            return Err(self.error("eglMakeCurrent"));
+        }
+
+        println!("Created a surfaceless EGL {major}.{minor} context");
+        Ok(())
+    }
+
+    fn choose_config(&self) -> Result<EglConfig> {
+        let config_attribs = [EGL_RENDERABLE_TYPE, EGL_OPENGL_BIT, EGL_NONE];
+        let mut config = ptr::null_mut();
+        let mut num_configs = 0;
+
+        let chosen = (self.fns.choose_config)(
+            self.display,
+            config_attribs.as_ptr(),
+            &mut config,
+            1,
+            &mut num_configs,
+        );
+
+        match chosen != 0 && num_configs > 0 {
+            true => Ok(config),
+            false => Err(self.error("eglChooseConfig")),
+        }
+    }
+
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        match CString::new(name) {
+            Ok(name) => (self.fns.get_proc_address)(name.as_ptr()),
+            Err(_) => ptr::null(),
+        }
+    }
+
+    fn has_extension(&self, display: EglDisplay, name: &str) -> bool {
+        let extensions = (self.fns.query_string)(display, EGL_EXTENSIONS);
+        if extensions.is_null() {
+            return false;
+        }
+
+        let extensions = unsafe { CStr::from_ptr(extensions) };
+        extensions
+            .to_string_lossy()
+            .split_ascii_whitespace()
+            .any(|e| e == name)
+    }
+
+    fn error(&self, function: &str) -> eyre::Report {
+        eyre!(
+            "{function} failed with EGL error {:#x}",
+            (self.fns.get_error)()
+        )
+    }
+}
+
+impl Drop for SurfacelessContext {
+    fn drop(&mut self) {
+        let fns = &self.fns;
+
+        if !self.display.is_null() {
+            (fns.make_current)(
+                self.display,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+
+            if !self.context.is_null() {
+                (fns.destroy_context)(self.display, self.context);
+            }
+
+            (fns.terminate)(self.display);
+        }
+
+        unsafe {
+            libc::dlclose(self.library);
+        }
+    }
+}
@@ -1,8 +1,9 @@
 //! PBR experiments
 //!
 //! `main` function is the entry-point
-use app_settings::AppSettings;
+use app_settings::{AppSettings, ViewportDim};
 use camera::{Camera, CameraTyp, Flycam, Orbitalcam};
+use config::Config;
 use eyre::Result;
 use glam::Vec3;
 use gui::Gui;
@@ -19,6 +20,9 @@ mod app_settings;
 /// A module for working with a basic free camera.
 mod camera;
 
+/// Startup configuration from the command line and the config file.
+mod config;
+
 /// All of the code for drawing the GUI using egui.
 mod gui;
 
@@ -34,6 +38,10 @@ mod ogl;
 /// Handles window creation and egui boilerplate.
 mod window;
 
+/// Renders images without a window using a surfaceless EGL context.
+#[cfg(unix)]
+mod headless;
+
 mod resources;
 
 mod util;
@@ -42,13 +50,32 @@ mod brdf_raw;
 
 /// Creates the window, configures OpenGL, sets up the scene and begins the render loop.
 fn main() -> Result<()> {
+    let config = Config::load()?;
+    config::set_shader_dir(config.shader_dir)?;
+    config::set_ibl_cache_dir(config.ibl_cache_dir)?;
+
+    if let Some(args) = config.render {
+        #[cfg(unix)]
+        return headless::render(args, config.resources, config.memory_budget);
+
+        #[cfg(not(unix))]
+        {
+            let _ = args;
+            return Err(eyre::eyre!(
+                "Headless rendering requires EGL, which is only supported on Unix-like systems"
+            ));
+        }
+    }
+
     let mut window = AppWindow::new("Physically Based Rendering - Tomáš Král")?;
 
     gl::load_with(|name| window.window.subsystem().gl_get_proc_address(name) as _);
+    ogl::bindless::load_with(|name| window.window.subsystem().gl_get_proc_address(name) as _);
     ogl::init_debug();
 
-    let app_settings = RcMut::new(AppSettings::new(&window));
-    let resources = RcMut::new(Resources::init()?);
+    let viewport_dim = ViewportDim::new(window.width, window.height);
+    let app_settings = RcMut::new(AppSettings::new(viewport_dim));
+    let resources = RcMut::new(Resources::init(config.resources, config.memory_budget)?);
     let mut renderer = Renderer::new()?;
 
     let mut gui_ctx = Gui {
@@ -80,6 +107,10 @@ fn main() -> Result<()> {
 
         handle_inputs(&mut window.event_pump, active_cam);
 
+        resources
+            .get_mut()
+            .reload_changed(&mut app_settings.get_mut());
+
         {
             let mut rctx = RenderCtx {
                 app_settings: &mut app_settings.get_mut(),
@@ -90,6 +121,8 @@ fn main() -> Result<()> {
             renderer.render(&mut rctx)?;
         }
 
+        resources.get_mut().enforce_budget();
+
         gui_ctx.create_gui(&mut window.egui_ctx);
 
         let should_quit = window.end_frame();
@@ -4,8 +4,14 @@ use std::{
     time::Duration,
 };
 
+/// Loading of the bindless texture extension
+pub mod bindless;
+/// Abstraction for framebuffer objects
+pub mod framebuffer;
 /// Abstraction for ordinary buffers
 pub mod gl_buffer;
+/// Abstraction for sampler objects
+pub mod sampler;
 /// Abstraction for working with OpenGL Shaders.
 pub mod shader;
 /// Abstraction for shader storage buffer objects (SSBOs)
@@ -22,6 +28,8 @@ pub type ProgramId = u32;
 pub type ShaderId = u32;
 pub type BufferId = u32;
 pub type VaoId = u32;
+pub type SamplerId = u32;
+pub type FramebufferId = u32;
 
 pub fn gl_time_query<R, F: FnOnce() -> R>(label: &str, fun: F) -> R {
     let mut query_id = 0;
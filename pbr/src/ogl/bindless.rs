@@ -0,0 +1,84 @@
+use std::{
+    ffi::{c_void, CStr},
+    mem,
+    sync::OnceLock,
+};
+
+/// The gl crate is generated without extensions, so the ARB_bindless_texture functions are loaded here
+type GetTextureSamplerHandle = extern "system" fn(u32, u32) -> u64;
+type MakeTextureHandleResident = extern "system" fn(u64);
+type IsTextureHandleResident = extern "system" fn(u64) -> u8;
+
+struct BindlessFns {
+    get_texture_sampler_handle: GetTextureSamplerHandle,
+    make_texture_handle_resident: MakeTextureHandleResident,
+    is_texture_handle_resident: IsTextureHandleResident,
+}
+
+static BINDLESS: OnceLock<Option<BindlessFns>> = OnceLock::new();
+
+/// Loads the functions if the extension is supported, has to be called after gl::load_with
+pub fn load_with(loader: impl FnMut(&'static str) -> *const c_void) {
+    let fns = match has_extension("GL_ARB_bindless_texture") {
+        true => load_fns(loader),
+        false => None,
+    };
+
+    let _ = BINDLESS.set(fns);
+}
+
+fn load_fns(mut loader: impl FnMut(&'static str) -> *const c_void) -> Option<BindlessFns> {
+    let mut load = |name| {
+        let ptr = loader(name);
+        (!ptr.is_null()).then_some(ptr)
+    };
+
+    let get_handle = load("glGetTextureSamplerHandleARB")?;
+    let make_resident = load("glMakeTextureHandleResidentARB")?;
+    let is_resident = load("glIsTextureHandleResidentARB")?;
+
+    unsafe {
+        Some(BindlessFns {
+            get_texture_sampler_handle: mem::transmute::<*const c_void, GetTextureSamplerHandle>(
+                get_handle,
+            ),
+            make_texture_handle_resident: mem::transmute::<*const c_void, MakeTextureHandleResident>(
+                make_resident,
+            ),
+            is_texture_handle_resident: mem::transmute::<*const c_void, IsTextureHandleResident>(
+                is_resident,
+            ),
+        })
+    }
+}
+
+pub fn is_supported() -> bool {
+    matches!(BINDLESS.get(), Some(Some(_)))
+}
+
+/// Returns a resident handle of the texture and sampler pair.
+///
+/// The parameters of both the texture and the sampler can't be changed after the handle is created.
+pub fn texture_sampler_handle(texture: u32, sampler: u32) -> Option<u64> {
+    let fns = BINDLESS.get()?.as_ref()?;
+
+    let handle = (fns.get_texture_sampler_handle)(texture, sampler);
+    // The same pair always returns the same handle, which can't be made resident twice
+    if (fns.is_texture_handle_resident)(handle) == gl::FALSE {
+        (fns.make_texture_handle_resident)(handle);
+    }
+
+    Some(handle)
+}
+
+fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+
+    (0..count as u32).any(|i| {
+        let ext = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+        !ext.is_null() && unsafe { CStr::from_ptr(ext as _) }.to_bytes() == name.as_bytes()
+    })
+}
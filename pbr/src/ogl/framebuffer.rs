@@ -0,0 +1,110 @@
+use std::ffi::CStr;
+
+use eyre::{eyre, Result};
+use gl::types::GLenum;
+
+use super::{texture::GlTexture, FramebufferId};
+
+/// Framebuffer object for rendering into textures
+pub struct GlFramebuffer {
+    pub id: FramebufferId,
+}
+
+impl GlFramebuffer {
+    pub fn new() -> Self {
+        let mut id: u32 = 0;
+
+        unsafe {
+            gl::CreateFramebuffers(1, &mut id);
+        }
+
+        Self { id }
+    }
+
+    pub fn attach_texture(&self, attachment: GLenum, texture: &GlTexture, level: i32) {
+        unsafe {
+            gl::NamedFramebufferTexture(self.id, attachment, texture.id, level);
+        }
+    }
+
+    /// Attaches a single layer of an array texture (or a single face of a cubemap array)
+    pub fn attach_texture_layer(
+        &self,
+        attachment: GLenum,
+        texture: &GlTexture,
+        level: i32,
+        layer: i32,
+    ) {
+        unsafe {
+            gl::NamedFramebufferTextureLayer(self.id, attachment, texture.id, level, layer);
+        }
+    }
+
+    /// Framebuffers without color attachments, for example for rendering shadow maps
+    pub fn disable_color(&self) {
+        unsafe {
+            gl::NamedFramebufferDrawBuffer(self.id, gl::NONE);
+            gl::NamedFramebufferReadBuffer(self.id, gl::NONE);
+        }
+    }
+
+    pub fn check_status(&self) -> Result<()> {
+        let status = unsafe { gl::CheckNamedFramebufferStatus(self.id, gl::FRAMEBUFFER) };
+
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(eyre!("Framebuffer is incomplete: {status:#x}"))
+        }
+    }
+
+    /// Copies the whole area of the buffers selected by the mask, resolves multisampled buffers
+    pub fn blit_to(&self, dst: &GlFramebuffer, width: i32, height: i32, mask: u32) {
+        unsafe {
+            gl::BlitNamedFramebuffer(
+                self.id,
+                dst.id,
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                mask,
+                gl::NEAREST,
+            );
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    /// Binds the default framebuffer
+    pub fn unbind() {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    pub fn add_label(&self, label: &CStr) {
+        unsafe {
+            gl::ObjectLabel(
+                gl::FRAMEBUFFER,
+                self.id,
+                label.to_bytes().len() as _,
+                label.as_ptr(),
+            );
+        }
+    }
+}
+
+impl Drop for GlFramebuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.id) }
+    }
+}
@@ -18,6 +18,18 @@ impl GlBuffer {
 
         Self { id }
     }
+
+    /// Allocates an uninitialized buffer, it can only be written to by the GPU
+    pub fn with_size(size: usize) -> Self {
+        let mut id: u32 = 0;
+
+        unsafe {
+            gl::CreateBuffers(1, &mut id);
+            gl::NamedBufferStorage(id, size as isize, std::ptr::null(), 0);
+        }
+
+        Self { id }
+    }
 }
 
 impl Drop for GlBuffer {
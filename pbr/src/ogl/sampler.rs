@@ -0,0 +1,84 @@
+use gl::types::GLenum;
+
+use super::SamplerId;
+
+/// Sampler state that is separate from the texture images.
+///
+/// A sampler bound to a texture unit overrides the sampling parameters of the texture.
+pub struct GlSampler {
+    pub id: SamplerId,
+}
+
+impl GlSampler {
+    pub fn new(min_filter: GLenum, mag_filter: GLenum, wrap_s: GLenum, wrap_t: GLenum) -> Self {
+        let mut id: u32 = 0;
+
+        unsafe {
+            gl::CreateSamplers(1, &mut id);
+
+            gl::SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
+            gl::SamplerParameteri(id, gl::TEXTURE_WRAP_S, wrap_s as i32);
+            gl::SamplerParameteri(id, gl::TEXTURE_WRAP_T, wrap_t as i32);
+        }
+
+        Self { id }
+    }
+
+    /// A new sampler with the same filtering and wrapping, but with a different anisotropy
+    pub fn with_anisotropy(&self, anisotropy: f32) -> Self {
+        let param = |pname| {
+            let mut value = 0;
+            unsafe {
+                gl::GetSamplerParameteriv(self.id, pname, &mut value);
+            }
+            value as GLenum
+        };
+
+        let sampler = Self::new(
+            param(gl::TEXTURE_MIN_FILTER),
+            param(gl::TEXTURE_MAG_FILTER),
+            param(gl::TEXTURE_WRAP_S),
+            param(gl::TEXTURE_WRAP_T),
+        );
+        sampler.set_anisotropy(anisotropy);
+        sampler
+    }
+
+    /// 1.0 disables anisotropic filtering
+    pub fn set_anisotropy(&self, anisotropy: f32) {
+        unsafe {
+            gl::SamplerParameterf(self.id, gl::TEXTURE_MAX_ANISOTROPY, anisotropy);
+        }
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, self.id);
+        }
+    }
+
+    /// Unbinds samplers from all of the units in the range, the textures use their own parameters again
+    pub fn unbind_range(first_unit: u32, count: u32) {
+        unsafe {
+            gl::BindSamplers(first_unit, count as _, std::ptr::null());
+        }
+    }
+}
+
+impl Drop for GlSampler {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteSamplers(1, &self.id) }
+    }
+}
+
+/// Maximum anisotropy supported by the implementation
+pub fn max_anisotropy() -> f32 {
+    let mut max = 1.;
+
+    unsafe {
+        gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+    }
+
+    max
+}
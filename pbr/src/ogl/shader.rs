@@ -1,7 +1,7 @@
 use eyre::{eyre, Context, Result};
 use gl::types::GLenum;
-use glam::{Mat4, Vec3, Vec4};
-use std::{ffi::CStr, fs, ptr};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use std::{ffi::CStr, fs, path::Path, ptr};
 
 use super::{ProgramId, ShaderId};
 
@@ -35,16 +35,43 @@ impl Shader {
     }
 
     /// Loads a vertex shader and a fragment shader from specified paths and tries to create a shader program.
-    pub fn with_files(vs_path: &str, fs_path: &str) -> Result<Shader> {
-        let vs_src =
-            String::from_utf8(fs::read(vs_path).wrap_err("Couldn't load the vertex shader file")?)?;
-        let fs_src = String::from_utf8(
-            fs::read(fs_path).wrap_err("Couldn't load the fragment shader file")?,
-        )?;
+    pub fn with_files(vs_path: impl AsRef<Path>, fs_path: impl AsRef<Path>) -> Result<Shader> {
+        let vs_src = read_src(vs_path.as_ref(), "vertex")?;
+        let fs_src = read_src(fs_path.as_ref(), "fragment")?;
 
         Self::with_src_defines(vs_src, &[], fs_src, &[])
     }
 
+    /// Same as with_files, but the vertex shader is compiled with the defines
+    pub fn with_files_vs_defines(
+        vs_path: impl AsRef<Path>,
+        vs_defines: &[&str],
+        fs_path: impl AsRef<Path>,
+    ) -> Result<Shader> {
+        let vs_src = read_src(vs_path.as_ref(), "vertex")?;
+        let fs_src = read_src(fs_path.as_ref(), "fragment")?;
+
+        Self::with_src_defines(vs_src, vs_defines, fs_src, &[])
+    }
+
+    /// Same as with_files, but with a geometry shader between the vertex and the fragment shader
+    pub fn with_files_geometry(
+        vs_path: impl AsRef<Path>,
+        gs_path: impl AsRef<Path>,
+        fs_path: impl AsRef<Path>,
+    ) -> Result<Shader> {
+        let mut vs_src = read_src(vs_path.as_ref(), "vertex")?;
+        let mut gs_src = read_src(gs_path.as_ref(), "geometry")?;
+        let mut fs_src = read_src(fs_path.as_ref(), "fragment")?;
+
+        let vs = Self::compile_shader(&mut vs_src, &[], gl::VERTEX_SHADER)?;
+        let gs = Self::compile_shader(&mut gs_src, &[], gl::GEOMETRY_SHADER)?;
+        let fs = Self::compile_shader(&mut fs_src, &[], gl::FRAGMENT_SHADER)?;
+
+        let program_id = Self::create_shader_program(&[vs, gs, fs])?;
+        Ok(Shader { program_id })
+    }
+
     /// Tries to compile vertex and fragment shaders from respective paths with respective defines and
     /// create a shader program from them.
     pub fn with_src_defines(
@@ -61,10 +88,8 @@ impl Shader {
     }
 
     /// Creates a new compute shader from the path
-    pub fn comp_with_path_defines(comp_path: &str, defines: &[&str]) -> Result<Self> {
-        let mut comp_src = String::from_utf8(
-            fs::read(comp_path).wrap_err("Couldn't load the compute shader file")?,
-        )?;
+    pub fn comp_with_path_defines(comp_path: impl AsRef<Path>, defines: &[&str]) -> Result<Self> {
+        let mut comp_src = read_src(comp_path.as_ref(), "compute")?;
 
         // Add null terminator !
         comp_src.push('\0');
@@ -76,7 +101,7 @@ impl Shader {
     }
 
     /// Creates a new compute shader from the path
-    pub fn comp_with_path(comp_path: &str) -> Result<Self> {
+    pub fn comp_with_path(comp_path: impl AsRef<Path>) -> Result<Self> {
         Self::comp_with_path_defines(comp_path, &[])
     }
 
@@ -205,6 +230,14 @@ impl Shader {
         }
     }
 
+    #[allow(unused)]
+    pub fn set_vec2(&self, vec: Vec2, name: &CStr) {
+        unsafe {
+            let loc = gl::GetUniformLocation(self.program_id, name.as_ptr() as _);
+            gl::Uniform2f(loc, vec.x, vec.y);
+        }
+    }
+
     #[allow(unused)]
     pub fn set_vec3(&self, vec: Vec3, name: &CStr) {
         unsafe {
@@ -251,3 +284,11 @@ impl Drop for Shader {
         unsafe { gl::DeleteProgram(self.program_id) }
     }
 }
+
+/// Reads the source of a shader, `kind` is only used in the error message
+pub(super) fn read_src(path: &Path, kind: &str) -> Result<String> {
+    let src = fs::read(path)
+        .wrap_err_with(|| format!("Couldn't load the {kind} shader file '{}'", path.display()))?;
+
+    Ok(String::from_utf8(src)?)
+}
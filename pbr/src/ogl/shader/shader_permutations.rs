@@ -1,8 +1,8 @@
-use std::{collections::HashMap, fs, hash::Hash};
+use std::{collections::HashMap, hash::Hash, path::Path};
 
-use eyre::{Context, Result};
+use eyre::Result;
 
-use super::Shader;
+use super::{read_src, Shader};
 
 /// A module for handling the permutations of a shader.
 ///
@@ -18,13 +18,9 @@ pub struct ShaderPermutations<T: ShaderDefines> {
 }
 
 impl<T: ShaderDefines> ShaderPermutations<T> {
-    pub fn new(vs_path: &str, fs_path: &str) -> Result<Self> {
-        let vs_src =
-            String::from_utf8(fs::read(vs_path).wrap_err("Couldn't load the vertex shader file")?)?;
-
-        let fs_src = String::from_utf8(
-            fs::read(fs_path).wrap_err("Couldn't load the fragment shader file")?,
-        )?;
+    pub fn new(vs_path: impl AsRef<Path>, fs_path: impl AsRef<Path>) -> Result<Self> {
+        let vs_src = read_src(vs_path.as_ref(), "vertex")?;
+        let fs_src = read_src(fs_path.as_ref(), "fragment")?;
 
         Ok(Self {
             permutations: HashMap::new(),
@@ -43,12 +39,10 @@ impl<T: ShaderDefines> ShaderPermutations<T> {
     }
 
     fn compile_shader(&mut self, defines: &T) -> Result<Shader> {
-        Shader::with_src_defines(
-            self.vs_src.clone(),
-            &[],
-            self.fs_src.clone(),
-            &defines.defines(),
-        )
+        let defines = defines.defines();
+
+        // The vertex shader gets the same defines, it can ignore the ones it doesn't use
+        Shader::with_src_defines(self.vs_src.clone(), &defines, self.fs_src.clone(), &defines)
     }
 }
 
@@ -28,6 +28,15 @@ impl<const BINDING: u32> Ssbo<BINDING> {
         Self { id }
     }
 
+    /// Replaces the contents of the SSBO, the buffer is reallocated
+    pub fn update<T: Pod + Zeroable>(&mut self, buf: &[T]) {
+        let buf: &[u8] = bytemuck::cast_slice(buf);
+
+        unsafe {
+            gl::NamedBufferData(self.id, buf.len() as _, buf.as_ptr() as _, gl::DYNAMIC_DRAW);
+        }
+    }
+
     pub fn bind(&self) {
         unsafe {
             gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
@@ -1,35 +1,64 @@
-use std::rc::Rc;
-
 use cstr::cstr;
-use eyre::Result;
+use eyre::{eyre, Result};
 use glam::{Mat4, Quat, Vec3};
 
 use shader_constants::CONSTS;
 
 use crate::{
-    app_settings::{AppSettings, MaterialSrc},
-    brdf_raw::BrdfType,
+    app_settings::{Antialiasing, AppSettings, DebugView, MaterialSrc, ViewportDim},
     camera::Camera,
-    ogl::{texture::GlTexture, uniform_buffer::UniformBuffer, vao::Vao},
+    ogl::{
+        bindless, sampler::GlSampler, ssbo::Ssbo, texture::GlTexture,
+        uniform_buffer::UniformBuffer, vao::Vao, ProgramId,
+    },
     resources::Resources,
-    scene::{Mesh, Node, Primitive, Scene},
+    scene::{Aabb, Node, Primitive, SampledTexture, Scene, SharedGeometry},
 };
 
+mod area_lights;
+pub mod bloom;
+mod clusters;
 mod cubemap;
+mod culling;
+pub mod furnace;
 pub mod ibl;
-mod lighting;
+pub mod lighting;
 pub mod material;
+mod multi_draw;
 pub mod pbr_settings;
+mod post_process;
+mod prepass;
+pub mod screenshot;
 mod shaders;
+mod shadows;
+mod ssao;
+mod ssr;
+pub mod stats;
+mod taa;
 mod transforms;
+pub mod wireframe;
 
 pub use material::PbrMaterial;
 
 use self::{
-    lighting::Lighting,
+    area_lights::LightGizmos,
+    clusters::Clusters,
+    culling::{BoundsGizmo, Frustum},
+    furnace::Furnace,
+    ibl::IblEnv,
+    lighting::{Light, LightType, Lighting},
+    multi_draw::{DrawMaterial, MultiDraw},
     pbr_settings::PbrSettings,
-    shaders::{DataDrivenDefines, PbrDefines, Shaders},
+    post_process::{FrameOutput, PostProcess},
+    prepass::Prepass,
+    screenshot::Screenshot,
+    shaders::Shaders,
+    shadows::ShadowMaps,
+    ssao::Ssao,
+    ssr::Ssr,
+    stats::FragmentStats,
     transforms::Transforms,
+    wireframe::Wireframe,
 };
 
 /// A component responsible for rendering the scene.
@@ -41,11 +70,51 @@ pub struct Renderer {
     material: UniformBuffer<PbrMaterial>,
     /// Current lighting settings
     lighting: UniformBuffer<Lighting>,
+    /// Lights of the current frame, with the shadow map indices
+    lights: Vec<Light>,
+    lights_ssbo: Ssbo<{ CONSTS.buffer_bindings.lights }>,
     /// Runtime rendering settings
     pbr_settings: UniformBuffer<PbrSettings>,
+    shadow_maps: ShadowMaps,
+    clusters: Clusters,
     cube: Vao,
+    light_gizmos: LightGizmos,
+    bounds_gizmo: BoundsGizmo,
+    wireframe: Wireframe,
+    prepass: Prepass,
+    ssao: Ssao,
+    ssr: Ssr,
+    post_process: PostProcess,
+    fragment_stats: FragmentStats,
+    multi_draw: MultiDraw,
+    furnace: Furnace,
 
     dfg_lut: GlTexture,
+    ltc_lut: GlTexture,
+}
+
+/// A primitive of the scene prepared for drawing
+struct DrawItem<'s> {
+    primitive: &'s Primitive,
+    transform: Mat4,
+    /// World-space bounds of the primitive
+    aabb: Aabb,
+    /// Program of the shader permutation, the draws are grouped by it
+    program: ProgramId,
+    /// Squared distance of the primitive center from the camera
+    distance: f32,
+}
+
+impl<'s> DrawItem<'s> {
+    fn is_blended(&self) -> bool {
+        self.primitive.pbr_material.blend
+    }
+
+    /// The opaque draws at the start of the sorted draws, only they are drawn in the depth prepass
+    fn opaque<'d>(draws: &'d [DrawItem<'s>]) -> &'d [DrawItem<'s>] {
+        let count = draws.iter().take_while(|d| !d.is_blended()).count();
+        &draws[..count]
+    }
 }
 
 pub struct RenderCtx<'r> {
@@ -58,69 +127,316 @@ impl Renderer {
     /// Create a new renderer
     pub fn new() -> Result<Self> {
         let dfg_lut = ibl::dfg_integration()?;
+        let ltc_lut = area_lights::fit_ltc()?;
 
         Ok(Self {
             shaders: Shaders::new()?,
             transforms: UniformBuffer::new(Transforms::new_indentity()),
             material: UniformBuffer::new(PbrMaterial::new()),
             lighting: UniformBuffer::new(Lighting::new()),
+            lights: Vec::new(),
+            lights_ssbo: Ssbo::new::<Light>(&[]),
             pbr_settings: UniformBuffer::new(PbrSettings::new()),
+            shadow_maps: ShadowMaps::new()?,
+            clusters: Clusters::new()?,
             cube: cubemap::init_cube(),
+            light_gizmos: LightGizmos::new(),
+            bounds_gizmo: BoundsGizmo::new(),
+            wireframe: Wireframe::new()?,
+            prepass: Prepass::new()?,
+            ssao: Ssao::new()?,
+            ssr: Ssr::new()?,
+            post_process: PostProcess::new()?,
+            fragment_stats: FragmentStats::new(),
+            multi_draw: MultiDraw::new(),
+            furnace: Furnace::new(),
             dfg_lut,
+            ltc_lut,
         })
     }
 
-    /// Render a new frame
+    /// Render a new frame, a requested screenshot is exported before it
     pub fn render(&mut self, rctx: &mut RenderCtx) -> Result<()> {
-        self.reset_gl_state(rctx);
-        self.update_uniforms(rctx)?;
+        if rctx.app_settings.screenshot.requested {
+            rctx.app_settings.screenshot.requested = false;
+
+            let status = match self.export_screenshot(rctx) {
+                Ok(()) => format!("Saved {}", rctx.app_settings.screenshot.path.display()),
+                Err(e) => format!("Export failed: {e}"),
+            };
+            rctx.app_settings.screenshot.status = Some(status);
+        }
+
+        self.render_frame(rctx, FrameOutput::Window)?;
+        Ok(())
+    }
 
-        self.render_lights()?;
+    /// Renders the screenshot at the resolution from the settings and saves it
+    fn export_screenshot(&mut self, rctx: &mut RenderCtx) -> Result<()> {
+        let settings = rctx.app_settings.screenshot.clone();
+        let screenshot = self.render_screenshot(rctx, settings.width, settings.height)?;
+        screenshot.save(&settings.path, settings.format)
+    }
 
-        // Ugly borrowing hack...
-        let mut scenes = std::mem::take(&mut rctx.res.scenes);
-        let selected_scene = rctx.app_settings.selected_scene;
-        let scene = scenes[selected_scene].load()?;
+    /// Renders the viewport into offscreen targets of the given size, independent of the window
+    pub fn render_screenshot(
+        &mut self,
+        rctx: &mut RenderCtx,
+        width: u32,
+        height: u32,
+    ) -> Result<Screenshot> {
+        let window_viewport = rctx.app_settings.viewport_dim;
+        rctx.app_settings.viewport_dim = ViewportDim {
+            min_x: 0.,
+            min_y: 0.,
+            width: width as f32,
+            height: height as f32,
+        };
 
-        let transform = Self::calc_model_transform(scene, rctx.app_settings);
-        self.render_gltf_node(&scene.root, transform, rctx)?;
+        // The history of TAA is discarded at the new size, so it needs the whole jitter sequence to converge
+        let history_frames = match rctx.app_settings.antialiasing {
+            Antialiasing::Taa => taa::JITTER_PHASES - 1,
+            _ => 0,
+        };
 
-        rctx.res.scenes = scenes;
+        let screenshot = (0..history_frames)
+            .try_for_each(|_| self.render_frame(rctx, FrameOutput::History).map(|_| ()))
+            .and_then(|_| self.render_frame(rctx, FrameOutput::Screenshot));
 
-        self.draw_cubemap(rctx)?;
+        rctx.app_settings.viewport_dim = window_viewport;
 
-        Ok(())
+        screenshot?.ok_or_else(|| eyre!("The screenshot wasn't rendered"))
     }
 
-    fn update_uniforms(&mut self, rctx: &mut RenderCtx) -> Result<()> {
-        self.update_brdf(rctx)?;
+    /// Renders the scene into the HDR target and resolves it into the output
+    fn render_frame(
+        &mut self,
+        rctx: &mut RenderCtx,
+        output: FrameOutput,
+    ) -> Result<Option<Screenshot>> {
+        // The scene is rendered into the HDR target, which is only as large as the viewport
+        let viewport_dim = rctx.app_settings.viewport_dim.at_origin();
+
+        self.reset_gl_state(&viewport_dim, rctx.app_settings, output)?;
+        self.update_uniforms(rctx)?;
+
+        // The furnace test has no direct lights
+        if !rctx.app_settings.furnace.enabled {
+            self.render_lights()?;
+        }
+
+        self.bind_ibl_textures(rctx)?;
+
+        // The counters are accumulated over both sides of the split view
+        rctx.app_settings.render_stats.reset_counters();
+        self.fragment_stats
+            .begin_frame(&mut rctx.app_settings.render_stats);
 
-        let selected_scene = rctx.app_settings.selected_scene;
+        self.prepare_lights(rctx, &viewport_dim)?;
 
+        if rctx.app_settings.split.enabled {
+            let width = viewport_dim.width as i32;
+            let divider = (viewport_dim.width * rctx.app_settings.split.divider).round() as i32;
+
+            self.render_view(rctx, &viewport_dim, Some((0, divider)))?;
+
+            // The right side is rendered with the main settings swapped for its own
+            rctx.app_settings.swap_split_side();
+            let right = self.render_view(rctx, &viewport_dim, Some((divider, width - divider)));
+            rctx.app_settings.swap_split_side();
+            right?;
+        } else {
+            self.render_view(rctx, &viewport_dim, None)?;
+        }
+
+        self.fragment_stats.end_frame();
+
+        self.post_process.resolve_msaa();
+
+        if rctx.app_settings.ssr_enabled() {
+            if let Some(hdr) = self.post_process.hdr_color() {
+                self.ssr
+                    .store_history(hdr, &self.unjittered_transforms(&viewport_dim));
+            }
+        }
+
+        let frame_transforms = self.transforms.inner;
+        let app_settings = &*rctx.app_settings;
+        let res = &mut *rctx.res;
+        let (wireframe, transforms) = (&self.wireframe, &mut self.transforms);
+
+        // The overlay isn't affected by the jitter, the anti-aliasing and the tonemapping
+        let overlay = |scene_depth: &GlTexture, viewport_dim: &ViewportDim| {
+            let settings = &app_settings.wireframe;
+            if !settings.enabled && !settings.shows_vectors() {
+                return;
+            }
+
+            if let Some(scene) = res.scenes.get(app_settings.selected_scene) {
+                transforms.inner.projection = Self::projection(viewport_dim);
+                let model = Self::calc_model_transform(scene, app_settings);
+                wireframe.render(
+                    &scene.root,
+                    model,
+                    transforms,
+                    settings,
+                    scene_depth,
+                    viewport_dim,
+                );
+            }
+        };
+
+        self.post_process
+            .resolve(app_settings, &frame_transforms, output, overlay)
+    }
+
+    /// Renders the scene and the background with the current material source and PBR settings.
+    ///
+    /// The columns of the viewport restrict the drawing to one side of the split view.
+    fn render_view(
+        &mut self,
+        rctx: &mut RenderCtx,
+        viewport_dim: &ViewportDim,
+        columns: Option<(i32, i32)>,
+    ) -> Result<()> {
         self.pbr_settings.inner = rctx.app_settings.pbr_settings;
+        if rctx.app_settings.furnace.enabled {
+            Furnace::override_settings(&mut self.pbr_settings.inner);
+        }
         self.pbr_settings.update();
 
+        // The data-driven shaders can't be used until the measured BRDF is loaded
+        if self.update_brdf(rctx) {
+            let selected_scene = rctx.app_settings.selected_scene;
+            if let Some(scene) = rctx.res.scenes.get(selected_scene) {
+                scene.set_anisotropy(rctx.app_settings.anisotropy);
+
+                let transform = Self::calc_model_transform(scene, rctx.app_settings);
+
+                let settings = &self.pbr_settings.inner;
+                // The measured BRDFs only use the occlusion texture
+                let ssao = settings.ssao_enabled() && !rctx.app_settings.material_src.is_measured();
+                if ssao || settings.ssr_enabled() {
+                    self.prepass.render(&scene.root, transform, viewport_dim)?;
+                }
+
+                if ssao {
+                    self.ssao.render(&self.prepass, &self.transforms.inner);
+                }
+
+                if settings.ssr_enabled() {
+                    let transforms = self.unjittered_transforms(viewport_dim);
+                    self.ssr.render(&self.prepass, &transforms);
+                }
+
+                // The shadow pass and the prepass leave their own framebuffers bound
+                self.post_process
+                    .bind_hdr_target(viewport_dim, rctx.app_settings.antialiasing)?;
+                Self::set_viewport(viewport_dim);
+                Self::set_scissor(viewport_dim, columns);
+
+                // Only the pixels of this side are shaded
+                let view_dim = match columns {
+                    Some((_, width)) => ViewportDim {
+                        width: width as f32,
+                        ..*viewport_dim
+                    },
+                    None => *viewport_dim,
+                };
+
+                self.render_scene(scene, transform, &view_dim, rctx.app_settings)?;
+            }
+
+            // Other passes (cubemap, GUI) use the parameters of their own textures
+            GlSampler::unbind_range(0, CONSTS.texture_ports.clearcoat_normal + 1);
+        }
+
+        // The background would only obscure the debug views
+        if rctx.app_settings.pbr_settings.debug_view == DebugView::None {
+            Self::set_scissor(viewport_dim, columns);
+            self.draw_cubemap(rctx)?;
+        }
+
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+
+        Ok(())
+    }
+
+    /// Renders the shadow maps and assigns the lights to the clusters.
+    ///
+    /// The camera and the lights are the same on both sides of the split view, so it's done once per frame.
+    fn prepare_lights(&mut self, rctx: &mut RenderCtx, viewport_dim: &ViewportDim) -> Result<()> {
+        let app_settings = &*rctx.app_settings;
+        if let Some(scene) = rctx.res.scenes.get(app_settings.selected_scene) {
+            let transform = Self::calc_model_transform(scene, app_settings);
+
+            self.shadow_maps.render(
+                &scene.root,
+                transform,
+                &mut self.lights,
+                &self.transforms.inner,
+                app_settings.shadows_enabled(),
+            )?;
+        }
+
+        // Uploaded after the shadow map indices are assigned
+        self.lights_ssbo.update(&self.lights);
+        self.clusters
+            .cull_lights(&self.transforms.inner, viewport_dim);
+
+        Ok(())
+    }
+
+    /// Limits the drawing to the columns of the viewport, render_view disables the scissor test at the end
+    fn set_scissor(viewport_dim: &ViewportDim, columns: Option<(i32, i32)>) {
+        if let Some((x, width)) = columns {
+            unsafe {
+                gl::Enable(gl::SCISSOR_TEST);
+                gl::Scissor(x, 0, width, viewport_dim.height as i32);
+            }
+        }
+    }
+
+    /// The projection of the camera without the jitter of TAA
+    fn projection(viewport_dim: &ViewportDim) -> Mat4 {
         // TODO: let this be user-configurable
-        let persp = Mat4::perspective_rh_gl(
+        Mat4::perspective_rh_gl(
             f32::to_radians(60.),
-            rctx.app_settings.viewport_dim.width / rctx.app_settings.viewport_dim.height,
+            viewport_dim.width / viewport_dim.height,
             0.1,
             1000.,
-        );
+        )
+    }
 
-        let scene = rctx.res.scenes[selected_scene].load()?;
-        self.transforms.inner.projection = persp;
+    /// The transforms of the frame with the projection without the jitter of TAA
+    fn unjittered_transforms(&self, viewport_dim: &ViewportDim) -> Transforms {
+        Transforms {
+            projection: Self::projection(viewport_dim),
+            ..self.transforms.inner
+        }
+    }
+
+    fn update_uniforms(&mut self, rctx: &mut RenderCtx) -> Result<()> {
+        let persp = Self::projection(&rctx.app_settings.viewport_dim);
+
+        self.transforms.inner.projection = self
+            .post_process
+            .jitter_projection(persp, rctx.app_settings);
         self.transforms.inner.view = rctx.camera.view_mat();
-        self.transforms.inner.model = Self::calc_model_transform(scene, rctx.app_settings);
         self.transforms.update();
 
+        self.lights.clone_from(&rctx.app_settings.lights);
+
         self.lighting.inner.cam_pos = rctx.camera.get_pos().extend(0.0);
+        self.lighting.inner.num_lights = self.lights.len() as u32;
         self.lighting.update();
 
         Ok(())
     }
 
-    fn calc_model_transform(scene: &mut Scene, app_settings: &mut AppSettings) -> Mat4 {
+    fn calc_model_transform(scene: &Scene, app_settings: &AppSettings) -> Mat4 {
         let scale = Vec3::splat(app_settings.model_scale);
         let translation = app_settings.model_translation;
         let rot = app_settings.model_rotation;
@@ -136,31 +452,75 @@ impl Renderer {
         settings_transform * scene.transform
     }
 
-    fn update_brdf(&mut self, rctx: &mut RenderCtx) -> Result<()> {
+    /// Binds the SSBO of the selected measured BRDF.
+    ///
+    /// Returns false if the BRDF hasn't been loaded yet.
+    fn update_brdf(&mut self, rctx: &mut RenderCtx) -> bool {
         let material_src = rctx.app_settings.material_src;
 
         match material_src {
             MaterialSrc::MerlBrdf => {
                 let selected_brdf = rctx.app_settings.selected_merl_brdf;
-                let brdf = rctx.res.merl_brdfs[selected_brdf].load()?;
-                brdf.ssbo.bind();
+                match rctx.res.merl_brdfs.get(selected_brdf) {
+                    Some(brdf) => brdf.ssbo.bind(),
+                    None => return false,
+                }
             }
             MaterialSrc::UtiaBrdf => {
                 let selected_brdf = rctx.app_settings.selected_utia_brdf;
-                let brdf = rctx.res.utia_brdfs[selected_brdf].load()?;
-                brdf.ssbo.bind();
+                match rctx.res.utia_brdfs.get(selected_brdf) {
+                    Some(brdf) => brdf.ssbo.bind(),
+                    None => return false,
+                }
             }
             _ => (),
         };
 
+        true
+    }
+
+    /// Binds the textures shared by all primitives.
+    ///
+    /// The IBL textures stay unbound while the first envmap is loading, the scene is then lit only by lights.
+    fn bind_ibl_textures(&mut self, rctx: &mut RenderCtx) -> Result<()> {
+        let tp = CONSTS.texture_ports;
+        let (irradiance, prefilter) = match Self::current_env(&mut self.furnace, rctx)? {
+            Some(iblenv) => (iblenv.irradiance_tex.id, iblenv.prefilter_tex.id),
+            None => (0, 0),
+        };
+
+        unsafe {
+            gl::BindTextureUnit(tp.irradiance, irradiance);
+            gl::BindTextureUnit(tp.prefilter, prefilter);
+            gl::BindTextureUnit(tp.brdf, self.dfg_lut.id);
+            gl::BindTextureUnit(tp.ltc, self.ltc_lut.id);
+        }
+
         Ok(())
     }
 
-    fn draw_cubemap(&mut self, rctx: &mut RenderCtx) -> Result<()> {
+    /// The selected envmap, or the uniform environment when the furnace test is enabled
+    fn current_env<'r>(
+        furnace: &'r mut Furnace,
+        rctx: &'r mut RenderCtx,
+    ) -> Result<Option<&'r IblEnv>> {
+        if rctx.app_settings.furnace.enabled {
+            return furnace.env().map(Some);
+        }
+
         let selected_envmap = rctx.app_settings.selected_envmap;
-        let cubemap = rctx.res.envmaps[selected_envmap].load()?;
+        Ok(rctx.res.envmaps.get(selected_envmap).map(|env| &*env))
+    }
 
-        let texid = if rctx.app_settings.blur_background {
+    fn draw_cubemap(&mut self, rctx: &mut RenderCtx) -> Result<()> {
+        // The blurred irradiance has the integration error, the furnace background has to be exactly 1
+        let blur_background =
+            rctx.app_settings.blur_background && !rctx.app_settings.furnace.enabled;
+        let Some(cubemap) = Self::current_env(&mut self.furnace, rctx)? else {
+            return Ok(());
+        };
+
+        let texid = if blur_background {
             cubemap.irradiance_tex.id
         } else {
             cubemap.cubemap_tex.id
@@ -182,7 +542,24 @@ impl Renderer {
         Ok(())
     }
 
-    fn reset_gl_state(&self, rctx: &mut RenderCtx) {
+    fn reset_gl_state(
+        &mut self,
+        viewport_dim: &ViewportDim,
+        app_settings: &AppSettings,
+        output: FrameOutput,
+    ) -> Result<()> {
+        // Clear the window, the HDR target is cleared after it's bound.
+        // Offscreen frames don't touch the default framebuffer, which doesn't exist in a headless context.
+        unsafe {
+            gl::ClearColor(0.15, 0.15, 0.15, 1.0);
+            if output == FrameOutput::Window {
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            }
+        }
+
+        self.post_process
+            .bind_hdr_target(viewport_dim, app_settings.antialiasing)?;
+
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LEQUAL);
@@ -191,13 +568,7 @@ impl Renderer {
             gl::CullFace(gl::BACK);
             gl::FrontFace(gl::CCW);
 
-            gl::Viewport(
-                rctx.app_settings.viewport_dim.min_x as i32,
-                rctx.app_settings.viewport_dim.min_y as i32,
-                rctx.app_settings.viewport_dim.width as i32,
-                rctx.app_settings.viewport_dim.height as i32,
-            );
-            gl::ClearColor(0.15, 0.15, 0.15, 1.0);
+            Self::set_viewport(viewport_dim);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
             gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
@@ -206,79 +577,281 @@ impl Renderer {
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         }
+
+        Ok(())
     }
 
-    /// Recursive - traverses the node hierarchy and handles each node.
-    fn render_gltf_node(
+    fn set_viewport(viewport_dim: &ViewportDim) {
+        unsafe {
+            gl::Viewport(
+                viewport_dim.min_x as i32,
+                viewport_dim.min_y as i32,
+                viewport_dim.width as i32,
+                viewport_dim.height as i32,
+            );
+        }
+    }
+
+    /// Draws the primitives of the scene grouped by the shader permutation and sorted front-to-back.
+    ///
+    /// The optional depth prepass makes sure that only the visible fragments are shaded.
+    /// The `view_dim` is the part of the viewport that is drawn, used for the statistics.
+    fn render_scene(
         &mut self,
-        node: &Node,
-        outer_transform: Mat4,
-        rctx: &mut RenderCtx,
+        scene: &mut Scene,
+        transform: Mat4,
+        view_dim: &ViewportDim,
+        app_settings: &mut AppSettings,
     ) -> Result<()> {
-        let next_level_transform = outer_transform * node.transform;
+        let multi_draw = app_settings.multi_draw && bindless::is_supported();
+        if multi_draw {
+            scene.prepare_shared_geometry();
+        }
 
-        if let Some(mesh) = &node.mesh {
-            self.render_mesh(mesh, next_level_transform, rctx)?;
+        let scene = &*scene;
+        let cam_pos = self.lighting.inner.cam_pos.truncate();
+
+        let mut draws = Vec::new();
+        self.collect_draws(
+            &scene.root,
+            transform,
+            app_settings.material_src,
+            multi_draw,
+            cam_pos,
+            &mut draws,
+        )?;
+
+        let primitives = draws.len() as u32;
+        if app_settings.frustum_culling {
+            let transforms = &self.transforms.inner;
+            let frustum = Frustum::from_view_projection(transforms.projection * transforms.view);
+            draws.retain(|draw| frustum.is_visible(&draw.aabb));
         }
 
-        for node in &node.children {
-            self.render_gltf_node(node, next_level_transform, rctx)?;
+        // Opaque draws are grouped by the shader and go front to back,
+        // the blended ones are drawn after them and back to front
+        draws.sort_by(|a, b| {
+            a.is_blended()
+                .cmp(&b.is_blended())
+                .then_with(|| match a.is_blended() {
+                    false => a
+                        .program
+                        .cmp(&b.program)
+                        .then(a.distance.total_cmp(&b.distance)),
+                    true => b.distance.total_cmp(&a.distance),
+                })
+        });
+
+        let stats = &mut app_settings.render_stats;
+        stats.primitives = primitives;
+        stats.culled_primitives = primitives - draws.len() as u32;
+
+        match scene.shared_geometry() {
+            Some(geometry) if multi_draw => {
+                self.render_multi_draw(&draws, geometry, view_dim, app_settings)?;
+            }
+            _ => self.render_draws(&draws, view_dim, app_settings)?,
+        }
+
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+        }
+
+        if app_settings.show_bounds {
+            self.render_bounds(&draws);
         }
 
         Ok(())
     }
 
-    /// Renders the mesh of a node
-    fn render_mesh(
+    /// Issues a draw call for every primitive
+    fn render_draws(
         &mut self,
-        mesh: &Mesh,
-        node_transform: Mat4,
-        rctx: &mut RenderCtx,
+        draws: &[DrawItem],
+        view_dim: &ViewportDim,
+        app_settings: &mut AppSettings,
     ) -> Result<()> {
-        self.transforms.inner.model = node_transform;
-        self.transforms.update();
+        let depth_prepass = app_settings.depth_prepass;
+        let mut draw_calls = draws.len();
+        if depth_prepass {
+            let opaque = DrawItem::opaque(draws);
+            self.render_depth_prepass(opaque);
+            draw_calls += opaque.len();
+        }
 
-        for primitive in &mesh.primitives {
-            self.bind_textures(primitive, rctx)?;
-            self.set_material(primitive, rctx);
+        app_settings.render_stats.draw_calls += draw_calls as u32;
 
-            let shader = match rctx.app_settings.material_src {
-                b @ (MaterialSrc::MerlBrdf | MaterialSrc::UtiaBrdf) => {
-                    let brdf_typ = match b {
-                        MaterialSrc::MerlBrdf => BrdfType::Merl,
-                        MaterialSrc::UtiaBrdf => BrdfType::Utia,
-                        _ => unreachable!(),
-                    };
-                    let defines = DataDrivenDefines::from_prim_brdf(primitive, brdf_typ);
-                    self.shaders.data_based_shaders.get_shader(defines)?
-                }
-                _ => {
-                    let defines = PbrDefines::from_prim(primitive);
-                    self.shaders.pbr_shaders.get_shader(defines)?
-                }
-            };
+        self.fragment_stats.begin();
+
+        let mut current_program = None;
+        for draw in draws {
+            self.transforms.inner.model = draw.transform;
+            self.transforms.update();
+
+            Self::bind_textures(draw.primitive);
+            self.set_material(draw.primitive, app_settings);
+
+            if current_program != Some(draw.program) {
+                current_program = Some(draw.program);
+                app_settings.render_stats.shader_switches += 1;
+            }
 
+            let shader = self.shaders.get_primitive_shader(
+                draw.primitive,
+                app_settings.material_src,
+                false,
+            )?;
             shader.use_shader(|| {
-                Self::draw_mesh(primitive);
+                Self::draw_mesh(draw.primitive);
+            });
+        }
+
+        self.fragment_stats
+            .end(depth_prepass, view_dim.width, view_dim.height);
+
+        Ok(())
+    }
+
+    /// Issues a single multi-draw call per shader permutation
+    fn render_multi_draw(
+        &mut self,
+        draws: &[DrawItem],
+        geometry: &SharedGeometry,
+        view_dim: &ViewportDim,
+        app_settings: &mut AppSettings,
+    ) -> Result<()> {
+        let materials: Vec<DrawMaterial> = draws
+            .iter()
+            .map(|d| {
+                DrawMaterial::new(
+                    self.primitive_material(d.primitive, app_settings),
+                    d.primitive,
+                )
             })
+            .collect();
+        self.multi_draw.upload(draws, &materials, geometry);
+
+        let depth_prepass = app_settings.depth_prepass;
+        if depth_prepass {
+            unsafe {
+                gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            }
+
+            let shader = &self.shaders.depth_multi_draw_shader;
+            self.multi_draw
+                .draw(shader, geometry, 0, DrawItem::opaque(draws).len());
+
+            unsafe {
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                gl::DepthMask(gl::FALSE);
+            }
+        }
+
+        app_settings.render_stats.draw_calls += depth_prepass as u32;
+
+        self.fragment_stats.begin();
+
+        let mut first = 0;
+        for group in draws.chunk_by(|a, b| a.program == b.program) {
+            let shader = self.shaders.get_primitive_shader(
+                group[0].primitive,
+                app_settings.material_src,
+                true,
+            )?;
+            self.multi_draw.draw(shader, geometry, first, group.len());
+
+            first += group.len();
+            app_settings.render_stats.draw_calls += 1;
+            app_settings.render_stats.shader_switches += 1;
+        }
+
+        self.fragment_stats
+            .end(depth_prepass, view_dim.width, view_dim.height);
+
+        Ok(())
+    }
+
+    /// Draws the world-space bounding boxes of the visible primitives
+    fn render_bounds(&mut self, draws: &[DrawItem]) {
+        self.shaders.light_shader.use_shader(|| {
+            self.shaders
+                .light_shader
+                .set_vec3(Vec3::new(0., 1., 0.), cstr!("lightColor"));
+
+            for draw in draws {
+                self.transforms.inner.model = BoundsGizmo::model_transform(&draw.aabb);
+                self.transforms.update();
+
+                self.bounds_gizmo.draw();
+            }
+        });
+    }
+
+    /// Recursive - traverses the node hierarchy and gathers the primitives
+    fn collect_draws<'s>(
+        &mut self,
+        node: &'s Node,
+        outer_transform: Mat4,
+        material_src: MaterialSrc,
+        multi_draw: bool,
+        cam_pos: Vec3,
+        draws: &mut Vec<DrawItem<'s>>,
+    ) -> Result<()> {
+        let transform = outer_transform * node.transform;
+
+        if let Some(mesh) = &node.mesh {
+            for primitive in &mesh.primitives {
+                let shader =
+                    self.shaders
+                        .get_primitive_shader(primitive, material_src, multi_draw)?;
+                let aabb = primitive.aabb.transformed(transform);
+
+                draws.push(DrawItem {
+                    primitive,
+                    transform,
+                    aabb,
+                    program: shader.program_id,
+                    distance: aabb.center().distance_squared(cam_pos),
+                });
+            }
+        }
+
+        for node in &node.children {
+            self.collect_draws(node, transform, material_src, multi_draw, cam_pos, draws)?;
         }
 
         Ok(())
     }
 
-    fn bind_textures(&mut self, primitive: &Primitive, rctx: &mut RenderCtx) -> Result<()> {
-        let bind_texture_unit = |tex: &Option<Rc<GlTexture>>, port: u32| {
+    /// Fills the depth buffer, the shading pass then only passes the depth test with the closest fragments
+    fn render_depth_prepass(&mut self, draws: &[DrawItem]) {
+        unsafe {
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        }
+
+        self.shaders.depth_shader.use_shader(|| {
+            for draw in draws {
+                self.transforms.inner.model = draw.transform;
+                self.transforms.update();
+
+                Self::draw_mesh(draw.primitive);
+            }
+        });
+
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            // The depth buffer is already complete
+            gl::DepthMask(gl::FALSE);
+        }
+    }
+
+    fn bind_textures(primitive: &Primitive) {
+        let bind_texture_unit = |tex: &Option<SampledTexture>, port: u32| {
             if let Some(tex) = tex {
-                unsafe {
-                    gl::BindTextureUnit(port, tex.id);
-                }
+                tex.bind(port);
             }
         };
 
-        // Do this first as this can use texture units 0-1
-        let selected_envmap = rctx.app_settings.selected_envmap;
-        let iblenv = rctx.res.envmaps[selected_envmap].load()?;
-
         let tp = CONSTS.texture_ports;
         bind_texture_unit(&primitive.pbr_material.base_color_texture, tp.albedo);
         bind_texture_unit(&primitive.pbr_material.mr_texture, tp.mr);
@@ -291,46 +864,45 @@ impl Renderer {
             bind_texture_unit(&cc.roughness_texture, tp.clearcoat_roughness);
             bind_texture_unit(&cc.normal_texture, tp.clearcoat_normal);
         }
+    }
 
-        unsafe {
-            gl::BindTextureUnit(tp.irradiance, iblenv.irradiance_tex.id);
-            gl::BindTextureUnit(tp.prefilter, iblenv.prefilter_tex.id);
-            gl::BindTextureUnit(tp.brdf, self.dfg_lut.id);
-        }
-
-        Ok(())
+    fn set_material(&mut self, prim: &Primitive, app_settings: &AppSettings) {
+        self.material.inner = self.primitive_material(prim, app_settings);
+        self.material.update();
     }
 
-    fn set_material(&mut self, prim: &Primitive, rctx: &mut RenderCtx) {
-        if rctx.app_settings.material_src == MaterialSrc::PbrOverride {
-            self.material.inner = rctx.app_settings.pbr_material_override;
-        } else if rctx.app_settings.material_src == MaterialSrc::Gltf {
-            self.material.inner.base_color_factor = prim.pbr_material.base_color_factor;
-            self.material.inner.emissive_factor[0..3]
-                .copy_from_slice(&prim.pbr_material.emissive_factor);
-            self.material.inner.metallic_factor = prim.pbr_material.metallic_factor;
-            self.material.inner.roughness_factor = prim.pbr_material.roughness_factor;
-            self.material.inner.normal_scale = prim.pbr_material.normal_scale;
-            self.material.inner.occlusion_strength = prim.pbr_material.occlusion_strength;
+    /// The material of the primitive, the measured BRDFs keep the previous material
+    fn primitive_material(&self, prim: &Primitive, app_settings: &AppSettings) -> PbrMaterial {
+        let mut material = self.material.inner;
+
+        if app_settings.material_src == MaterialSrc::PbrOverride {
+            material = app_settings.pbr_material_override;
+        } else if app_settings.material_src == MaterialSrc::Gltf {
+            material.base_color_factor = prim.pbr_material.base_color_factor;
+            material.emissive_factor[0..3].copy_from_slice(&prim.pbr_material.emissive_factor);
+            material.metallic_factor = prim.pbr_material.metallic_factor;
+            material.roughness_factor = prim.pbr_material.roughness_factor;
+            material.normal_scale = prim.pbr_material.normal_scale;
+            material.occlusion_strength = prim.pbr_material.occlusion_strength;
 
             if let Some(intensity_factor) = prim.clearcoat.as_ref().map(|c| c.intensity_factor) {
-                self.material.inner.clearcoat_intensity_factor = intensity_factor;
+                material.clearcoat_intensity_factor = intensity_factor;
             }
 
             if let Some(roughness_factor) = prim.clearcoat.as_ref().map(|c| c.roughness_factor) {
-                self.material.inner.clearcoat_roughness_factor = roughness_factor;
+                material.clearcoat_roughness_factor = roughness_factor;
             }
 
             if let Some(normal_scale) = prim.clearcoat.as_ref().map(|c| c.normal_scale) {
-                self.material.inner.clearcoat_normal_scale = normal_scale;
+                material.clearcoat_normal_scale = normal_scale;
             }
 
             if let Some(anisotropy) = prim.anisotropy.as_ref().map(|a| a.anisotropy) {
-                self.material.inner.anisotropy = anisotropy;
+                material.anisotropy = anisotropy;
             }
         }
 
-        self.material.update();
+        material
     }
 
     fn draw_mesh(prim: &Primitive) {
@@ -349,39 +921,55 @@ impl Renderer {
     }
 
     fn render_lights(&mut self) -> Result<()> {
-        let lighting = self.lighting.inner;
-        let num_lights = lighting.lights;
+        // Area lights can be seen from both sides
+        unsafe {
+            gl::Disable(gl::CULL_FACE);
+        }
+
+        for light in &self.lights {
+            let typ = light.typ();
+
+            // Directional lights don't have a position
+            if typ == LightType::Directional {
+                continue;
+            }
 
-        for (light_pos, light_color) in lighting
-            .light_pos
-            .iter()
-            .zip(lighting.light_color)
-            .take(num_lights as usize)
-        {
             self.shaders.light_shader.use_shader(|| {
-                self.transforms.inner.model = Mat4::from_translation(light_pos.truncate())
-                    * Mat4::from_scale(Vec3::splat(0.1));
+                self.transforms.inner.model = if typ.is_area() {
+                    LightGizmos::model_transform(light)
+                } else {
+                    Mat4::from_translation(light.position.truncate())
+                        * Mat4::from_scale(Vec3::splat(0.1))
+                };
                 self.transforms.update();
 
                 self.shaders
                     .light_shader
-                    .set_vec3(light_color.truncate(), cstr!("lightColor"));
-
-                unsafe {
-                    gl::BindVertexArray(self.cube.id);
-
-                    gl::DrawElements(
-                        gl::TRIANGLES,
-                        cubemap::INDICES.len() as _,
-                        gl::UNSIGNED_BYTE,
-                        0 as _,
-                    );
-
-                    gl::BindVertexArray(0);
-                };
+                    .set_vec3(light.color, cstr!("lightColor"));
+
+                if typ.is_area() {
+                    self.light_gizmos.draw(typ);
+                } else {
+                    unsafe {
+                        gl::BindVertexArray(self.cube.id);
+
+                        gl::DrawElements(
+                            gl::TRIANGLES,
+                            cubemap::INDICES.len() as _,
+                            gl::UNSIGNED_BYTE,
+                            0 as _,
+                        );
+
+                        gl::BindVertexArray(0);
+                    };
+                }
             });
         }
 
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,189 @@
+use std::{f32::consts::PI, mem::size_of};
+
+use cstr::cstr;
+use eyre::Result;
+use glam::{Mat4, Vec3};
+use shader_constants::CONSTS;
+
+use crate::{
+    config::shader_path,
+    ogl::{gl_buffer::GlBuffer, gl_time_query, shader::Shader, texture::GlTexture, vao::Vao},
+};
+
+use super::lighting::{Light, LightType};
+
+/// Number of segments of the disk and tube gizmos
+const GIZMO_SEGMENTS: u32 = 32;
+
+/// Fits the LTC lookup table of the GGX BRDF.
+///
+/// Stores the 4 non-trivial entries of the normalized inverse LTC matrix for each
+/// perceptual roughness (X axis) and sqrt(1 - NoV) (Y axis).
+pub fn fit_ltc() -> Result<GlTexture> {
+    let size = CONSTS.ltc.size;
+    let ltc_lut = GlTexture::new(gl::TEXTURE_2D);
+
+    unsafe {
+        gl::TextureStorage2D(ltc_lut.id, 1, gl::RGBA32F, size, size);
+
+        gl::TextureParameteri(ltc_lut.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TextureParameteri(ltc_lut.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TextureParameteri(ltc_lut.id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TextureParameteri(ltc_lut.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    };
+
+    ltc_lut.add_label(cstr!("LTC LUT"));
+
+    let ltc_fit_shader = Shader::comp_with_path(shader_path("ltc_fit.comp"))?;
+
+    gl_time_query("LTC fitting", || {
+        ltc_fit_shader.use_shader(|| unsafe {
+            gl::BindImageTexture(0, ltc_lut.id, 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::RGBA32F);
+
+            // Fit a single row of work groups at a time so the OS doesn't timeout the GPU
+            let local_size = CONSTS.ltc.local_size_xy;
+            for offset_y in (0..size as u32).step_by(local_size as usize) {
+                ltc_fit_shader.set_u32(offset_y, cstr!("offset_y"));
+
+                gl::DispatchCompute(size as u32 / local_size, 1, 1);
+                gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+            }
+        });
+    });
+
+    Ok(ltc_lut)
+}
+
+/// A mesh that only has positions
+struct GizmoMesh {
+    vao: Vao,
+    index_count: i32,
+}
+
+impl GizmoMesh {
+    fn new(positions: &[Vec3], indices: &[u32]) -> Self {
+        let vao = Vao::new();
+
+        let vertex_buf = GlBuffer::new(positions);
+        vao.attach_vertex_buf(
+            &vertex_buf,
+            3,
+            CONSTS.vertex_attrib_indices.position,
+            gl::FLOAT,
+            size_of::<Vec3>(),
+        );
+
+        let index_buf = GlBuffer::new(indices);
+        vao.attach_index_buffer(&index_buf);
+
+        Self {
+            vao,
+            index_count: indices.len() as i32,
+        }
+    }
+
+    fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao.id);
+            gl::DrawElements(gl::TRIANGLES, self.index_count, gl::UNSIGNED_INT, 0 as _);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+/// Meshes of the area light shapes
+pub struct LightGizmos {
+    /// Square from -1 to 1 in the XY plane
+    rect: GizmoMesh,
+    /// Disk with radius 1 in the XY plane
+    disk: GizmoMesh,
+    /// Cylinder with radius 1 around the Z axis from -1 to 1
+    tube: GizmoMesh,
+}
+
+impl LightGizmos {
+    pub fn new() -> Self {
+        let rect = GizmoMesh::new(
+            &[
+                Vec3::new(-1., -1., 0.),
+                Vec3::new(1., -1., 0.),
+                Vec3::new(1., 1., 0.),
+                Vec3::new(-1., 1., 0.),
+            ],
+            &[0, 1, 2, 0, 2, 3],
+        );
+
+        let circle = |z: f32| {
+            (0..GIZMO_SEGMENTS).map(move |i| {
+                let phi = 2. * PI * i as f32 / GIZMO_SEGMENTS as f32;
+                Vec3::new(phi.cos(), phi.sin(), z)
+            })
+        };
+
+        // Triangle fan around the center
+        let mut disk_positions = vec![Vec3::ZERO];
+        disk_positions.extend(circle(0.));
+        let mut disk_indices = Vec::new();
+        for i in 0..GIZMO_SEGMENTS {
+            disk_indices.extend([0, 1 + i, 1 + (i + 1) % GIZMO_SEGMENTS]);
+        }
+        let disk = GizmoMesh::new(&disk_positions, &disk_indices);
+
+        // Side of the cylinder and the caps
+        let mut tube_positions: Vec<Vec3> = circle(-1.).chain(circle(1.)).collect();
+        tube_positions.extend([Vec3::new(0., 0., -1.), Vec3::new(0., 0., 1.)]);
+        let bottom_center = 2 * GIZMO_SEGMENTS;
+        let top_center = bottom_center + 1;
+
+        let mut tube_indices = Vec::new();
+        for i in 0..GIZMO_SEGMENTS {
+            let next = (i + 1) % GIZMO_SEGMENTS;
+            let (b0, b1) = (i, next);
+            let (t0, t1) = (GIZMO_SEGMENTS + i, GIZMO_SEGMENTS + next);
+
+            tube_indices.extend([b0, b1, t1, b0, t1, t0]);
+            tube_indices.extend([bottom_center, b1, b0]);
+            tube_indices.extend([top_center, t0, t1]);
+        }
+        let tube = GizmoMesh::new(&tube_positions, &tube_indices);
+
+        Self { rect, disk, tube }
+    }
+
+    /// Draws the shape of an area light, the model transform is given by model_transform()
+    pub fn draw(&self, typ: LightType) {
+        match typ {
+            LightType::Rect => self.rect.draw(),
+            LightType::Disk => self.disk.draw(),
+            LightType::Tube => self.tube.draw(),
+            // Punctual lights don't have a shape
+            LightType::Point | LightType::Directional | LightType::Spot => (),
+        }
+    }
+
+    /// Transforms the gizmo mesh to the shape of the light, the orientation matches the shaders
+    pub fn model_transform(light: &Light) -> Mat4 {
+        let axis = light.direction();
+        let world_up = if axis.y.abs() < 0.99 {
+            Vec3::Y
+        } else {
+            Vec3::X
+        };
+        let right = world_up.cross(axis).normalize();
+        let up = axis.cross(right);
+
+        let (scale_x, scale_y, scale_z) = match light.typ() {
+            LightType::Rect => (0.5 * light.width, 0.5 * light.height, 1.),
+            LightType::Disk => (0.5 * light.width, 0.5 * light.width, 1.),
+            LightType::Tube => (0.5 * light.height, 0.5 * light.height, 0.5 * light.width),
+            LightType::Point | LightType::Directional | LightType::Spot => (1., 1., 1.),
+        };
+
+        Mat4::from_cols(
+            (right * scale_x).extend(0.),
+            (up * scale_y).extend(0.),
+            (axis * scale_z).extend(0.),
+            light.position.truncate().extend(1.),
+        )
+    }
+}
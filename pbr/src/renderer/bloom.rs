@@ -0,0 +1,193 @@
+use cstr::cstr;
+use eyre::Result;
+use shader_constants::CONSTS;
+
+use crate::{
+    config::shader_path,
+    ogl::{shader::Shader, texture::GlTexture},
+};
+
+use super::post_process;
+
+/// User-configurable parameters of the bloom
+#[derive(Clone, Copy)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    /// Brightness where the pixels start contributing to the bloom, 0 keeps all of the energy
+    pub threshold: f32,
+    /// Weight of the bloom when it's blended with the HDR image
+    pub intensity: f32,
+    /// Radius of the upsampling filter in texels
+    pub radius: f32,
+}
+
+impl BloomSettings {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.,
+            intensity: 0.04,
+            radius: 1.,
+        }
+    }
+}
+
+/// Half-resolution texture with a mip level for each step of the blur
+struct MipChain {
+    texture: GlTexture,
+    /// Sizes of the mip levels
+    sizes: Vec<(i32, i32)>,
+}
+
+impl MipChain {
+    fn new(width: i32, height: i32) -> Self {
+        let mut sizes = Vec::new();
+        let (mut w, mut h) = ((width / 2).max(1), (height / 2).max(1));
+
+        while sizes.len() < CONSTS.post_process.bloom_levels as usize {
+            sizes.push((w, h));
+
+            if w == 1 && h == 1 {
+                break;
+            }
+
+            w = (w / 2).max(1);
+            h = (h / 2).max(1);
+        }
+
+        let (w, h) = sizes[0];
+        let texture = GlTexture::new(gl::TEXTURE_2D);
+
+        unsafe {
+            gl::TextureStorage2D(texture.id, sizes.len() as i32, gl::RGBA16F, w, h);
+            gl::TextureParameteri(texture.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(texture.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            // The levels are sampled explicitly, the filtering only happens inside of a level
+            gl::TextureParameteri(
+                texture.id,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR_MIPMAP_NEAREST as i32,
+            );
+            gl::TextureParameteri(texture.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        }
+
+        texture.add_label(cstr!("Bloom mip chain"));
+
+        Self { texture, sizes }
+    }
+}
+
+/// Bloom based on the "Next Generation Post Processing in Call of Duty: Advanced Warfare" presentation.
+///
+/// The HDR image is progressively downsampled and then upsampled back with a tent filter,
+/// the levels are added together during the upsampling.
+pub struct Bloom {
+    downsample_shader: Shader,
+    upsample_shader: Shader,
+    mip_chain: Option<MipChain>,
+    /// Size of the HDR image the mip chain was created for
+    source_size: (i32, i32),
+}
+
+impl Bloom {
+    pub fn new() -> Result<Self> {
+        let downsample_shader = Shader::comp_with_path(shader_path("bloom_downsample.comp"))?;
+        let upsample_shader = Shader::comp_with_path(shader_path("bloom_upsample.comp"))?;
+
+        Ok(Self {
+            downsample_shader,
+            upsample_shader,
+            mip_chain: None,
+            source_size: (0, 0),
+        })
+    }
+
+    /// Blurs the HDR image and returns the texture with the bloom in its first level
+    pub fn render(
+        &mut self,
+        hdr: &GlTexture,
+        width: i32,
+        height: i32,
+        settings: &BloomSettings,
+    ) -> &GlTexture {
+        if self.mip_chain.is_none() || self.source_size != (width, height) {
+            self.mip_chain = Some(MipChain::new(width, height));
+            self.source_size = (width, height);
+        }
+
+        let mip_chain = self.mip_chain.as_ref().unwrap();
+
+        self.downsample(hdr, mip_chain, settings);
+        self.upsample(mip_chain, settings);
+
+        &mip_chain.texture
+    }
+
+    fn downsample(&self, hdr: &GlTexture, mip_chain: &MipChain, settings: &BloomSettings) {
+        let shader = &self.downsample_shader;
+
+        shader.use_shader(|| unsafe {
+            shader.set_f32(settings.threshold, cstr!("threshold"));
+
+            for (level, &(w, h)) in mip_chain.sizes.iter().enumerate() {
+                let first_pass = level == 0;
+
+                // The first level is downsampled from the HDR image, the others from the previous level
+                if first_pass {
+                    gl::BindTextureUnit(0, hdr.id);
+                    shader.set_i32(0, cstr!("srcLevel"));
+                } else {
+                    gl::BindTextureUnit(0, mip_chain.texture.id);
+                    shader.set_i32(level as i32 - 1, cstr!("srcLevel"));
+                }
+                shader.set_u32(first_pass as u32, cstr!("firstPass"));
+
+                gl::BindImageTexture(
+                    0,
+                    mip_chain.texture.id,
+                    level as i32,
+                    gl::FALSE,
+                    0,
+                    gl::WRITE_ONLY,
+                    gl::RGBA16F,
+                );
+
+                post_process::dispatch_compute_2d(w, h);
+                gl::MemoryBarrier(
+                    gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT,
+                );
+            }
+        });
+    }
+
+    fn upsample(&self, mip_chain: &MipChain, settings: &BloomSettings) {
+        let shader = &self.upsample_shader;
+
+        shader.use_shader(|| unsafe {
+            shader.set_f32(settings.radius, cstr!("radius"));
+            gl::BindTextureUnit(0, mip_chain.texture.id);
+
+            // From the smallest level up, each level is added to the level above it
+            for level in (0..mip_chain.sizes.len() - 1).rev() {
+                let (w, h) = mip_chain.sizes[level];
+
+                shader.set_i32(level as i32 + 1, cstr!("srcLevel"));
+
+                gl::BindImageTexture(
+                    0,
+                    mip_chain.texture.id,
+                    level as i32,
+                    gl::FALSE,
+                    0,
+                    gl::READ_WRITE,
+                    gl::RGBA16F,
+                );
+
+                post_process::dispatch_compute_2d(w, h);
+                gl::MemoryBarrier(
+                    gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT,
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,93 @@
+use eyre::Result;
+use glam::{Mat4, Vec4};
+use shader_constants::CONSTS;
+
+use crate::{
+    app_settings::ViewportDim,
+    config::shader_path,
+    ogl::{
+        shader::Shader,
+        ssbo::Ssbo,
+        uniform_buffer::{UniformBuffer, UniformBufferElement},
+    },
+};
+
+use super::transforms::Transforms;
+
+const NUM_CLUSTERS: usize = (CONSTS.clusters.x * CONSTS.clusters.y * CONSTS.clusters.z) as usize;
+
+/// Uniform buffer element with the data needed for computing the cluster of a fragment
+#[derive(bytemuck::NoUninit, Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub struct ClusterUniforms {
+    view: Mat4,
+    inverse_projection: Mat4,
+    /// Offset and size of the viewport in window coordinates
+    viewport: Vec4,
+    near: f32,
+    far: f32,
+    padding: [f32; 2],
+}
+
+impl UniformBufferElement for ClusterUniforms {
+    const BINDING: u32 = CONSTS.buffer_bindings.clusters;
+}
+
+/// Clustered light culling.
+///
+/// The view frustum is divided into froxels - tiles in screen space and exponential slices in depth.
+/// A compute shader assigns the lights to the froxels they affect, so the fragment shaders
+/// only iterate the lights of their froxel.
+pub struct Clusters {
+    uniforms: UniformBuffer<ClusterUniforms>,
+    shader: Shader,
+    /// Light counts of the clusters followed by the light indices of the clusters
+    light_lists: Ssbo<{ CONSTS.buffer_bindings.cluster_lights }>,
+}
+
+impl Clusters {
+    pub fn new() -> Result<Self> {
+        let shader = Shader::comp_with_path(shader_path("cluster_lights.comp"))?;
+
+        let list_len = NUM_CLUSTERS * (1 + CONSTS.clusters.max_lights as usize);
+        let light_lists = Ssbo::new(&vec![0u32; list_len]);
+
+        let uniforms = UniformBuffer::new(ClusterUniforms {
+            view: Mat4::IDENTITY,
+            inverse_projection: Mat4::IDENTITY,
+            viewport: Vec4::ONE,
+            near: 0.1,
+            far: 1000.,
+            padding: [0.; 2],
+        });
+
+        Ok(Self {
+            uniforms,
+            shader,
+            light_lists,
+        })
+    }
+
+    /// Assigns the lights to the clusters, the lights have to be uploaded already
+    pub fn cull_lights(&mut self, transforms: &Transforms, viewport_dim: &ViewportDim) {
+        let (near, far) = transforms.near_far();
+
+        self.uniforms.inner.view = transforms.view;
+        self.uniforms.inner.inverse_projection = transforms.projection.inverse();
+        self.uniforms.inner.viewport = Vec4::new(
+            viewport_dim.min_x,
+            viewport_dim.min_y,
+            viewport_dim.width,
+            viewport_dim.height,
+        );
+        self.uniforms.inner.near = near;
+        self.uniforms.inner.far = far;
+        self.uniforms.update();
+        self.light_lists.bind();
+
+        self.shader.use_shader(|| unsafe {
+            gl::DispatchCompute(1, 1, CONSTS.clusters.z);
+            gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+        });
+    }
+}
@@ -0,0 +1,107 @@
+use std::mem::size_of;
+
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use shader_constants::CONSTS;
+
+use crate::{
+    ogl::{gl_buffer::GlBuffer, vao::Vao},
+    scene::{Aabb, BoundingSphere},
+};
+
+use super::cubemap;
+
+/// Edges of the cube from the cubemap module
+#[rustfmt::skip]
+const BOX_LINE_INDICES: [u8; 24] = [
+    0, 1, 1, 2, 2, 3, 3, 0,
+    4, 5, 5, 6, 6, 7, 7, 4,
+    0, 4, 1, 5, 2, 6, 3, 7,
+];
+
+/// Planes of the camera frustum in world space, the normals point inside
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from the view-projection matrix.
+    ///
+    /// Based on: Gribb, Hartmann, “Fast Extraction of Viewing Frustum Planes from the World-View-Projection Matrix.”
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let r0 = view_projection.row(0);
+        let r1 = view_projection.row(1);
+        let r2 = view_projection.row(2);
+        let r3 = view_projection.row(3);
+
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2]
+            .map(|plane| plane / plane.xyz().length());
+
+        Self { planes }
+    }
+
+    /// The sphere test is cheaper, the AABB test is used for boxes that pass it
+    pub fn is_visible(&self, aabb: &Aabb) -> bool {
+        self.intersects_sphere(&aabb.bounding_sphere()) && self.intersects_aabb(aabb)
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    /// Conservative test, boxes near the frustum corners may be reported as visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner of the box that is the furthest along the plane normal
+            let normal = plane.xyz();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+
+            normal.dot(corner) + plane.w >= 0.
+        })
+    }
+}
+
+/// Wireframe box for visualizing the bounding volumes
+pub struct BoundsGizmo {
+    vao: Vao,
+}
+
+impl BoundsGizmo {
+    pub fn new() -> Self {
+        let vao = Vao::new();
+
+        let vertex_buf = GlBuffer::new(&cubemap::VERTICES);
+        vao.attach_vertex_buf(
+            &vertex_buf,
+            3,
+            CONSTS.vertex_attrib_indices.position,
+            gl::FLOAT,
+            size_of::<cubemap::Vertex>(),
+        );
+
+        let index_buf = GlBuffer::new(&BOX_LINE_INDICES);
+        vao.attach_index_buffer(&index_buf);
+
+        Self { vao }
+    }
+
+    /// Model transform of the unit cube that covers the box
+    pub fn model_transform(aabb: &Aabb) -> Mat4 {
+        Mat4::from_translation(aabb.center()) * Mat4::from_scale(aabb.half_extents())
+    }
+
+    /// The shader and its model transform have to be set already
+    pub fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao.id);
+            gl::DrawElements(
+                gl::LINES,
+                BOX_LINE_INDICES.len() as _,
+                gl::UNSIGNED_BYTE,
+                0 as _,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use eyre::Result;
+
+use super::{
+    ibl::{HdrImage, IblEnv},
+    pbr_settings::PbrSettings,
+};
+
+/// User-configurable parameters of the white furnace test
+#[derive(Clone, Copy)]
+pub struct FurnaceSettings {
+    pub enabled: bool,
+    /// Deviation from 1 that is shown with the fully saturated false color
+    pub range: f32,
+}
+
+impl FurnaceSettings {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            range: 0.05,
+        }
+    }
+}
+
+/// White furnace test of the energy conservation.
+///
+/// A white material in a uniform white environment has to reflect exactly the incoming radiance,
+/// so the rendered image is compared with 1 instead of being tonemapped.
+pub struct Furnace {
+    /// Created the first time the test is enabled
+    env: Option<IblEnv>,
+}
+
+impl Furnace {
+    pub fn new() -> Self {
+        Self { env: None }
+    }
+
+    /// The irradiance and prefilter maps are computed from the uniform environment
+    /// in the same way as for the loaded envmaps
+    pub fn env(&mut self) -> Result<&IblEnv> {
+        let env = match self.env.take() {
+            Some(env) => env,
+            None => IblEnv::from_equimap(HdrImage::uniform(1.))?,
+        };
+
+        Ok(self.env.insert(env))
+    }
+
+    /// Only the IBL contributes to the test, the effects that depend on the scene are disabled
+    pub fn override_settings(pbr_settings: &mut PbrSettings) {
+        pbr_settings.set_ibl_enabled(true);
+        pbr_settings.set_direct_light_enabled(false);
+        pbr_settings.set_ssao_enabled(false);
+        pbr_settings.set_ssr_enabled(false);
+    }
+}
@@ -1,4 +1,8 @@
-use std::{cmp::Ordering, fs::File, io::BufReader};
+use std::{
+    cmp::Ordering,
+    fs,
+    time::{Duration, Instant},
+};
 
 use cstr::cstr;
 use eyre::{eyre, Result};
@@ -6,27 +10,103 @@ use eyre::{eyre, Result};
 use gl::types::GLenum;
 use image::codecs::hdr;
 
-use crate::ogl::{gl_time_query, shader::Shader, texture::GlTexture, TextureId};
+use crate::{
+    config::{ibl_cache_dir, shader_path},
+    ogl::{gl_time_query, shader::Shader, texture::GlTexture},
+    resources::{LoadProgress, Upload},
+};
 
 use shader_constants::CONSTS;
 
+mod cache;
+
+use self::cache::{CacheKey, CachedIbl, CachedTexture};
+
 const CUBEMAP_FACES: u32 = 6;
 const IRRADIANCE_MAP_SIZE: i32 = 64;
 const PREFILTER_MAP_SIZE: i32 = 256;
 const BRDF_LUT_SIZE: i32 = 512;
 
+/// Size of the blocks of the irradiance and prefilter maps that are computed by one dispatch
+const BLOCK_SIZE: u32 = 8;
+/// Time spent computing the irradiance and prefilter maps in one frame
+const COMPUTE_FRAME_BUDGET: Duration = Duration::from_millis(8);
+
 pub struct IblEnv {
     pub cubemap_tex: GlTexture,
     pub irradiance_tex: GlTexture,
     pub prefilter_tex: GlTexture,
 }
 
+/// CPU-side data of an IBL environment
+pub enum IblData {
+    /// The maps have to be computed from the equirectangular map
+    Equimap {
+        equimap: HdrImage,
+        /// None if the cache is disabled or the key couldn't be computed
+        cache_key: Option<CacheKey>,
+    },
+    /// The maps were loaded from the on-disk cache
+    Cached(CachedIbl),
+}
+
+impl IblData {
+    /// Loads the precomputed maps from the cache if possible, otherwise loads the HDR image
+    pub fn load(path: &str, progress: &LoadProgress) -> Result<Self> {
+        // The file is read only once, it's both hashed and decoded
+        let bytes = fs::read(path)?;
+
+        let cache_key = match ibl_cache_dir().map(|_| CacheKey::new(path, &bytes)) {
+            Some(Ok(key)) => Some(key),
+            Some(Err(e)) => {
+                eprintln!("Couldn't compute the IBL cache key of '{path}': {e}");
+                None
+            }
+            None => None,
+        };
+
+        if let Some(key) = &cache_key {
+            match CachedIbl::load(key, progress) {
+                Ok(Some(cached)) => return Ok(Self::Cached(cached)),
+                Ok(None) => (),
+                Err(e) => eprintln!("Invalid IBL cache of '{path}': {e}"),
+            }
+        }
+
+        // The progress of the equirectangular maps is reported while computing the IBL maps
+        let equimap = decode_hdr_image(&bytes)?;
+
+        Ok(Self::Equimap { equimap, cache_key })
+    }
+}
+
 impl IblEnv {
-    pub fn from_equimap_path(path: &str) -> Result<Self> {
-        let (cubemap_tex, max_radiance) = Self::load_cubemap_from_equi(path)?;
-        let irradiance_tex = Self::compute_irradiance_map(cubemap_tex.id, max_radiance)?;
-        let prefilter_tex = Self::compute_prefilter_map(cubemap_tex.id, max_radiance)?;
+    /// Uploads the cached maps or starts computing the maps, which continues over the next frames
+    pub fn from_data(data: IblData) -> Result<Upload<Self>> {
+        match data {
+            IblData::Cached(cached) => Ok(Upload::Done(Self::from_cache(&cached)?)),
+            IblData::Equimap { equimap, cache_key } => {
+                Ok(Upload::Pending(IblComputation::new(equimap, cache_key)?))
+            }
+        }
+    }
+
+    fn from_cache(cached: &CachedIbl) -> Result<Self> {
+        let create_texture = |cached: &CachedTexture| -> Result<GlTexture> {
+            let tex = Self::create_cubemap_texture(
+                cached.size,
+                cached.internal_format,
+                cached.levels.len() as i32,
+            );
+            cached.upload(&tex)?;
+            Ok(tex)
+        };
+
+        let cubemap_tex = create_texture(&cached.cubemap)?;
+        let irradiance_tex = create_texture(&cached.irradiance)?;
+        let prefilter_tex = create_texture(&cached.prefilter)?;
 
+        cubemap_tex.add_label(cstr!("environment map"));
         irradiance_tex.add_label(cstr!("irradiance map"));
         prefilter_tex.add_label(cstr!("prefilter map"));
 
@@ -37,9 +117,39 @@ impl IblEnv {
         })
     }
 
+    /// Approximate GPU memory used by the maps in bytes
+    pub fn memory_size(&self) -> usize {
+        // All of the maps are RGBA32F
+        let cubemap_size = |size: i32, levels: i32| {
+            (0..levels)
+                .map(|level| (size >> level).max(1) as usize)
+                .map(|s| s * s * CUBEMAP_FACES as usize * 16)
+                .sum::<usize>()
+        };
+
+        let levels = CONSTS.ibl.cubemap_roughnes_levels;
+        cubemap_size(CONSTS.ibl.cubemap_size, levels)
+            + cubemap_size(IRRADIANCE_MAP_SIZE, 1)
+            + cubemap_size(PREFILTER_MAP_SIZE, levels)
+    }
+
+    fn read_back(&self) -> Result<CachedIbl> {
+        Ok(CachedIbl {
+            cubemap: CachedTexture::read_back(&self.cubemap_tex)?,
+            irradiance: CachedTexture::read_back(&self.irradiance_tex)?,
+            prefilter: CachedTexture::read_back(&self.prefilter_tex)?,
+        })
+    }
+
+    /// Computes all of the maps at once, blocks the render thread until they are done
+    pub fn from_equimap(equimap: HdrImage) -> Result<Self> {
+        let mut computation = IblComputation::new(equimap, None)?;
+        computation.step(Duration::MAX);
+        Ok(computation.finish())
+    }
+
     /// Converts an HDR equirectangular map to a cubemap
-    fn load_cubemap_from_equi(path: &str) -> Result<(GlTexture, f32)> {
-        let equimap = load_hdr_image(path)?;
+    fn load_cubemap_from_equi(equimap: HdrImage) -> Result<(GlTexture, f32)> {
         let max_value = equimap.max_value;
         let equi_tex = Self::create_equi_texture(equimap);
         let cubemap_tex = Self::create_cubemap_texture(
@@ -47,8 +157,7 @@ impl IblEnv {
             gl::RGBA32F,
             CONSTS.ibl.cubemap_roughnes_levels,
         );
-        let equi_to_cubemap_shader =
-            Shader::comp_with_path("shaders_stitched/equi_to_cubemap.comp")?;
+        let equi_to_cubemap_shader = Shader::comp_with_path(shader_path("equi_to_cubemap.comp"))?;
 
         cubemap_tex.add_label(cstr!("environment map"));
 
@@ -79,138 +188,6 @@ impl IblEnv {
         Ok((cubemap_tex, max_value))
     }
 
-    /// Computes the diffuse irradiance map from the cubemap
-    fn compute_irradiance_map(cubemap_tex_id: TextureId, max_radiance: f32) -> Result<GlTexture> {
-        let irradiance_tex = Self::create_cubemap_texture(IRRADIANCE_MAP_SIZE, gl::RGBA32F, 1);
-        let irradiance_shader = Shader::comp_with_path("shaders_stitched/irradiance.comp")?;
-
-        println!("Computing the irradiance map...");
-
-        let (sample_delta, max_time) = match max_radiance {
-            x if x > 0. && x < 1000. => (0.01, "a few seconds"),
-            x if x > 1000. && x < 50000. => (0.005, "up to 30 seconds"),
-            _ => (0.0015, "more than a minute"),
-        };
-
-        println!(
-            "Max radiance is: {}, setting sample_delta to: {}",
-            max_radiance, sample_delta
-        );
-
-        println!(
-            "WARNING: this MIGHT take {} and MAY make your PC unresponsive",
-            max_time
-        );
-
-        gl_time_query("irradiance compute shader", || {
-            irradiance_shader.use_shader(|| unsafe {
-                irradiance_shader.set_f32(sample_delta, cstr!("sampleDelta"));
-
-                gl::BindTextureUnit(0, cubemap_tex_id);
-                gl::BindImageTexture(
-                    1,
-                    irradiance_tex.id,
-                    0,
-                    gl::TRUE,
-                    0,
-                    gl::WRITE_ONLY,
-                    gl::RGBA32F,
-                );
-
-                // Split the compute invocations into smaller parts so the OS doesn't timeout the GPU
-                const STEP_SIZE: u32 = 8;
-                for offset_x in (0..IRRADIANCE_MAP_SIZE).step_by(STEP_SIZE as usize) {
-                    irradiance_shader.set_u32(offset_x as u32, cstr!("offset_x"));
-
-                    for offset_y in (0..IRRADIANCE_MAP_SIZE).step_by(STEP_SIZE as usize) {
-                        irradiance_shader.set_u32(offset_y as u32, cstr!("offset_y"));
-
-                        Self::dispatch_compute_divide(STEP_SIZE, STEP_SIZE, CUBEMAP_FACES);
-                        gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
-                        gl::Finish();
-                    }
-                }
-            });
-        });
-
-        Ok(irradiance_tex)
-    }
-
-    /// Computes the LD part of the specular integral
-    fn compute_prefilter_map(cubemap_tex_id: TextureId, max_radiance: f32) -> Result<GlTexture> {
-        let size = PREFILTER_MAP_SIZE;
-        let mip_levels = CONSTS.ibl.cubemap_roughnes_levels;
-        let prefilter_tex = Self::create_cubemap_texture(size, gl::RGBA32F, mip_levels);
-
-        println!("Computing the prefilter map...");
-
-        let (num_samples, max_time) = match max_radiance {
-            x if x > 0. && x < 1000. => (1024, "a few seconds"),
-            x if x > 1000. && x < 50000. => (512 * 1024, "up to 30 seconds"),
-            _ => (1024 * 1024, "more than a minute"),
-        };
-
-        println!(
-            "Max radiance is: {}, setting num_samples to: {}",
-            max_radiance, num_samples
-        );
-
-        println!(
-            "WARNING: this MIGHT take {} and MAY make your PC unresponsive",
-            max_time
-        );
-
-        let prefilter_shader = Shader::comp_with_path("shaders_stitched/prefilter.comp")?;
-        gl_time_query("split_sum prefiltering", || {
-            prefilter_shader.use_shader(|| unsafe {
-                gl::BindTextureUnit(0, cubemap_tex_id);
-
-                for lod in 0..CONSTS.ibl.cubemap_roughnes_levels {
-                    let roughness = lod as f32 / (CONSTS.ibl.cubemap_roughnes_levels as f32 - 1.);
-                    prefilter_shader.set_f32(roughness, cstr!("linearRoughness"));
-
-                    gl::BindImageTexture(
-                        1,
-                        prefilter_tex.id,
-                        lod,
-                        gl::TRUE,
-                        0,
-                        gl::WRITE_ONLY,
-                        gl::RGBA32F,
-                    );
-
-                    // Less rough mip levels don't need so many samples
-                    let num_samples = match lod {
-                        0 => 1024,
-                        1 => num_samples / 4,
-                        2 => num_samples / 2,
-                        _ => num_samples,
-                    }
-                    .max(1024);
-
-                    let mip_size = PREFILTER_MAP_SIZE / 2i32.pow(lod as u32);
-                    prefilter_shader.set_i32(num_samples, cstr!("sampleCount"));
-
-                    // Split the compute invocations into smaller parts so the OS doesn't timeout the GPU
-                    const STEP_SIZE: u32 = 8;
-                    for offset_x in (0..mip_size).step_by(STEP_SIZE as usize) {
-                        prefilter_shader.set_u32(offset_x as u32, cstr!("offset_x"));
-
-                        for offset_y in (0..mip_size).step_by(STEP_SIZE as usize) {
-                            prefilter_shader.set_u32(offset_y as u32, cstr!("offset_y"));
-
-                            Self::dispatch_compute_divide(STEP_SIZE, STEP_SIZE, CUBEMAP_FACES);
-                            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
-                            gl::Finish();
-                        }
-                    }
-                }
-            });
-        });
-
-        Ok(prefilter_tex)
-    }
-
     unsafe fn dispatch_compute_divide(x: u32, y: u32, z: u32) {
         gl::DispatchCompute(
             x / CONSTS.ibl.local_size_xy,
@@ -270,6 +247,185 @@ impl IblEnv {
     }
 }
 
+/// Computation of the irradiance and prefilter maps, split into blocks.
+///
+/// Bright environment maps need a lot of samples and the computation can take more than a minute,
+/// only a few blocks are computed in each frame, so the application stays responsive.
+pub struct IblComputation {
+    cubemap_tex: GlTexture,
+    irradiance_tex: GlTexture,
+    prefilter_tex: GlTexture,
+    irradiance_shader: Shader,
+    prefilter_shader: Shader,
+    /// Step between the samples of the irradiance integral
+    sample_delta: f32,
+    /// Number of samples of the rough prefilter map levels
+    num_samples: i32,
+    blocks: Vec<IblBlock>,
+    /// Index of the next block that will be computed
+    next_block: usize,
+    /// The finished maps are stored in the cache if there is a key
+    cache_key: Option<CacheKey>,
+}
+
+#[derive(Clone, Copy)]
+enum IblMap {
+    Irradiance,
+    Prefilter { lod: i32 },
+}
+
+/// Block of a map that is computed by one dispatch, small enough so the OS doesn't timeout the GPU
+#[derive(Clone, Copy)]
+struct IblBlock {
+    map: IblMap,
+    offset_x: u32,
+    offset_y: u32,
+}
+
+impl IblComputation {
+    fn new(equimap: HdrImage, cache_key: Option<CacheKey>) -> Result<Self> {
+        let levels = CONSTS.ibl.cubemap_roughnes_levels;
+
+        let (cubemap_tex, max_radiance) = IblEnv::load_cubemap_from_equi(equimap)?;
+        let irradiance_tex = IblEnv::create_cubemap_texture(IRRADIANCE_MAP_SIZE, gl::RGBA32F, 1);
+        let prefilter_tex = IblEnv::create_cubemap_texture(PREFILTER_MAP_SIZE, gl::RGBA32F, levels);
+
+        irradiance_tex.add_label(cstr!("irradiance map"));
+        prefilter_tex.add_label(cstr!("prefilter map"));
+
+        let irradiance_shader = Shader::comp_with_path(shader_path("irradiance.comp"))?;
+        let prefilter_shader = Shader::comp_with_path(shader_path("prefilter.comp"))?;
+
+        let (sample_delta, num_samples, max_time) = match max_radiance {
+            x if x > 0. && x < 1000. => (0.01, 1024, "a few seconds"),
+            x if x > 1000. && x < 50000. => (0.005, 512 * 1024, "up to 30 seconds"),
+            _ => (0.0015, 1024 * 1024, "more than a minute"),
+        };
+
+        println!(
+            "Max radiance is: {}, setting sample_delta to: {} and num_samples to: {}",
+            max_radiance, sample_delta, num_samples
+        );
+        println!("Computing the irradiance and prefilter maps, this MIGHT take {max_time}");
+
+        let map_blocks = |map: IblMap, size: i32| {
+            (0..size as u32)
+                .step_by(BLOCK_SIZE as usize)
+                .flat_map(move |offset_x| {
+                    (0..size as u32)
+                        .step_by(BLOCK_SIZE as usize)
+                        .map(move |offset_y| IblBlock {
+                            map,
+                            offset_x,
+                            offset_y,
+                        })
+                })
+        };
+
+        let mut blocks: Vec<IblBlock> =
+            map_blocks(IblMap::Irradiance, IRRADIANCE_MAP_SIZE).collect();
+        for lod in 0..levels {
+            let mip_size = PREFILTER_MAP_SIZE / 2i32.pow(lod as u32);
+            blocks.extend(map_blocks(IblMap::Prefilter { lod }, mip_size));
+        }
+
+        Ok(Self {
+            cubemap_tex,
+            irradiance_tex,
+            prefilter_tex,
+            irradiance_shader,
+            prefilter_shader,
+            sample_delta,
+            num_samples,
+            blocks,
+            next_block: 0,
+            cache_key,
+        })
+    }
+
+    /// Computes the next blocks for one frame and reports the progress.
+    ///
+    /// Returns the environment when all of the blocks are done.
+    pub fn advance(mut self, progress: &LoadProgress) -> Upload<IblEnv> {
+        self.step(COMPUTE_FRAME_BUDGET);
+        progress.set(self.next_block as f32 / self.blocks.len() as f32);
+
+        match self.next_block < self.blocks.len() {
+            true => Upload::Pending(self),
+            false => Upload::Done(self.finish()),
+        }
+    }
+
+    /// Computes the next blocks until the time budget runs out, at least one block is computed
+    fn step(&mut self, budget: Duration) {
+        let start = Instant::now();
+
+        while let Some(&block) = self.blocks.get(self.next_block) {
+            self.compute_block(block);
+            self.next_block += 1;
+
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    fn compute_block(&self, block: IblBlock) {
+        let (shader, target, lod) = match block.map {
+            IblMap::Irradiance => (&self.irradiance_shader, &self.irradiance_tex, 0),
+            IblMap::Prefilter { lod } => (&self.prefilter_shader, &self.prefilter_tex, lod),
+        };
+
+        shader.use_shader(|| unsafe {
+            match block.map {
+                IblMap::Irradiance => shader.set_f32(self.sample_delta, cstr!("sampleDelta")),
+                IblMap::Prefilter { lod } => {
+                    let roughness = lod as f32 / (CONSTS.ibl.cubemap_roughnes_levels as f32 - 1.);
+                    shader.set_f32(roughness, cstr!("linearRoughness"));
+
+                    // Less rough mip levels don't need so many samples
+                    let num_samples = match lod {
+                        0 => 1024,
+                        1 => self.num_samples / 4,
+                        2 => self.num_samples / 2,
+                        _ => self.num_samples,
+                    }
+                    .max(1024);
+                    shader.set_i32(num_samples, cstr!("sampleCount"));
+                }
+            }
+
+            shader.set_u32(block.offset_x, cstr!("offset_x"));
+            shader.set_u32(block.offset_y, cstr!("offset_y"));
+
+            gl::BindTextureUnit(0, self.cubemap_tex.id);
+            gl::BindImageTexture(1, target.id, lod, gl::TRUE, 0, gl::WRITE_ONLY, gl::RGBA32F);
+
+            IblEnv::dispatch_compute_divide(BLOCK_SIZE, BLOCK_SIZE, CUBEMAP_FACES);
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+            // Waiting for each block keeps the time budget accurate
+            gl::Finish();
+        });
+    }
+
+    fn finish(self) -> IblEnv {
+        let iblenv = IblEnv {
+            cubemap_tex: self.cubemap_tex,
+            irradiance_tex: self.irradiance_tex,
+            prefilter_tex: self.prefilter_tex,
+        };
+
+        if let Some(key) = self.cache_key {
+            match iblenv.read_back() {
+                Ok(cached) => cached.store(key),
+                Err(e) => eprintln!("Couldn't read back the IBL maps: {e}"),
+            }
+        }
+
+        iblenv
+    }
+}
+
 /// Computes the BRDF part of the specular integral
 pub fn dfg_integration() -> Result<GlTexture> {
     let brdf_lut = GlTexture::new(gl::TEXTURE_2D);
@@ -283,7 +439,7 @@ pub fn dfg_integration() -> Result<GlTexture> {
         gl::TextureParameteri(brdf_lut.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
     };
 
-    let dfg_integration_shader = Shader::comp_with_path("shaders_stitched/dfg_integration.comp")?;
+    let dfg_integration_shader = Shader::comp_with_path(shader_path("dfg_integration.comp"))?;
 
     gl_time_query("split_sum dfg integration", || {
         dfg_integration_shader.use_shader(|| unsafe {
@@ -301,18 +457,31 @@ pub fn dfg_integration() -> Result<GlTexture> {
     Ok(brdf_lut)
 }
 
-struct HdrImage {
+/// Decoded HDR equirectangular map
+pub struct HdrImage {
     pixels: Vec<f32>,
     width: u32,
     height: u32,
     max_value: f32,
 }
 
-fn load_hdr_image(path: &str) -> Result<HdrImage> {
-    let file = File::open(path)?;
-    let file = BufReader::new(file);
+impl HdrImage {
+    /// Equirectangular map with the same radiance in every direction
+    pub fn uniform(radiance: f32) -> Self {
+        let (width, height) = (64, 32);
+
+        Self {
+            pixels: vec![radiance; (width * height * 3) as usize],
+            width,
+            height,
+            max_value: radiance,
+        }
+    }
+}
 
-    let decoder = hdr::HdrDecoder::new(file)?;
+/// Decodes the contents of a Radiance HDR file
+pub fn decode_hdr_image(bytes: &[u8]) -> Result<HdrImage> {
+    let decoder = hdr::HdrDecoder::new(bytes)?;
     let metadata = decoder.metadata();
     let (width, height) = (metadata.width, metadata.height);
 
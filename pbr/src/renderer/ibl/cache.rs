@@ -0,0 +1,439 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use eyre::{eyre, Result};
+use gl::types::GLenum;
+use shader_constants::CONSTS;
+
+use crate::{
+    config::{ibl_cache_dir, shader_path},
+    ogl::texture::GlTexture,
+    resources::LoadProgress,
+};
+
+use super::{CUBEMAP_FACES, IRRADIANCE_MAP_SIZE, PREFILTER_MAP_SIZE};
+
+const MAGIC: &[u8; 8] = b"PBRIBL\0\0";
+/// Has to be incremented when the file format changes
+const FORMAT_VERSION: u32 = 1;
+
+/// Identifies the source of the cached maps.
+///
+/// The cache is invalid if the HDR file, the IBL parameters or the compute shaders change.
+pub struct CacheKey {
+    source_path: String,
+    content_hash: [u8; 32],
+    params_hash: [u8; 32],
+}
+
+impl CacheKey {
+    /// `contents` are the contents of the HDR file at `path`
+    pub fn new(path: &str, contents: &[u8]) -> Result<Self> {
+        let source_path = fs::canonicalize(path)?.to_string_lossy().into_owned();
+        let content_hash = *blake3::hash(contents).as_bytes();
+
+        let mut params = blake3::Hasher::new();
+        for param in [
+            CONSTS.ibl.cubemap_size,
+            CONSTS.ibl.cubemap_roughnes_levels,
+            IRRADIANCE_MAP_SIZE,
+            PREFILTER_MAP_SIZE,
+        ] {
+            params.update(&param.to_le_bytes());
+        }
+
+        for shader in ["equi_to_cubemap.comp", "irradiance.comp", "prefilter.comp"] {
+            params.update(&fs::read(shader_path(shader))?);
+        }
+
+        Ok(Self {
+            source_path,
+            content_hash,
+            params_hash: *params.finalize().as_bytes(),
+        })
+    }
+
+    /// Each source file has a single cache file, which is overwritten when the source changes
+    fn cache_file(&self, dir: &Path) -> PathBuf {
+        let name = blake3::hash(self.source_path.as_bytes()).to_hex();
+        dir.join(format!("{}.iblcache", &name[..16]))
+    }
+}
+
+/// All levels of a cubemap texture
+pub struct CachedTexture {
+    pub internal_format: GLenum,
+    pub size: i32,
+    /// Pixels of all faces of each mip level
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CachedTexture {
+    /// Reads the texture back from the GPU
+    pub fn read_back(tex: &GlTexture) -> Result<Self> {
+        let mut internal_format = 0;
+        let mut size = 0;
+        let mut num_levels = 0;
+
+        unsafe {
+            gl::GetTextureLevelParameteriv(
+                tex.id,
+                0,
+                gl::TEXTURE_INTERNAL_FORMAT,
+                &mut internal_format,
+            );
+            gl::GetTextureLevelParameteriv(tex.id, 0, gl::TEXTURE_WIDTH, &mut size);
+            gl::GetTextureParameteriv(tex.id, gl::TEXTURE_IMMUTABLE_LEVELS, &mut num_levels);
+        }
+
+        let internal_format = internal_format as GLenum;
+        let (typ, pixel_size) = pixel_type(internal_format)?;
+
+        let levels = (0..num_levels)
+            .map(|level| {
+                let mut pixels = vec![0u8; level_len(size, level, pixel_size)];
+
+                unsafe {
+                    gl::GetTextureImage(
+                        tex.id,
+                        level,
+                        gl::RGBA,
+                        typ,
+                        pixels.len() as _,
+                        pixels.as_mut_ptr() as _,
+                    );
+                }
+
+                pixels
+            })
+            .collect();
+
+        Ok(Self {
+            internal_format,
+            size,
+            levels,
+        })
+    }
+
+    /// Uploads the pixels into a texture created with the same format, size and level count
+    pub fn upload(&self, tex: &GlTexture) -> Result<()> {
+        let (typ, _) = pixel_type(self.internal_format)?;
+
+        for (level, pixels) in self.levels.iter().enumerate() {
+            let size = (self.size >> level).max(1);
+
+            unsafe {
+                gl::TextureSubImage3D(
+                    tex.id,
+                    level as _,
+                    0,
+                    0,
+                    0,
+                    size,
+                    size,
+                    CUBEMAP_FACES as _,
+                    gl::RGBA,
+                    typ,
+                    pixels.as_ptr() as _,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_all(&self.internal_format.to_le_bytes())?;
+        w.write_all(&self.size.to_le_bytes())?;
+        w.write_all(&(self.levels.len() as u32).to_le_bytes())?;
+
+        for level in &self.levels {
+            w.write_all(level)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a texture of the expected size and level count, the file is corrupted otherwise
+    fn read(r: &mut impl Read, expected_size: i32, expected_levels: i32) -> Result<Self> {
+        let internal_format = read_u32(r)?;
+        let size = read_u32(r)? as i32;
+        let num_levels = read_u32(r)? as i32;
+
+        let (_, pixel_size) = pixel_type(internal_format)?;
+        if size != expected_size || num_levels != expected_levels {
+            return Err(eyre!(
+                "Invalid texture dimensions: size {size} with {num_levels} levels"
+            ));
+        }
+
+        let levels = (0..num_levels)
+            .map(|level| {
+                let mut pixels = vec![0u8; level_len(size, level, pixel_size)];
+                r.read_exact(&mut pixels)?;
+                Ok(pixels)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            internal_format,
+            size,
+            levels,
+        })
+    }
+}
+
+/// Precomputed maps of an IBL environment
+pub struct CachedIbl {
+    pub cubemap: CachedTexture,
+    pub irradiance: CachedTexture,
+    pub prefilter: CachedTexture,
+}
+
+impl CachedIbl {
+    /// Loads the maps from the cache, returns None if there's no valid cache file
+    pub fn load(key: &CacheKey, progress: &LoadProgress) -> Result<Option<Self>> {
+        let Some(dir) = ibl_cache_dir() else {
+            return Ok(None);
+        };
+
+        let file = match File::open(key.cache_file(dir)) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+
+        Self::read(key, &mut BufReader::new(file), progress)
+    }
+
+    /// Returns None if the cache belongs to a different source or parameters
+    fn read(key: &CacheKey, r: &mut impl Read, progress: &LoadProgress) -> Result<Option<Self>> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(r)? != FORMAT_VERSION {
+            return Ok(None);
+        }
+
+        // Checked before allocating, the length can be anything in a corrupted file
+        let path_len = read_u32(r)? as usize;
+        if path_len != key.source_path.len() {
+            return Ok(None);
+        }
+
+        let mut source_path = vec![0u8; path_len];
+        r.read_exact(&mut source_path)?;
+
+        let mut content_hash = [0u8; 32];
+        r.read_exact(&mut content_hash)?;
+        let mut params_hash = [0u8; 32];
+        r.read_exact(&mut params_hash)?;
+
+        if source_path != key.source_path.as_bytes()
+            || content_hash != key.content_hash
+            || params_hash != key.params_hash
+        {
+            return Ok(None);
+        }
+
+        let levels = CONSTS.ibl.cubemap_roughnes_levels;
+        let cubemap = CachedTexture::read(r, CONSTS.ibl.cubemap_size, levels)?;
+        progress.set(0.8);
+        let irradiance = CachedTexture::read(r, IRRADIANCE_MAP_SIZE, 1)?;
+        let prefilter = CachedTexture::read(r, PREFILTER_MAP_SIZE, levels)?;
+        progress.set(1.);
+
+        Ok(Some(Self {
+            cubemap,
+            irradiance,
+            prefilter,
+        }))
+    }
+
+    /// Writes the cache file on a separate thread
+    pub fn store(self, key: CacheKey) {
+        let Some(dir) = ibl_cache_dir() else {
+            return;
+        };
+        let dir = dir.to_path_buf();
+
+        let spawned = thread::Builder::new()
+            .name("IBL cache writer".to_string())
+            .spawn(move || {
+                if let Err(e) = self.write(&key, &dir) {
+                    eprintln!("Couldn't write the IBL cache of '{}': {e}", key.source_path);
+                }
+            });
+
+        if let Err(e) = spawned {
+            eprintln!("Couldn't spawn the IBL cache writer thread: {e}");
+        }
+    }
+
+    fn write(&self, key: &CacheKey, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+
+        let path = key.cache_file(dir);
+        // Write into a temporary file first, so a partially written cache is never read
+        let tmp_path = path.with_extension("tmp");
+
+        let mut w = BufWriter::new(File::create(&tmp_path)?);
+        self.write_to(key, &mut w)?;
+        w.flush()?;
+        drop(w);
+
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    fn write_to(&self, key: &CacheKey, w: &mut impl Write) -> Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        w.write_all(&(key.source_path.len() as u32).to_le_bytes())?;
+        w.write_all(key.source_path.as_bytes())?;
+        w.write_all(&key.content_hash)?;
+        w.write_all(&key.params_hash)?;
+
+        self.cubemap.write(w)?;
+        self.irradiance.write(w)?;
+        self.prefilter.write(w)?;
+        Ok(())
+    }
+}
+
+/// Returns the pixel type used for transfers and the size of a pixel in bytes
+fn pixel_type(internal_format: GLenum) -> Result<(GLenum, usize)> {
+    match internal_format {
+        gl::RGBA32F => Ok((gl::FLOAT, 16)),
+        gl::RGBA16F => Ok((gl::HALF_FLOAT, 8)),
+        f => Err(eyre!("Unsupported cubemap format: {f:#x}")),
+    }
+}
+
+/// Size of all faces of a mip level in bytes
+fn level_len(size: i32, level: i32, pixel_size: usize) -> usize {
+    let size = (size >> level).max(1) as usize;
+    size * size * CUBEMAP_FACES as usize * pixel_size
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Size of an RGBA16F pixel, smaller than the RGBA32F of the real maps
+    const PIXEL_SIZE: usize = 8;
+
+    fn test_key() -> CacheKey {
+        CacheKey {
+            source_path: "/envmaps/test.hdr".to_string(),
+            content_hash: [1; 32],
+            params_hash: [2; 32],
+        }
+    }
+
+    fn test_texture(size: i32, num_levels: i32) -> CachedTexture {
+        CachedTexture {
+            internal_format: gl::RGBA16F,
+            size,
+            levels: (0..num_levels)
+                .map(|level| vec![level as u8 + 1; level_len(size, level, PIXEL_SIZE)])
+                .collect(),
+        }
+    }
+
+    fn test_ibl() -> CachedIbl {
+        let levels = CONSTS.ibl.cubemap_roughnes_levels;
+
+        CachedIbl {
+            cubemap: test_texture(CONSTS.ibl.cubemap_size, levels),
+            irradiance: test_texture(IRRADIANCE_MAP_SIZE, 1),
+            prefilter: test_texture(PREFILTER_MAP_SIZE, levels),
+        }
+    }
+
+    fn written(ibl: &CachedIbl, key: &CacheKey) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ibl.write_to(key, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn read(bytes: &[u8], key: &CacheKey) -> Result<Option<CachedIbl>> {
+        CachedIbl::read(key, &mut &bytes[..], &LoadProgress::default())
+    }
+
+    /// Offset of the size of the environment map, after the header and the internal format
+    fn cubemap_size_offset(key: &CacheKey) -> usize {
+        MAGIC.len() + 4 + 4 + key.source_path.len() + 32 + 32 + 4
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = test_key();
+        let ibl = test_ibl();
+
+        let cached = read(&written(&ibl, &key), &key).unwrap().unwrap();
+
+        for (expected, actual) in [
+            (&ibl.cubemap, &cached.cubemap),
+            (&ibl.irradiance, &cached.irradiance),
+            (&ibl.prefilter, &cached.prefilter),
+        ] {
+            assert_eq!(expected.internal_format, actual.internal_format);
+            assert_eq!(expected.size, actual.size);
+            assert!(expected.levels == actual.levels);
+        }
+    }
+
+    #[test]
+    fn different_source() {
+        let ibl = test_ibl();
+        let bytes = written(&ibl, &test_key());
+
+        let mut key = test_key();
+        key.content_hash = [3; 32];
+        assert!(matches!(read(&bytes, &key), Ok(None)));
+
+        key.source_path = "/envmaps/other_test.hdr".to_string();
+        assert!(matches!(read(&bytes, &key), Ok(None)));
+    }
+
+    #[test]
+    fn corrupted_path_length() {
+        let key = test_key();
+        let mut bytes = written(&test_ibl(), &key);
+
+        let offset = MAGIC.len() + 4;
+        bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(matches!(read(&bytes, &key), Ok(None)));
+    }
+
+    #[test]
+    fn corrupted_texture_size() {
+        let key = test_key();
+        let mut bytes = written(&test_ibl(), &key);
+
+        let offset = cubemap_size_offset(&key);
+        bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(read(&bytes, &key).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let key = test_key();
+        let bytes = written(&test_ibl(), &key);
+
+        for len in [4, cubemap_size_offset(&key), bytes.len() - 1] {
+            assert!(read(&bytes[..len], &key).is_err());
+        }
+    }
+}
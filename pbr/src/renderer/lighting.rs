@@ -1,42 +1,176 @@
-use glam::Vec4;
+use glam::{Vec3, Vec4};
 use shader_constants::CONSTS;
 
 use crate::ogl::uniform_buffer::UniformBufferElement;
 
-/// Uniform buffer element that stores the lighing data
+#[repr(u32)]
+#[derive(bytemuck::NoUninit, Copy, Clone, PartialEq, Debug)]
+pub enum LightType {
+    Point = 0,
+    Directional = 1,
+    Spot = 2,
+    Rect = 3,
+    Disk = 4,
+    Tube = 5,
+}
+
+impl LightType {
+    pub const ALL: [LightType; 6] = [
+        LightType::Point,
+        LightType::Directional,
+        LightType::Spot,
+        LightType::Rect,
+        LightType::Disk,
+        LightType::Tube,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            LightType::Point => "Point",
+            LightType::Directional => "Directional",
+            LightType::Spot => "Spot",
+            LightType::Rect => "Rect",
+            LightType::Disk => "Disk",
+            LightType::Tube => "Tube",
+        }
+    }
+
+    pub fn is_area(self) -> bool {
+        matches!(self, LightType::Rect | LightType::Disk | LightType::Tube)
+    }
+
+    fn from_u32(typ: u32) -> Self {
+        match typ {
+            0 => LightType::Point,
+            1 => LightType::Directional,
+            2 => LightType::Spot,
+            3 => LightType::Rect,
+            4 => LightType::Disk,
+            5 => LightType::Tube,
+            _ => unreachable!("Invalid light type: {typ}"),
+        }
+    }
+}
+
+/// A single light, the layout matches the GLSL std430 layout
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub struct Light {
+    pub position: Vec4,
+    /// Direction the light is shining in, used by directional, spot, rect and disk lights.
+    /// Axis of tube lights.
+    pub direction: Vec4,
+    pub color: Vec3,
+    /// Luminous intensity of point and spot lights, illuminance of directional lights,
+    /// luminance of area lights
+    pub intensity: f32,
+    // Stored as u32 so that the lights can be cast to bytes, use typ() and set_typ()
+    typ: u32,
+    /// Distance where the light stops affecting the surfaces, 0 means infinite range
+    pub range: f32,
+    /// Cosine of the angle where the spot light cone starts to fade out
+    pub inner_cone_cos: f32,
+    /// Cosine of the angle where the spot light cone ends
+    pub outer_cone_cos: f32,
+    /// First layer of the light's shadow map, -1 if the light doesn't cast shadows.
+    /// Set by the shadow mapping pass.
+    pub shadow_index: i32,
+    casts_shadows: u32,
+    /// Width of rect lights, diameter of disk lights and length of tube lights
+    pub width: f32,
+    /// Height of rect lights and diameter of tube lights
+    pub height: f32,
+}
+
+impl Light {
+    /// A light of the given type at the position that shines towards the origin
+    pub fn new(typ: LightType, position: Vec3) -> Self {
+        let direction = (-position).try_normalize().unwrap_or(Vec3::NEG_Y);
+        let (width, height) = match typ {
+            LightType::Tube => (1., 0.05),
+            _ => (1., 0.5),
+        };
+
+        Self {
+            position: position.extend(1.),
+            direction: direction.extend(0.),
+            color: Vec3::ONE,
+            intensity: 1.,
+            typ: typ as u32,
+            range: 0.,
+            inner_cone_cos: 25f32.to_radians().cos(),
+            outer_cone_cos: 35f32.to_radians().cos(),
+            shadow_index: -1,
+            casts_shadows: 1,
+            width,
+            height,
+        }
+    }
+
+    pub fn typ(&self) -> LightType {
+        LightType::from_u32(self.typ)
+    }
+
+    pub fn set_typ(&mut self, typ: LightType) {
+        self.typ = typ as u32;
+    }
+
+    pub fn casts_shadows(&self) -> bool {
+        self.casts_shadows != 0
+    }
+
+    pub fn set_casts_shadows(&mut self, casts_shadows: bool) {
+        self.casts_shadows = casts_shadows as u32;
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        self.direction
+            .truncate()
+            .try_normalize()
+            .unwrap_or(Vec3::NEG_Y)
+    }
+
+    /// Inner angle of the spot light cone in radians
+    pub fn inner_cone_angle(&self) -> f32 {
+        self.inner_cone_cos.clamp(-1., 1.).acos()
+    }
+
+    /// Outer angle of the spot light cone in radians
+    pub fn outer_cone_angle(&self) -> f32 {
+        self.outer_cone_cos.clamp(-1., 1.).acos()
+    }
+
+    /// Sets the angles of the spot light cone in radians, the inner angle can't be larger than the outer one
+    pub fn set_cone_angles(&mut self, inner: f32, outer: f32) {
+        let outer = outer.clamp(0., 90f32.to_radians());
+        let inner = inner.clamp(0., outer);
+
+        self.inner_cone_cos = inner.cos();
+        self.outer_cone_cos = outer.cos();
+    }
+}
+
+/// The lights that are in the scene when the app starts
+pub fn default_lights() -> Vec<Light> {
+    vec![Light::new(LightType::Point, Vec3::new(-1., 1., 1.))]
+}
+
+/// Uniform buffer element that stores the lighing data.
+///
+/// The lights themselves are stored in an SSBO.
 #[derive(bytemuck::NoUninit, Copy, Clone, PartialEq, Debug)]
 #[repr(C)]
 pub struct Lighting {
-    pub light_pos: [Vec4; 4],
-    pub light_color: [Vec4; 4],
     pub cam_pos: Vec4,
-    pub lights: u32,
+    pub num_lights: u32,
     padding: [u32; 3],
 }
 
 impl Lighting {
     pub fn new() -> Self {
-        let cam_pos = Vec4::ZERO;
-        let lights = 1;
-        let light_pos = [
-            Vec4::new(-1., 1., 1., 1.0),
-            Vec4::new(10., 10., 10.0, 1.0),
-            Vec4::ZERO,
-            Vec4::ZERO,
-        ];
-
-        let light_color = [
-            Vec4::new(1., 1., 1., 0.),
-            Vec4::new(1., 1., 1., 0.),
-            Vec4::ONE,
-            Vec4::ONE,
-        ];
-
         Self {
-            cam_pos,
-            light_pos,
-            light_color,
-            lights,
+            cam_pos: Vec4::ZERO,
+            num_lights: 0,
             padding: [0; 3],
         }
     }
@@ -3,7 +3,7 @@ use shader_constants::CONSTS;
 use crate::ogl::uniform_buffer::UniformBufferElement;
 
 /// Uniform buffer element that stores the material settings
-#[derive(Default, bytemuck::NoUninit, Copy, Clone, PartialEq, Debug)]
+#[derive(Default, bytemuck::Pod, bytemuck::Zeroable, Copy, Clone, PartialEq, Debug)]
 #[repr(C)]
 pub struct PbrMaterial {
     pub base_color_factor: [f32; 4],
@@ -0,0 +1,149 @@
+use glam::Mat4;
+use shader_constants::CONSTS;
+
+use crate::{
+    ogl::{shader::Shader, ssbo::Ssbo},
+    scene::{Primitive, SampledTexture, SharedGeometry},
+};
+
+use super::{material::PbrMaterial, DrawItem};
+
+/// Command of glMultiDrawElementsIndirect
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawElementsIndirectCommand {
+    count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    /// Index of the draw in the SSBOs
+    base_instance: u32,
+}
+
+/// Element of the draw materials SSBO, must match structs/pbrMaterial.glsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawMaterial {
+    material: PbrMaterial,
+    /// Bindless handles, zero for missing textures
+    textures: [u64; 8],
+}
+
+impl DrawMaterial {
+    pub fn new(material: PbrMaterial, primitive: &Primitive) -> Self {
+        let handle = |tex: &Option<SampledTexture>| {
+            tex.as_ref().and_then(|t| t.bindless_handle()).unwrap_or(0)
+        };
+
+        let pbr = &primitive.pbr_material;
+        let cc = primitive.clearcoat.as_ref();
+
+        Self {
+            material,
+            textures: [
+                handle(&pbr.base_color_texture),
+                handle(&pbr.mr_texture),
+                handle(&pbr.normal_texture),
+                handle(&pbr.occlusion_texture),
+                handle(&pbr.emissive_texture),
+                cc.map_or(0, |c| handle(&c.intensity_texture)),
+                cc.map_or(0, |c| handle(&c.roughness_texture)),
+                cc.map_or(0, |c| handle(&c.normal_texture)),
+            ],
+        }
+    }
+}
+
+/// Alternative path that draws the whole scene with a few glMultiDrawElementsIndirect calls.
+///
+/// The geometry is in shared buffers, the transforms and materials of the draws are in SSBOs
+/// and the textures are accessed with bindless handles.
+pub struct MultiDraw {
+    transforms: Ssbo<{ CONSTS.buffer_bindings.draw_transforms }>,
+    materials: Ssbo<{ CONSTS.buffer_bindings.draw_materials }>,
+    indirect_buffer: u32,
+}
+
+impl MultiDraw {
+    pub fn new() -> Self {
+        let mut indirect_buffer = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut indirect_buffer);
+        }
+
+        Self {
+            transforms: Ssbo::new::<Mat4>(&[]),
+            materials: Ssbo::new::<DrawMaterial>(&[]),
+            indirect_buffer,
+        }
+    }
+
+    /// Uploads the per-draw data, the materials are in the same order as the draws
+    pub fn upload(
+        &mut self,
+        draws: &[DrawItem],
+        materials: &[DrawMaterial],
+        geometry: &SharedGeometry,
+    ) {
+        let transforms: Vec<Mat4> = draws.iter().map(|d| d.transform).collect();
+
+        let commands: Vec<DrawElementsIndirectCommand> = draws
+            .iter()
+            .enumerate()
+            .map(|(i, draw)| {
+                let range = geometry.ranges[draw.primitive.index];
+
+                DrawElementsIndirectCommand {
+                    count: range.num_indices,
+                    instance_count: 1,
+                    first_index: range.first_index,
+                    base_vertex: range.base_vertex,
+                    base_instance: i as u32,
+                }
+            })
+            .collect();
+
+        self.transforms.update(&transforms);
+        self.materials.update(materials);
+
+        let bytes: &[u8] = bytemuck::cast_slice(&commands);
+        unsafe {
+            gl::NamedBufferData(
+                self.indirect_buffer,
+                bytes.len() as _,
+                bytes.as_ptr() as _,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+    }
+
+    /// Draws a range of the uploaded draws with a single call
+    pub fn draw(&self, shader: &Shader, geometry: &SharedGeometry, first: usize, count: usize) {
+        self.transforms.bind();
+        self.materials.bind();
+
+        shader.use_shader(|| unsafe {
+            gl::BindVertexArray(geometry.vao.id);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.indirect_buffer);
+
+            gl::MultiDrawElementsIndirect(
+                gl::TRIANGLES,
+                gl::UNSIGNED_INT,
+                (first * std::mem::size_of::<DrawElementsIndirectCommand>()) as _,
+                count as i32,
+                0,
+            );
+
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+            gl::BindVertexArray(0);
+        });
+    }
+}
+
+impl Drop for MultiDraw {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.indirect_buffer);
+        }
+    }
+}
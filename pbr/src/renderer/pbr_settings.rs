@@ -1,6 +1,9 @@
 use shader_constants::CONSTS;
 
-use crate::{app_settings::DiffuseType, ogl::uniform_buffer::UniformBufferElement};
+use crate::{
+    app_settings::{DebugView, DiffuseType},
+    ogl::uniform_buffer::UniformBufferElement,
+};
 
 #[derive(bytemuck::NoUninit, Copy, Clone, PartialEq)]
 #[repr(C)]
@@ -12,6 +15,25 @@ pub struct PbrSettings {
     ibl_enabled: u32,
     pub diffuse_type: DiffuseType,
     energycomp_enabled: u32,
+    shadows_enabled: u32,
+    /// Radius of the PCF kernel in texels
+    pub pcf_radius: u32,
+    /// Constant depth bias, scaled by the slope of the surface
+    pub shadow_bias: f32,
+    /// Offset of the shadow lookup position along the normal in world units
+    pub normal_bias: f32,
+    ssao_enabled: u32,
+    /// View-space radius of the ambient occlusion in world units
+    pub ssao_radius: f32,
+    /// Exponent of the ambient occlusion visibility
+    pub ssao_intensity: f32,
+    ssr_enabled: u32,
+    /// Maximum length of the reflected rays in world units
+    pub ssr_max_distance: f32,
+    /// Depth of the surfaces that the reflected rays can hit, in world units
+    pub ssr_thickness: f32,
+    /// Replaces the shaded color with one of the material inputs or lighting terms
+    pub debug_view: DebugView,
 }
 
 impl PbrSettings {
@@ -22,6 +44,17 @@ impl PbrSettings {
             ibl_enabled: 1,
             diffuse_type: DiffuseType::Lambert,
             energycomp_enabled: 1,
+            shadows_enabled: 1,
+            pcf_radius: 1,
+            shadow_bias: 0.0005,
+            normal_bias: 0.01,
+            ssao_enabled: 1,
+            ssao_radius: 0.5,
+            ssao_intensity: 1.,
+            ssr_enabled: 1,
+            ssr_max_distance: 10.,
+            ssr_thickness: 0.2,
+            debug_view: DebugView::None,
         }
     }
 
@@ -41,6 +74,18 @@ impl PbrSettings {
         self.energycomp_enabled = if energycomp_enabled { 1 } else { 0 };
     }
 
+    pub fn set_shadows_enabled(&mut self, shadows_enabled: bool) {
+        self.shadows_enabled = if shadows_enabled { 1 } else { 0 };
+    }
+
+    pub fn set_ssao_enabled(&mut self, ssao_enabled: bool) {
+        self.ssao_enabled = if ssao_enabled { 1 } else { 0 };
+    }
+
+    pub fn set_ssr_enabled(&mut self, ssr_enabled: bool) {
+        self.ssr_enabled = if ssr_enabled { 1 } else { 0 };
+    }
+
     pub fn clearcoat_enabled(&self) -> bool {
         self.clearcoat_enabled != 0
     }
@@ -56,6 +101,18 @@ impl PbrSettings {
     pub fn energycomp_enabled(&self) -> bool {
         self.energycomp_enabled != 0
     }
+
+    pub fn shadows_enabled(&self) -> bool {
+        self.shadows_enabled != 0
+    }
+
+    pub fn ssao_enabled(&self) -> bool {
+        self.ssao_enabled != 0
+    }
+
+    pub fn ssr_enabled(&self) -> bool {
+        self.ssr_enabled != 0
+    }
 }
 
 impl UniformBufferElement for PbrSettings {
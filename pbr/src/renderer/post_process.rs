@@ -0,0 +1,435 @@
+use cstr::cstr;
+use eyre::Result;
+use glam::Mat4;
+use shader_constants::CONSTS;
+
+use crate::{
+    app_settings::{Antialiasing, AppSettings, ViewportDim},
+    config::shader_path,
+    ogl::{framebuffer::GlFramebuffer, shader::Shader, texture::GlTexture, vao::Vao},
+};
+
+use super::{bloom::Bloom, screenshot::Screenshot, taa::Taa, transforms::Transforms};
+
+/// Dispatches a compute shader with one invocation per pixel of the image
+pub fn dispatch_compute_2d(width: i32, height: i32) {
+    let local_size = CONSTS.post_process.local_size_xy;
+    let groups_x = (width as u32).div_ceil(local_size);
+    let groups_y = (height as u32).div_ceil(local_size);
+
+    unsafe {
+        gl::DispatchCompute(groups_x, groups_y, 1);
+    }
+}
+
+/// Where the result of a frame ends up
+#[derive(PartialEq, Clone, Copy)]
+pub enum FrameOutput {
+    /// The viewport of the default framebuffer
+    Window,
+    /// Only the TAA history is updated, the frame isn't tonemapped
+    History,
+    /// A separate target that is read back
+    Screenshot,
+}
+
+/// Number of samples of the HDR target when MSAA is enabled
+const MSAA_SAMPLES: i32 = 4;
+
+/// Multisampled buffers the scene is rendered into with MSAA, resolved into the HDR target
+struct MsaaBuffers {
+    framebuffer: GlFramebuffer,
+    _color: GlTexture,
+    _depth: GlTexture,
+}
+
+impl MsaaBuffers {
+    fn new(width: i32, height: i32) -> Result<Self> {
+        let color = GlTexture::new(gl::TEXTURE_2D_MULTISAMPLE);
+        let depth = GlTexture::new(gl::TEXTURE_2D_MULTISAMPLE);
+
+        unsafe {
+            gl::TextureStorage2DMultisample(
+                color.id,
+                MSAA_SAMPLES,
+                gl::RGBA16F,
+                width,
+                height,
+                gl::TRUE,
+            );
+            gl::TextureStorage2DMultisample(
+                depth.id,
+                MSAA_SAMPLES,
+                gl::DEPTH_COMPONENT32F,
+                width,
+                height,
+                gl::TRUE,
+            );
+        }
+
+        color.add_label(cstr!("HDR color MSAA"));
+        depth.add_label(cstr!("HDR depth MSAA"));
+
+        let framebuffer = GlFramebuffer::new();
+        framebuffer.attach_texture(gl::COLOR_ATTACHMENT0, &color, 0);
+        framebuffer.attach_texture(gl::DEPTH_ATTACHMENT, &depth, 0);
+        framebuffer.check_status()?;
+        framebuffer.add_label(cstr!("HDR framebuffer MSAA"));
+
+        Ok(Self {
+            framebuffer,
+            _color: color,
+            _depth: depth,
+        })
+    }
+}
+
+/// Offscreen target the scene is rendered into
+struct HdrTarget {
+    framebuffer: GlFramebuffer,
+    color: GlTexture,
+    depth: GlTexture,
+    /// Only present when MSAA is enabled
+    msaa: Option<MsaaBuffers>,
+    width: i32,
+    height: i32,
+}
+
+impl HdrTarget {
+    fn new(width: i32, height: i32, msaa: bool) -> Result<Self> {
+        let color = GlTexture::new(gl::TEXTURE_2D);
+        let depth = GlTexture::new(gl::TEXTURE_2D);
+
+        unsafe {
+            gl::TextureStorage2D(color.id, 1, gl::RGBA16F, width, height);
+            gl::TextureParameteri(color.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(color.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(color.id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(color.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+            gl::TextureStorage2D(depth.id, 1, gl::DEPTH_COMPONENT32F, width, height);
+            gl::TextureParameteri(depth.id, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TextureParameteri(depth.id, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        }
+
+        color.add_label(cstr!("HDR color"));
+        depth.add_label(cstr!("HDR depth"));
+
+        let framebuffer = GlFramebuffer::new();
+        framebuffer.attach_texture(gl::COLOR_ATTACHMENT0, &color, 0);
+        framebuffer.attach_texture(gl::DEPTH_ATTACHMENT, &depth, 0);
+        framebuffer.check_status()?;
+        framebuffer.add_label(cstr!("HDR framebuffer"));
+
+        let msaa = match msaa {
+            true => Some(MsaaBuffers::new(width, height)?),
+            false => None,
+        };
+
+        Ok(Self {
+            framebuffer,
+            color,
+            depth,
+            msaa,
+            width,
+            height,
+        })
+    }
+}
+
+/// Tonemapped image for FXAA, which works on perceptual values, or for the screenshot export
+struct LdrTarget {
+    framebuffer: GlFramebuffer,
+    color: GlTexture,
+    width: i32,
+    height: i32,
+}
+
+impl LdrTarget {
+    fn new(width: i32, height: i32) -> Result<Self> {
+        let color = GlTexture::new(gl::TEXTURE_2D);
+
+        unsafe {
+            gl::TextureStorage2D(color.id, 1, gl::RGBA8, width, height);
+            gl::TextureParameteri(color.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(color.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(color.id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(color.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        }
+
+        color.add_label(cstr!("LDR color"));
+
+        let framebuffer = GlFramebuffer::new();
+        framebuffer.attach_texture(gl::COLOR_ATTACHMENT0, &color, 0);
+        framebuffer.check_status()?;
+        framebuffer.add_label(cstr!("LDR framebuffer"));
+
+        Ok(Self {
+            framebuffer,
+            color,
+            width,
+            height,
+        })
+    }
+}
+
+/// Renders the scene into an HDR target and resolves it to the window.
+///
+/// Anti-aliasing, bloom, tonemapping and exposure are applied in fullscreen passes, so they can be changed at runtime.
+pub struct PostProcess {
+    hdr_target: Option<HdrTarget>,
+    ldr_target: Option<LdrTarget>,
+    bloom: Bloom,
+    taa: Taa,
+    tonemap_shader: Shader,
+    fxaa_shader: Shader,
+    /// The fullscreen triangle doesn't have any vertex attributes, but a VAO has to be bound
+    empty_vao: Vao,
+}
+
+impl PostProcess {
+    pub fn new() -> Result<Self> {
+        let tonemap_shader =
+            Shader::with_files(shader_path("fullscreen.vert"), shader_path("tonemap.frag"))?;
+        let fxaa_shader =
+            Shader::with_files(shader_path("fullscreen.vert"), shader_path("fxaa.frag"))?;
+
+        Ok(Self {
+            hdr_target: None,
+            ldr_target: None,
+            bloom: Bloom::new()?,
+            taa: Taa::new()?,
+            tonemap_shader,
+            fxaa_shader,
+            empty_vao: Vao::new(),
+        })
+    }
+
+    /// Binds the HDR target, it is recreated when the size of the viewport or the MSAA setting changes
+    pub fn bind_hdr_target(
+        &mut self,
+        viewport_dim: &ViewportDim,
+        antialiasing: Antialiasing,
+    ) -> Result<()> {
+        let width = (viewport_dim.width as i32).max(1);
+        let height = (viewport_dim.height as i32).max(1);
+        let msaa = antialiasing == Antialiasing::Msaa;
+
+        let target = match self.hdr_target.take() {
+            Some(target)
+                if target.width == width
+                    && target.height == height
+                    && target.msaa.is_some() == msaa =>
+            {
+                target
+            }
+            _ => HdrTarget::new(width, height, msaa)?,
+        };
+
+        match &target.msaa {
+            Some(msaa) => msaa.framebuffer.bind(),
+            None => target.framebuffer.bind(),
+        }
+
+        self.hdr_target = Some(target);
+
+        Ok(())
+    }
+
+    /// Resolves the multisampled buffers into the HDR target, doesn't do anything without MSAA
+    pub fn resolve_msaa(&self) {
+        let Some(target) = &self.hdr_target else {
+            return;
+        };
+
+        if let Some(msaa) = &target.msaa {
+            msaa.framebuffer.blit_to(
+                &target.framebuffer,
+                target.width,
+                target.height,
+                gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT,
+            );
+        }
+    }
+
+    /// Jitters the projection when TAA is enabled, the history is discarded when it's not
+    pub fn jitter_projection(&mut self, projection: Mat4, app_settings: &AppSettings) -> Mat4 {
+        if app_settings.antialiasing == Antialiasing::Taa {
+            let viewport_dim = &app_settings.viewport_dim;
+            self.taa
+                .jitter_projection(projection, viewport_dim.width, viewport_dim.height)
+        } else {
+            self.taa.invalidate_history();
+            projection
+        }
+    }
+
+    /// Color of the HDR target, None before the first frame
+    pub fn hdr_color(&self) -> Option<&GlTexture> {
+        self.hdr_target.as_ref().map(|t| &t.color)
+    }
+
+    /// Applies TAA and bloom and tonemaps the HDR target into the viewport of the default framebuffer.
+    ///
+    /// The `overlay` is drawn into the output after that, it gets the depth of the scene
+    /// and the viewport of the scene in the output.
+    ///
+    /// The screenshot is only returned for [`FrameOutput::Screenshot`].
+    pub fn resolve(
+        &mut self,
+        app_settings: &AppSettings,
+        transforms: &Transforms,
+        output: FrameOutput,
+        overlay: impl FnOnce(&GlTexture, &ViewportDim),
+    ) -> Result<Option<Screenshot>> {
+        let Some(target) = &self.hdr_target else {
+            return Ok(None);
+        };
+
+        let color = if app_settings.antialiasing == Antialiasing::Taa {
+            self.taa.resolve(
+                &target.color,
+                &target.depth,
+                target.width,
+                target.height,
+                transforms,
+            )
+        } else {
+            &target.color
+        };
+
+        if output == FrameOutput::History {
+            return Ok(None);
+        }
+
+        let passthrough = app_settings.debug_views().map(|v| !v.is_radiance());
+        let divider = match app_settings.split.enabled {
+            true => app_settings.split.divider,
+            false => 1.,
+        };
+
+        let bloom_settings = &app_settings.bloom;
+        let furnace = &app_settings.furnace;
+        let bloom_enabled =
+            bloom_settings.enabled && passthrough.contains(&false) && !furnace.enabled;
+        let (bloom_tex, bloom_intensity) = if bloom_enabled {
+            let tex = self
+                .bloom
+                .render(color, target.width, target.height, bloom_settings);
+            (tex.id, bloom_settings.intensity)
+        } else {
+            (0, 0.)
+        };
+
+        let screenshot_target = match output {
+            FrameOutput::Screenshot => Some(LdrTarget::new(target.width, target.height)?),
+            _ => None,
+        };
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+        }
+
+        // FXAA needs the tonemapped image, so it's rendered into the LDR target first
+        let fxaa = app_settings.antialiasing == Antialiasing::Fxaa;
+        if fxaa {
+            let ldr_target = match self.ldr_target.take() {
+                Some(t) if t.width == target.width && t.height == target.height => t,
+                _ => LdrTarget::new(target.width, target.height)?,
+            };
+
+            ldr_target.framebuffer.bind();
+            unsafe {
+                gl::Viewport(0, 0, target.width, target.height);
+            }
+
+            self.ldr_target = Some(ldr_target);
+        } else {
+            Self::bind_output(screenshot_target.as_ref(), app_settings);
+        }
+
+        self.tonemap_shader.use_shader(|| unsafe {
+            self.tonemap_shader
+                .set_u32(app_settings.tonemapping as u32, cstr!("tonemapOperator"));
+            self.tonemap_shader
+                .set_f32(app_settings.exposure.exp2(), cstr!("exposure"));
+            self.tonemap_shader
+                .set_f32(bloom_intensity, cstr!("bloomIntensity"));
+            self.tonemap_shader
+                .set_u32(passthrough[0] as u32, cstr!("passthroughLeft"));
+            self.tonemap_shader
+                .set_u32(passthrough[1] as u32, cstr!("passthroughRight"));
+            self.tonemap_shader.set_f32(divider, cstr!("divider"));
+            self.tonemap_shader
+                .set_u32(furnace.enabled as u32, cstr!("furnaceTest"));
+            self.tonemap_shader
+                .set_f32(furnace.range, cstr!("furnaceRange"));
+
+            gl::BindTextureUnit(0, color.id);
+            gl::BindTextureUnit(1, bloom_tex);
+
+            Self::draw_fullscreen(&self.empty_vao);
+        });
+
+        if let (true, Some(ldr_target)) = (fxaa, &self.ldr_target) {
+            Self::bind_output(screenshot_target.as_ref(), app_settings);
+
+            self.fxaa_shader.use_shader(|| unsafe {
+                gl::BindTextureUnit(0, ldr_target.color.id);
+                Self::draw_fullscreen(&self.empty_vao);
+            });
+        }
+
+        let output_viewport = match screenshot_target {
+            Some(_) => app_settings.viewport_dim.at_origin(),
+            None => app_settings.viewport_dim,
+        };
+        overlay(&target.depth, &output_viewport);
+
+        match screenshot_target {
+            Some(ldr) => {
+                GlFramebuffer::unbind();
+                let (width, height) = (target.width as u32, target.height as u32);
+                Ok(Some(Screenshot::read(&ldr.color, color, width, height)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Binds the screenshot target if there is one, the viewport of the window otherwise
+    fn bind_output(screenshot_target: Option<&LdrTarget>, app_settings: &AppSettings) {
+        match screenshot_target {
+            Some(t) => {
+                t.framebuffer.bind();
+                unsafe {
+                    gl::Viewport(0, 0, t.width, t.height);
+                }
+            }
+            None => {
+                GlFramebuffer::unbind();
+                Self::set_window_viewport(app_settings);
+            }
+        }
+    }
+
+    fn set_window_viewport(app_settings: &AppSettings) {
+        let viewport_dim = &app_settings.viewport_dim;
+        unsafe {
+            gl::Viewport(
+                viewport_dim.min_x as i32,
+                viewport_dim.min_y as i32,
+                viewport_dim.width as i32,
+                viewport_dim.height as i32,
+            );
+        }
+    }
+
+    fn draw_fullscreen(vao: &Vao) {
+        unsafe {
+            gl::BindVertexArray(vao.id);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+        }
+    }
+}
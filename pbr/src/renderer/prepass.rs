@@ -0,0 +1,145 @@
+use cstr::cstr;
+use eyre::Result;
+use glam::Mat4;
+
+use crate::{
+    app_settings::ViewportDim,
+    config::shader_path,
+    ogl::{framebuffer::GlFramebuffer, shader::Shader, texture::GlTexture},
+    scene::Node,
+};
+
+use super::Renderer;
+
+/// Depth and view-space normals of the scene
+struct PrepassTarget {
+    framebuffer: GlFramebuffer,
+    normals: GlTexture,
+    depth: GlTexture,
+    width: i32,
+    height: i32,
+}
+
+impl PrepassTarget {
+    fn new(width: i32, height: i32) -> Result<Self> {
+        let normals = GlTexture::new(gl::TEXTURE_2D);
+        let depth = GlTexture::new(gl::TEXTURE_2D);
+
+        unsafe {
+            gl::TextureStorage2D(normals.id, 1, gl::RGBA16F, width, height);
+            gl::TextureStorage2D(depth.id, 1, gl::DEPTH_COMPONENT32F, width, height);
+
+            for tex in [&normals, &depth] {
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            }
+        }
+
+        normals.add_label(cstr!("Prepass normals"));
+        depth.add_label(cstr!("Prepass depth"));
+
+        let framebuffer = GlFramebuffer::new();
+        framebuffer.attach_texture(gl::COLOR_ATTACHMENT0, &normals, 0);
+        framebuffer.attach_texture(gl::DEPTH_ATTACHMENT, &depth, 0);
+        framebuffer.check_status()?;
+        framebuffer.add_label(cstr!("Prepass framebuffer"));
+
+        Ok(Self {
+            framebuffer,
+            normals,
+            depth,
+            width,
+            height,
+        })
+    }
+}
+
+/// Renders the depth and the geometric normals of the scene for the screen-space effects.
+///
+/// The textures have the size of the viewport.
+pub struct Prepass {
+    shader: Shader,
+    target: Option<PrepassTarget>,
+}
+
+impl Prepass {
+    pub fn new() -> Result<Self> {
+        let shader = Shader::with_files(shader_path("prepass.vert"), shader_path("prepass.frag"))?;
+
+        Ok(Self {
+            shader,
+            target: None,
+        })
+    }
+
+    /// The view and projection matrices are taken from the transforms uniform buffer
+    pub fn render(
+        &mut self,
+        root: &Node,
+        transform: Mat4,
+        viewport_dim: &ViewportDim,
+    ) -> Result<()> {
+        let width = (viewport_dim.width as i32).max(1);
+        let height = (viewport_dim.height as i32).max(1);
+
+        let target = match self.target.take() {
+            Some(target) if target.width == width && target.height == height => target,
+            _ => PrepassTarget::new(width, height)?,
+        };
+
+        target.framebuffer.bind();
+
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+            gl::ClearColor(0., 0., 0., 0.);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Disable(gl::BLEND);
+        }
+
+        self.shader.use_shader(|| {
+            self.render_node(root, transform);
+        });
+
+        unsafe {
+            gl::Enable(gl::BLEND);
+        }
+
+        self.target = Some(target);
+
+        Ok(())
+    }
+
+    fn render_node(&self, node: &Node, outer_transform: Mat4) {
+        let transform = outer_transform * node.transform;
+
+        if let Some(mesh) = &node.mesh {
+            self.shader.set_mat4(transform, cstr!("model"));
+
+            for primitive in &mesh.primitives {
+                Renderer::draw_mesh(primitive);
+            }
+        }
+
+        for child in &node.children {
+            self.render_node(child, transform);
+        }
+    }
+
+    /// Size of the textures, (0, 0) if the prepass wasn't rendered yet
+    pub fn size(&self) -> (i32, i32) {
+        self.target
+            .as_ref()
+            .map(|t| (t.width, t.height))
+            .unwrap_or((0, 0))
+    }
+
+    pub fn depth(&self) -> Option<&GlTexture> {
+        self.target.as_ref().map(|t| &t.depth)
+    }
+
+    pub fn normals(&self) -> Option<&GlTexture> {
+        self.target.as_ref().map(|t| &t.normals)
+    }
+}
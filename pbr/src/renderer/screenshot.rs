@@ -0,0 +1,129 @@
+use std::path::{Path, PathBuf};
+
+use eyre::{eyre, Result};
+use image::{imageops, ImageBuffer, ImageFormat, Rgba};
+
+use crate::ogl::texture::GlTexture;
+
+/// Largest exported width or height, the HDR readback of an 8192x8192 image already takes 1 GiB
+pub const MAX_SCREENSHOT_SIZE: u32 = 8192;
+
+/// User-configurable parameters of the screenshot export
+#[derive(Clone)]
+pub struct ScreenshotSettings {
+    pub width: u32,
+    pub height: u32,
+    pub format: ScreenshotFormat,
+    pub path: PathBuf,
+    /// Set by the GUI, the renderer exports the next frame and resets it
+    pub requested: bool,
+    /// Result of the last export
+    pub status: Option<String>,
+}
+
+impl ScreenshotSettings {
+    pub fn new() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            format: ScreenshotFormat::Png,
+            path: PathBuf::from("screenshot.png"),
+            requested: false,
+            status: None,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ScreenshotFormat {
+    /// The tonemapped image as displayed in the viewport
+    Png,
+    /// The linear HDR image before bloom and tonemapping
+    Exr,
+}
+
+impl ScreenshotFormat {
+    pub const ALL: [ScreenshotFormat; 2] = [ScreenshotFormat::Png, ScreenshotFormat::Exr];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "PNG (tonemapped)",
+            ScreenshotFormat::Exr => "OpenEXR (linear HDR)",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Exr => "exr",
+        }
+    }
+
+    /// OpenEXR for the .exr extension, PNG for everything else
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("exr") => ScreenshotFormat::Exr,
+            _ => ScreenshotFormat::Png,
+        }
+    }
+}
+
+/// Contents of the offscreen targets read back from the GPU
+pub struct Screenshot {
+    /// Tonemapped RGBA8 pixels
+    pub ldr: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    /// Linear RGBA32F pixels
+    pub hdr: ImageBuffer<Rgba<f32>, Vec<f32>>,
+}
+
+impl Screenshot {
+    /// Reads both targets, the rows are flipped because OpenGL stores the bottom row first
+    pub fn read(ldr: &GlTexture, hdr: &GlTexture, width: u32, height: u32) -> Result<Self> {
+        let mut ldr_pixels = vec![0u8; (width * height * 4) as usize];
+        let mut hdr_pixels = vec![0f32; (width * height * 4) as usize];
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTextureImage(
+                ldr.id,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ldr_pixels.len() as i32,
+                ldr_pixels.as_mut_ptr() as _,
+            );
+            gl::GetTextureImage(
+                hdr.id,
+                0,
+                gl::RGBA,
+                gl::FLOAT,
+                (hdr_pixels.len() * std::mem::size_of::<f32>()) as i32,
+                hdr_pixels.as_mut_ptr() as _,
+            );
+        }
+
+        let mut ldr = ImageBuffer::from_raw(width, height, ldr_pixels)
+            .ok_or_else(|| eyre!("Wrong size of the LDR screenshot buffer"))?;
+        let mut hdr = ImageBuffer::from_raw(width, height, hdr_pixels)
+            .ok_or_else(|| eyre!("Wrong size of the HDR screenshot buffer"))?;
+
+        imageops::flip_vertical_in_place(&mut ldr);
+        imageops::flip_vertical_in_place(&mut hdr);
+
+        Ok(Self { ldr, hdr })
+    }
+
+    pub fn save(&self, path: &Path, format: ScreenshotFormat) -> Result<()> {
+        match format {
+            ScreenshotFormat::Png => self.ldr.save_with_format(path, ImageFormat::Png)?,
+            ScreenshotFormat::Exr => {
+                // The alpha of the HDR target is meaningless, so it's made opaque
+                let mut hdr = self.hdr.clone();
+                hdr.pixels_mut().for_each(|p| p[3] = 1.);
+                hdr.save_with_format(path, ImageFormat::OpenExr)?
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use crate::{
+    app_settings::MaterialSrc,
     brdf_raw::BrdfType,
+    config::shader_path,
     ogl::shader::{
         shader_permutations::{ShaderDefines, ShaderPermutations},
         Shader,
@@ -22,10 +24,12 @@ pub struct PbrDefines {
     pub clearcoat_normal_map: bool,
 
     pub anisotropy_enabled: bool,
+
+    pub multi_draw: bool,
 }
 
 impl PbrDefines {
-    pub fn from_prim(prim: &Primitive) -> Self {
+    pub fn from_prim(prim: &Primitive, multi_draw: bool) -> Self {
         let pbr = &prim.pbr_material;
         let cc = prim.clearcoat.as_ref();
 
@@ -40,6 +44,7 @@ impl PbrDefines {
             clearcoat_roughness_map: cc.and_then(|c| c.roughness_texture.as_ref()).is_some(),
             clearcoat_normal_map: cc.and_then(|c| c.normal_texture.as_ref()).is_some(),
             anisotropy_enabled: prim.anisotropy.is_some(),
+            multi_draw,
         }
     }
 }
@@ -59,6 +64,7 @@ impl ShaderDefines for PbrDefines {
             (self.clearcoat_roughness_map, "CLEARCOAT_ROUGHNESS_MAP"),
             (self.clearcoat_normal_map, "CLEARCOAT_NORMAL_MAP"),
             (self.anisotropy_enabled, "ANISOTROPY"),
+            (self.multi_draw, "MULTI_DRAW"),
         ];
 
         for (field, define) in fiels_defines {
@@ -76,16 +82,18 @@ pub struct DataDrivenDefines {
     pub normal_map: bool,
     pub occlusion_map: bool,
     pub brdf_typ: BrdfType,
+    pub multi_draw: bool,
 }
 
 impl DataDrivenDefines {
-    pub fn from_prim_brdf(prim: &Primitive, brdf_typ: BrdfType) -> Self {
+    pub fn from_prim_brdf(prim: &Primitive, brdf_typ: BrdfType, multi_draw: bool) -> Self {
         let pbr = &prim.pbr_material;
 
         Self {
             normal_map: pbr.normal_texture.is_some(),
             occlusion_map: pbr.occlusion_texture.is_some(),
             brdf_typ,
+            multi_draw,
         }
     }
 }
@@ -97,6 +105,7 @@ impl ShaderDefines for DataDrivenDefines {
         let fiels_defines = [
             (self.normal_map, "NORMAL_MAP"),
             (self.occlusion_map, "OCCLUSION_MAP"),
+            (self.multi_draw, "MULTI_DRAW"),
         ];
 
         for (field, define) in fiels_defines {
@@ -116,21 +125,27 @@ pub struct Shaders {
     pub data_based_shaders: ShaderPermutations<DataDrivenDefines>,
     pub light_shader: Shader,
     pub cubemap_shader: Shader,
+    pub depth_shader: Shader,
+    /// Depth prepass of the multi-draw path
+    pub depth_multi_draw_shader: Shader,
 }
 
 impl Shaders {
     pub fn new() -> Result<Self> {
         let pbr_shaders =
-            ShaderPermutations::new("shaders_stitched/basic.vert", "shaders_stitched/pbr.frag")?;
-        let data_based_shaders = ShaderPermutations::new(
-            "shaders_stitched/basic.vert",
-            "shaders_stitched/data_driven.frag",
-        )?;
+            ShaderPermutations::new(shader_path("basic.vert"), shader_path("pbr.frag"))?;
+        let data_based_shaders =
+            ShaderPermutations::new(shader_path("basic.vert"), shader_path("data_driven.frag"))?;
         let light_shader =
-            Shader::with_files("shaders_stitched/basic.vert", "shaders_stitched/light.frag")?;
-        let cubemap_shader = Shader::with_files(
-            "shaders_stitched/cubemap.vert",
-            "shaders_stitched/cubemap.frag",
+            Shader::with_files(shader_path("basic.vert"), shader_path("light.frag"))?;
+        let cubemap_shader =
+            Shader::with_files(shader_path("cubemap.vert"), shader_path("cubemap.frag"))?;
+        let depth_shader =
+            Shader::with_files(shader_path("basic.vert"), shader_path("depth_only.frag"))?;
+        let depth_multi_draw_shader = Shader::with_files_vs_defines(
+            shader_path("basic.vert"),
+            &["MULTI_DRAW"],
+            shader_path("depth_only.frag"),
         )?;
 
         Ok(Self {
@@ -138,6 +153,31 @@ impl Shaders {
             data_based_shaders,
             light_shader,
             cubemap_shader,
+            depth_shader,
+            depth_multi_draw_shader,
         })
     }
+
+    /// The shader permutation a primitive is shaded with, it's compiled on first use
+    pub fn get_primitive_shader(
+        &mut self,
+        primitive: &Primitive,
+        material_src: MaterialSrc,
+        multi_draw: bool,
+    ) -> Result<&Shader> {
+        match material_src {
+            MaterialSrc::MerlBrdf | MaterialSrc::UtiaBrdf => {
+                let brdf_typ = match material_src {
+                    MaterialSrc::MerlBrdf => BrdfType::Merl,
+                    _ => BrdfType::Utia,
+                };
+                let defines = DataDrivenDefines::from_prim_brdf(primitive, brdf_typ, multi_draw);
+                self.data_based_shaders.get_shader(defines)
+            }
+            _ => {
+                let defines = PbrDefines::from_prim(primitive, multi_draw);
+                self.pbr_shaders.get_shader(defines)
+            }
+        }
+    }
 }
@@ -0,0 +1,483 @@
+use cstr::cstr;
+use eyre::Result;
+use glam::{Mat4, Vec3, Vec4};
+use shader_constants::CONSTS;
+
+use crate::{
+    config::shader_path,
+    ogl::{
+        framebuffer::GlFramebuffer,
+        shader::Shader,
+        texture::GlTexture,
+        uniform_buffer::{UniformBuffer, UniformBufferElement},
+    },
+    scene::Node,
+};
+
+use super::{
+    lighting::{Light, LightType},
+    transforms::Transforms,
+    Renderer,
+};
+
+const CASCADES: usize = CONSTS.shadows.cascades as usize;
+const MAX_LAYERS: usize = CONSTS.shadows.max_layers as usize;
+
+/// Directional light shadows are only rendered up to this distance from the camera
+const SHADOW_DISTANCE: f32 = 30.;
+/// Blend between logarithmic (1.0) and uniform (0.0) cascade splits
+const CASCADE_SPLIT_LAMBDA: f32 = 0.8;
+
+const POINT_SHADOW_NEAR: f32 = 0.05;
+const POINT_SHADOW_FAR: f32 = 50.;
+const SPOT_SHADOW_NEAR: f32 = 0.05;
+const SPOT_SHADOW_FAR: f32 = 50.;
+
+/// Uniform buffer element with the data needed for sampling the shadow maps
+#[derive(bytemuck::NoUninit, Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub struct ShadowUniforms {
+    /// Light-space transforms of the layers of the 2D shadow maps
+    shadow_matrices: [Mat4; MAX_LAYERS],
+    /// Cascades are selected based on the view-space depth
+    camera_view: Mat4,
+    /// View-space depth where each cascade ends
+    cascade_splits: Vec4,
+    point_shadow_far: f32,
+    padding: [f32; 3],
+}
+
+impl ShadowUniforms {
+    fn new() -> Self {
+        Self {
+            shadow_matrices: [Mat4::IDENTITY; MAX_LAYERS],
+            camera_view: Mat4::IDENTITY,
+            cascade_splits: Vec4::ZERO,
+            point_shadow_far: POINT_SHADOW_FAR,
+            padding: [0.; 3],
+        }
+    }
+}
+
+impl UniformBufferElement for ShadowUniforms {
+    const BINDING: u32 = CONSTS.buffer_bindings.shadows;
+}
+
+/// Shadow maps of all the lights.
+///
+/// Point and area lights use a cubemap of a cubemap array, spot lights use a layer of a 2D array texture
+/// and directional lights use multiple layers of the 2D array, one for each cascade.
+pub struct ShadowMaps {
+    uniforms: UniformBuffer<ShadowUniforms>,
+    shader: Shader,
+    framebuffer: GlFramebuffer,
+    /// Depth maps of the spot and directional lights
+    maps: GlTexture,
+    /// Depth cubemaps of the point lights
+    cube_maps: GlTexture,
+    /// Number of allocated 2D layers
+    layers: usize,
+    /// Number of allocated cubemaps
+    cube_layers: usize,
+    /// Maximum number of cubemaps supported by the GL implementation
+    max_cube_layers: usize,
+}
+
+impl ShadowMaps {
+    pub fn new() -> Result<Self> {
+        let shader = Shader::with_files(shader_path("shadow.vert"), shader_path("shadow.frag"))?;
+
+        let framebuffer = GlFramebuffer::new();
+        framebuffer.disable_color();
+        framebuffer.add_label(cstr!("Shadow map framebuffer"));
+
+        let mut max_array_layers = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_ARRAY_TEXTURE_LAYERS, &mut max_array_layers);
+        }
+        // Every cubemap of the array takes 6 layers
+        let max_cube_layers =
+            (CONSTS.shadows.max_cube_layers as usize).min(max_array_layers as usize / 6);
+
+        Ok(Self {
+            uniforms: UniformBuffer::new(ShadowUniforms::new()),
+            shader,
+            framebuffer,
+            maps: Self::create_maps(1),
+            cube_maps: Self::create_cube_maps(1),
+            layers: 1,
+            cube_layers: 1,
+            max_cube_layers,
+        })
+    }
+
+    /// Assigns the shadow map layers to the lights and renders the shadow maps of the scene.
+    ///
+    /// Changes the bound framebuffer and the viewport.
+    pub fn render(
+        &mut self,
+        root: &Node,
+        transform: Mat4,
+        lights: &mut [Light],
+        transforms: &Transforms,
+        enabled: bool,
+    ) -> Result<()> {
+        // The shaders declare the shadow samplers even if the shadows are disabled
+        self.bind_textures();
+
+        if !enabled {
+            for light in lights.iter_mut() {
+                light.shadow_index = -1;
+            }
+            return Ok(());
+        }
+
+        self.assign_layers(lights);
+        self.uniforms.inner.camera_view = transforms.view;
+
+        let cascade_splits = cascade_splits(transforms);
+        self.uniforms.inner.cascade_splits = Vec4::from_slice(&cascade_splits);
+
+        unsafe {
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        self.framebuffer.bind();
+
+        for light in lights.iter() {
+            if light.shadow_index < 0 {
+                continue;
+            }
+
+            let index = light.shadow_index as usize;
+            let position = light.position.truncate();
+
+            match light.typ() {
+                // Area lights cast the shadows from their center
+                LightType::Point | LightType::Rect | LightType::Disk | LightType::Tube => {
+                    for (face, view) in cube_face_views(position).into_iter().enumerate() {
+                        let projection = Mat4::perspective_rh_gl(
+                            90f32.to_radians(),
+                            1.,
+                            POINT_SHADOW_NEAR,
+                            POINT_SHADOW_FAR,
+                        );
+
+                        self.framebuffer.attach_texture_layer(
+                            gl::DEPTH_ATTACHMENT,
+                            &self.cube_maps,
+                            0,
+                            (index * 6 + face) as i32,
+                        );
+                        self.render_layer(
+                            root,
+                            transform,
+                            projection * view,
+                            Some(position),
+                            CONSTS.shadows.cube_map_size,
+                        )?;
+                    }
+                }
+                LightType::Spot => {
+                    let direction = light.direction();
+                    let view = Mat4::look_at_rh(position, position + direction, up_for(direction));
+                    let fov = (2. * light.outer_cone_angle()).min(170f32.to_radians());
+                    let projection =
+                        Mat4::perspective_rh_gl(fov, 1., SPOT_SHADOW_NEAR, SPOT_SHADOW_FAR);
+
+                    self.render_map_layer(root, transform, index, projection * view)?;
+                }
+                LightType::Directional => {
+                    let direction = light.direction();
+
+                    let mut near = transforms.near_far().0;
+                    for (cascade, far) in cascade_splits.into_iter().enumerate() {
+                        let view_projection =
+                            cascade_view_projection(transforms, direction, near, far);
+                        near = far;
+
+                        // Casters in front of the near plane are clamped to it instead of being clipped
+                        unsafe {
+                            gl::Enable(gl::DEPTH_CLAMP);
+                        }
+                        self.render_map_layer(root, transform, index + cascade, view_projection)?;
+                        unsafe {
+                            gl::Disable(gl::DEPTH_CLAMP);
+                        }
+                    }
+                }
+            }
+        }
+
+        GlFramebuffer::unbind();
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+        }
+
+        self.uniforms.update();
+
+        Ok(())
+    }
+
+    fn bind_textures(&self) {
+        let tp = CONSTS.texture_ports;
+
+        unsafe {
+            gl::BindTextureUnit(tp.shadow_maps, self.maps.id);
+            gl::BindTextureUnit(tp.shadow_cube_maps, self.cube_maps.id);
+        }
+    }
+
+    /// Gives every light the first layer of its shadow map, the textures are resized if needed
+    fn assign_layers(&mut self, lights: &mut [Light]) {
+        let mut layers = 0;
+        let mut cube_layers = 0;
+
+        for light in lights.iter_mut() {
+            light.shadow_index = -1;
+
+            if !light.casts_shadows() {
+                continue;
+            }
+
+            let (counter, needed, limit) = match light.typ() {
+                LightType::Spot => (&mut layers, 1, MAX_LAYERS),
+                LightType::Directional => (&mut layers, CASCADES, MAX_LAYERS),
+                LightType::Point | LightType::Rect | LightType::Disk | LightType::Tube => {
+                    (&mut cube_layers, 1, self.max_cube_layers)
+                }
+            };
+
+            if *counter + needed > limit {
+                continue;
+            }
+
+            light.shadow_index = *counter as i32;
+            *counter += needed;
+        }
+
+        // Textures can't have 0 layers
+        let layers = layers.max(1);
+        let cube_layers = cube_layers.max(1);
+
+        if layers != self.layers {
+            self.maps = Self::create_maps(layers);
+            self.layers = layers;
+        }
+
+        if cube_layers != self.cube_layers {
+            self.cube_maps = Self::create_cube_maps(cube_layers);
+            self.cube_layers = cube_layers;
+        }
+    }
+
+    fn render_map_layer(
+        &mut self,
+        root: &Node,
+        transform: Mat4,
+        layer: usize,
+        view_projection: Mat4,
+    ) -> Result<()> {
+        self.uniforms.inner.shadow_matrices[layer] = view_projection;
+
+        self.framebuffer
+            .attach_texture_layer(gl::DEPTH_ATTACHMENT, &self.maps, 0, layer as i32);
+        self.render_layer(
+            root,
+            transform,
+            view_projection,
+            None,
+            CONSTS.shadows.map_size,
+        )
+    }
+
+    /// Renders the depth of the scene into the layer that is attached to the framebuffer.
+    ///
+    /// Linear depth is stored if the point light position is specified.
+    fn render_layer(
+        &self,
+        root: &Node,
+        transform: Mat4,
+        view_projection: Mat4,
+        point_light: Option<Vec3>,
+        size: i32,
+    ) -> Result<()> {
+        self.framebuffer.check_status()?;
+
+        unsafe {
+            gl::Viewport(0, 0, size, size);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+
+        self.shader.use_shader(|| {
+            self.shader
+                .set_mat4(view_projection, cstr!("lightViewProjection"));
+            self.shader
+                .set_u32(point_light.is_some() as u32, cstr!("linearDepth"));
+            self.shader
+                .set_vec3(point_light.unwrap_or(Vec3::ZERO), cstr!("lightPos"));
+            self.shader.set_f32(POINT_SHADOW_FAR, cstr!("farPlane"));
+
+            self.render_node(root, transform);
+        });
+
+        Ok(())
+    }
+
+    fn render_node(&self, node: &Node, outer_transform: Mat4) {
+        let transform = outer_transform * node.transform;
+
+        if let Some(mesh) = &node.mesh {
+            self.shader.set_mat4(transform, cstr!("model"));
+
+            for primitive in &mesh.primitives {
+                Renderer::draw_mesh(primitive);
+            }
+        }
+
+        for child in &node.children {
+            self.render_node(child, transform);
+        }
+    }
+
+    fn create_maps(layers: usize) -> GlTexture {
+        let size = CONSTS.shadows.map_size;
+        let texture = GlTexture::new(gl::TEXTURE_2D_ARRAY);
+
+        unsafe {
+            gl::TextureStorage3D(
+                texture.id,
+                1,
+                gl::DEPTH_COMPONENT32F,
+                size,
+                size,
+                layers as i32,
+            );
+
+            // Everything outside of the shadow map is lit
+            gl::TextureParameteri(texture.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::TextureParameteri(texture.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            gl::TextureParameterfv(texture.id, gl::TEXTURE_BORDER_COLOR, [1f32; 4].as_ptr());
+        }
+
+        Self::set_comparison_params(&texture);
+        texture.add_label(cstr!("Shadow maps"));
+        texture
+    }
+
+    fn create_cube_maps(cubemaps: usize) -> GlTexture {
+        let size = CONSTS.shadows.cube_map_size;
+        let texture = GlTexture::new(gl::TEXTURE_CUBE_MAP_ARRAY);
+
+        unsafe {
+            gl::TextureStorage3D(
+                texture.id,
+                1,
+                gl::DEPTH_COMPONENT32F,
+                size,
+                size,
+                (cubemaps * 6) as i32,
+            );
+        }
+
+        Self::set_comparison_params(&texture);
+        texture.add_label(cstr!("Shadow cubemaps"));
+        texture
+    }
+
+    /// The shadow samplers compare the depth and filter the results (2x2 PCF in hardware)
+    fn set_comparison_params(texture: &GlTexture) {
+        unsafe {
+            gl::TextureParameteri(texture.id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(texture.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(
+                texture.id,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl::TextureParameteri(texture.id, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+        }
+    }
+}
+
+/// View matrices of the cubemap faces in the order of the cubemap layers
+fn cube_face_views(position: Vec3) -> [Mat4; 6] {
+    let face = |dir: Vec3, up: Vec3| Mat4::look_at_rh(position, position + dir, up);
+
+    [
+        face(Vec3::X, Vec3::NEG_Y),
+        face(Vec3::NEG_X, Vec3::NEG_Y),
+        face(Vec3::Y, Vec3::Z),
+        face(Vec3::NEG_Y, Vec3::NEG_Z),
+        face(Vec3::Z, Vec3::NEG_Y),
+        face(Vec3::NEG_Z, Vec3::NEG_Y),
+    ]
+}
+
+/// An up vector that isn't parallel with the direction
+fn up_for(direction: Vec3) -> Vec3 {
+    if direction.y.abs() > 0.99 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    }
+}
+
+/// View-space depths where the cascades end
+fn cascade_splits(transforms: &Transforms) -> [f32; CASCADES] {
+    let (near, far) = transforms.near_far();
+    let far = far.min(SHADOW_DISTANCE);
+
+    let mut splits = [0.; CASCADES];
+    for (i, split) in splits.iter_mut().enumerate() {
+        let fraction = (i + 1) as f32 / CASCADES as f32;
+
+        let logarithmic = near * (far / near).powf(fraction);
+        let uniform = near + (far - near) * fraction;
+        *split = CASCADE_SPLIT_LAMBDA * logarithmic + (1. - CASCADE_SPLIT_LAMBDA) * uniform;
+    }
+
+    splits
+}
+
+/// Orthographic projection of a directional light that covers a slice of the camera frustum
+fn cascade_view_projection(transforms: &Transforms, direction: Vec3, near: f32, far: f32) -> Mat4 {
+    let (camera_near, camera_far) = transforms.near_far();
+    let inv_view_projection = (transforms.projection * transforms.view).inverse();
+
+    // Points on the frustum edges are interpolated linearly with the view-space depth
+    let t_near = (near - camera_near) / (camera_far - camera_near);
+    let t_far = (far - camera_near) / (camera_far - camera_near);
+
+    let mut corners = Vec::with_capacity(8);
+    for x in [-1., 1.] {
+        for y in [-1., 1.] {
+            let near_corner = inv_view_projection.project_point3(Vec3::new(x, y, -1.));
+            let far_corner = inv_view_projection.project_point3(Vec3::new(x, y, 1.));
+
+            corners.push(near_corner.lerp(far_corner, t_near));
+            corners.push(near_corner.lerp(far_corner, t_far));
+        }
+    }
+
+    let center = corners.iter().copied().sum::<Vec3>() / corners.len() as f32;
+    // The bounding sphere doesn't change when the camera rotates, so the shadows don't shimmer
+    let radius = corners
+        .iter()
+        .map(|c| c.distance(center))
+        .fold(0., f32::max);
+    let radius = (radius * 16.).ceil() / 16.;
+
+    let view = Mat4::look_at_rh(center - direction * radius, center, up_for(direction));
+    let mut projection =
+        Mat4::orthographic_rh_gl(-radius, radius, -radius, radius, 0., 2. * radius);
+
+    // Snap the projection to whole texels
+    let texels = CONSTS.shadows.map_size as f32 / 2.;
+    let origin = (projection * view).project_point3(Vec3::ZERO) * texels;
+    let offset = (origin.round() - origin) / texels;
+    projection.w_axis.x += offset.x;
+    projection.w_axis.y += offset.y;
+
+    projection * view
+}
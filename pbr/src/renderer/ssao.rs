@@ -0,0 +1,128 @@
+use cstr::cstr;
+use eyre::Result;
+use shader_constants::CONSTS;
+
+use crate::{
+    config::shader_path,
+    ogl::{shader::Shader, texture::GlTexture},
+};
+
+use super::{post_process, prepass::Prepass, transforms::Transforms};
+
+/// The occlusion and a texture for the intermediate result of the separable blur
+struct AoTextures {
+    ao: GlTexture,
+    blur_tmp: GlTexture,
+    width: i32,
+    height: i32,
+}
+
+impl AoTextures {
+    fn new(width: i32, height: i32) -> Self {
+        let create = |label| {
+            let tex = GlTexture::new(gl::TEXTURE_2D);
+
+            unsafe {
+                gl::TextureStorage2D(tex.id, 1, gl::R8, width, height);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            }
+
+            tex.add_label(label);
+            tex
+        };
+
+        Self {
+            ao: create(cstr!("SSAO")),
+            blur_tmp: create(cstr!("SSAO blur")),
+            width,
+            height,
+        }
+    }
+}
+
+/// Screen-space ambient occlusion computed with GTAO from the depth and normals of the prepass.
+///
+/// The result is blurred and bound to the ssao texture port, the shading combines it with the occlusion textures.
+pub struct Ssao {
+    gtao_shader: Shader,
+    blur_shader: Shader,
+    textures: Option<AoTextures>,
+}
+
+impl Ssao {
+    pub fn new() -> Result<Self> {
+        let gtao_shader = Shader::comp_with_path(shader_path("gtao.comp"))?;
+        let blur_shader = Shader::comp_with_path(shader_path("gtao_blur.comp"))?;
+
+        Ok(Self {
+            gtao_shader,
+            blur_shader,
+            textures: None,
+        })
+    }
+
+    /// Computes the occlusion, the PBR settings uniform buffer has to be updated already
+    pub fn render(&mut self, prepass: &Prepass, transforms: &Transforms) {
+        let (Some(depth), Some(normals)) = (prepass.depth(), prepass.normals()) else {
+            return;
+        };
+
+        let (width, height) = prepass.size();
+        let textures = match self.textures.take() {
+            Some(t) if t.width == width && t.height == height => t,
+            _ => AoTextures::new(width, height),
+        };
+
+        let inverse_projection = transforms.projection.inverse();
+
+        self.gtao_shader.use_shader(|| unsafe {
+            self.gtao_shader
+                .set_mat4(transforms.projection, cstr!("projection"));
+            self.gtao_shader
+                .set_mat4(inverse_projection, cstr!("inverseProjection"));
+
+            gl::BindTextureUnit(0, depth.id);
+            gl::BindTextureUnit(1, normals.id);
+            Self::bind_image(&textures.ao);
+
+            post_process::dispatch_compute_2d(width, height);
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        });
+
+        self.blur_shader.use_shader(|| unsafe {
+            self.blur_shader
+                .set_mat4(inverse_projection, cstr!("inverseProjection"));
+            gl::BindTextureUnit(1, depth.id);
+
+            for (horizontal, src, dst) in [
+                (true, &textures.ao, &textures.blur_tmp),
+                (false, &textures.blur_tmp, &textures.ao),
+            ] {
+                self.blur_shader
+                    .set_u32(horizontal as u32, cstr!("horizontal"));
+                gl::BindTextureUnit(0, src.id);
+                Self::bind_image(dst);
+
+                post_process::dispatch_compute_2d(width, height);
+                gl::MemoryBarrier(
+                    gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+                );
+            }
+        });
+
+        unsafe {
+            gl::BindTextureUnit(CONSTS.texture_ports.ssao, textures.ao.id);
+        }
+
+        self.textures = Some(textures);
+    }
+
+    fn bind_image(texture: &GlTexture) {
+        unsafe {
+            gl::BindImageTexture(0, texture.id, 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::R8);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use cstr::cstr;
+use eyre::Result;
+use glam::Mat4;
+use shader_constants::CONSTS;
+
+use crate::{
+    config::shader_path,
+    ogl::{shader::Shader, texture::GlTexture},
+};
+
+use super::{post_process, prepass::Prepass, transforms::Transforms};
+
+/// Number of blur levels of the reflections, the roughest reflections use the last one
+const REFLECTION_LEVELS: i32 = 6;
+
+struct SsrTextures {
+    /// Hierarchical depth buffer, each level stores the closest depth of the level below
+    hiz: GlTexture,
+    hiz_levels: i32,
+    /// The reflected color premultiplied by the confidence, blurred in the mip levels
+    reflections: GlTexture,
+    /// HDR color of the previous frame
+    history: GlTexture,
+    width: i32,
+    height: i32,
+}
+
+impl SsrTextures {
+    fn new(width: i32, height: i32) -> Self {
+        let hiz_levels = 32 - (width.max(height) as u32).leading_zeros() as i32;
+        let reflection_levels = hiz_levels.min(REFLECTION_LEVELS);
+
+        let create = |levels, format, filter, label| {
+            let tex = GlTexture::new(gl::TEXTURE_2D);
+
+            unsafe {
+                gl::TextureStorage2D(tex.id, levels, format, width, height);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MIN_FILTER, filter as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            }
+
+            tex.add_label(label);
+            tex
+        };
+
+        let hiz = create(
+            hiz_levels,
+            gl::R32F,
+            gl::NEAREST_MIPMAP_NEAREST,
+            cstr!("Hi-Z"),
+        );
+        let reflections = create(
+            reflection_levels,
+            gl::RGBA16F,
+            gl::LINEAR_MIPMAP_LINEAR,
+            cstr!("SSR"),
+        );
+        let history = create(1, gl::RGBA16F, gl::LINEAR, cstr!("SSR history"));
+
+        // The first frame doesn't have any history
+        unsafe {
+            gl::ClearTexImage(history.id, 0, gl::RGBA, gl::FLOAT, std::ptr::null());
+        }
+
+        Self {
+            hiz,
+            hiz_levels,
+            reflections,
+            history,
+            width,
+            height,
+        }
+    }
+}
+
+/// Screen-space reflections traced through a hierarchical depth buffer built from the prepass.
+///
+/// The reflections are bound to the ssr texture port, the shading falls back to the prefilter map
+/// where the rays miss. Rougher surfaces sample the blurrier mip levels of the reflections.
+pub struct Ssr {
+    hiz_shader: Shader,
+    trace_shader: Shader,
+    textures: Option<SsrTextures>,
+    /// View-projection matrix of the frame in the history texture
+    prev_view_projection: Mat4,
+}
+
+impl Ssr {
+    pub fn new() -> Result<Self> {
+        let hiz_shader = Shader::comp_with_path(shader_path("hiz_build.comp"))?;
+        let trace_shader = Shader::comp_with_path(shader_path("ssr_trace.comp"))?;
+
+        Ok(Self {
+            hiz_shader,
+            trace_shader,
+            textures: None,
+            prev_view_projection: Mat4::IDENTITY,
+        })
+    }
+
+    /// Traces the reflections, the PBR settings uniform buffer has to be updated already.
+    ///
+    /// The projection mustn't be jittered, otherwise the reprojected history would shift.
+    pub fn render(&mut self, prepass: &Prepass, transforms: &Transforms) {
+        let (Some(depth), Some(normals)) = (prepass.depth(), prepass.normals()) else {
+            return;
+        };
+
+        let (width, height) = prepass.size();
+        let textures = match self.textures.take() {
+            Some(t) if t.width == width && t.height == height => t,
+            _ => SsrTextures::new(width, height),
+        };
+
+        self.build_hiz(depth, &textures);
+        self.trace(normals, &textures, transforms);
+
+        unsafe {
+            gl::GenerateTextureMipmap(textures.reflections.id);
+            gl::BindTextureUnit(CONSTS.texture_ports.ssr, textures.reflections.id);
+        }
+
+        self.textures = Some(textures);
+    }
+
+    /// Keeps the HDR color of the finished frame for the reflections of the next frame.
+    ///
+    /// The projection mustn't be jittered, same as in `render`.
+    pub fn store_history(&mut self, hdr: &GlTexture, transforms: &Transforms) {
+        let Some(textures) = &self.textures else {
+            return;
+        };
+
+        unsafe {
+            gl::CopyImageSubData(
+                hdr.id,
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                textures.history.id,
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                textures.width,
+                textures.height,
+                1,
+            );
+        }
+
+        self.prev_view_projection = transforms.projection * transforms.view;
+    }
+
+    fn build_hiz(&self, depth: &GlTexture, textures: &SsrTextures) {
+        let shader = &self.hiz_shader;
+
+        shader.use_shader(|| unsafe {
+            let (mut w, mut h) = (textures.width, textures.height);
+
+            for level in 0..textures.hiz_levels {
+                // The first level is a copy of the depth buffer
+                if level == 0 {
+                    gl::BindTextureUnit(0, depth.id);
+                    shader.set_u32(1, cstr!("copyDepth"));
+                } else {
+                    gl::BindTextureUnit(0, textures.hiz.id);
+                    shader.set_u32(0, cstr!("copyDepth"));
+                    shader.set_i32(level - 1, cstr!("srcLevel"));
+
+                    w = (w / 2).max(1);
+                    h = (h / 2).max(1);
+                }
+
+                gl::BindImageTexture(
+                    0,
+                    textures.hiz.id,
+                    level,
+                    gl::FALSE,
+                    0,
+                    gl::WRITE_ONLY,
+                    gl::R32F,
+                );
+
+                post_process::dispatch_compute_2d(w, h);
+                gl::MemoryBarrier(
+                    gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT,
+                );
+            }
+        });
+    }
+
+    fn trace(&self, normals: &GlTexture, textures: &SsrTextures, transforms: &Transforms) {
+        let shader = &self.trace_shader;
+
+        shader.use_shader(|| unsafe {
+            shader.set_mat4(transforms.projection, cstr!("projection"));
+            shader.set_mat4(transforms.projection.inverse(), cstr!("inverseProjection"));
+            shader.set_mat4(transforms.view.inverse(), cstr!("inverseView"));
+            shader.set_mat4(self.prev_view_projection, cstr!("prevViewProjection"));
+
+            gl::BindTextureUnit(0, textures.hiz.id);
+            gl::BindTextureUnit(1, normals.id);
+            gl::BindTextureUnit(2, textures.history.id);
+
+            gl::BindImageTexture(
+                0,
+                textures.reflections.id,
+                0,
+                gl::FALSE,
+                0,
+                gl::WRITE_ONLY,
+                gl::RGBA16F,
+            );
+
+            post_process::dispatch_compute_2d(textures.width, textures.height);
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        });
+    }
+}
@@ -0,0 +1,191 @@
+/// Number of frames a query result can be late, the results are read without waiting for the GPU
+const QUERY_LATENCY: usize = 3;
+/// Maximum number of the views in a frame, the sides of the split view
+const MAX_VIEWS: usize = 2;
+
+/// Statistics of the last rendered frame, displayed in the GUI
+#[derive(Clone, Copy, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    /// Number of times the shader program changed in the opaque pass
+    pub shader_switches: u32,
+    /// All primitives of the scene, including the culled ones
+    pub primitives: u32,
+    /// Primitives outside of the camera frustum
+    pub culled_primitives: u32,
+    /// Fragment shader invocations of the opaque pass per viewport pixel, without the depth prepass
+    pub overdraw: Option<f32>,
+    /// The same measurement as above, but with the depth prepass
+    pub overdraw_prepass: Option<f32>,
+}
+
+impl RenderStats {
+    /// Resets the counters at the start of a frame, the overdraw is kept until a new one is measured
+    pub fn reset_counters(&mut self) {
+        self.draw_calls = 0;
+        self.shader_switches = 0;
+    }
+
+    /// Relative reduction of the shaded fragments, once both variants were measured
+    pub fn overdraw_reduction(&self) -> Option<f32> {
+        match (self.overdraw, self.overdraw_prepass) {
+            (Some(without), Some(with)) if without > 0. => Some(1. - with / without),
+            _ => None,
+        }
+    }
+}
+
+/// Pipeline statistics queries of a single frame
+#[derive(Default)]
+struct PendingQuery {
+    depth_prepass: bool,
+    /// Number of the measured views, each one has its own query
+    views: usize,
+    /// Pixels of all of the measured views
+    pixels: u64,
+}
+
+/// Counts the fragment shader invocations of the opaque pass.
+///
+/// The queries are used in a ring, so the results are read a few frames later without stalling.
+pub struct FragmentStats {
+    /// Queries of the views of each frame in the ring
+    queries: [u32; QUERY_LATENCY * MAX_VIEWS],
+    pending: [Option<PendingQuery>; QUERY_LATENCY],
+    /// The frame that is being measured
+    current: Option<PendingQuery>,
+    /// A query of the current frame is active
+    active: bool,
+    index: usize,
+}
+
+impl FragmentStats {
+    pub fn new() -> Self {
+        let mut queries = [0; QUERY_LATENCY * MAX_VIEWS];
+
+        unsafe {
+            gl::CreateQueries(
+                gl::FRAGMENT_SHADER_INVOCATIONS,
+                queries.len() as i32,
+                queries.as_mut_ptr(),
+            );
+        }
+
+        Self {
+            queries,
+            pending: Default::default(),
+            current: None,
+            active: false,
+            index: 0,
+        }
+    }
+
+    /// The queries aren't created if the driver doesn't support pipeline statistics (e.g. llvmpipe)
+    fn is_supported(&self) -> bool {
+        self.queries[0] != 0
+    }
+
+    /// Collects the results of the frame whose queries are going to be reused and starts measuring a new frame
+    pub fn begin_frame(&mut self, stats: &mut RenderStats) {
+        if !self.is_supported() {
+            return;
+        }
+
+        if let Some(pending) = self.pending[self.index].take() {
+            let queries = &self.frame_queries()[..pending.views];
+
+            let available = queries.iter().all(|&query| {
+                let mut available = 0;
+                unsafe {
+                    gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+                }
+                available != 0
+            });
+
+            // The result is dropped if the GPU is more frames behind than expected
+            if available && pending.pixels > 0 {
+                let invocations: u64 = queries
+                    .iter()
+                    .map(|&query| {
+                        let mut invocations = 0;
+                        unsafe {
+                            gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut invocations);
+                        }
+                        invocations
+                    })
+                    .sum();
+
+                let overdraw = Some(invocations as f32 / pending.pixels as f32);
+                if pending.depth_prepass {
+                    stats.overdraw_prepass = overdraw;
+                } else {
+                    stats.overdraw = overdraw;
+                }
+            }
+        }
+
+        self.current = Some(PendingQuery::default());
+    }
+
+    /// Starts counting the invocations of a view
+    pub fn begin(&mut self) {
+        let Some(current) = &self.current else {
+            return;
+        };
+
+        if current.views < MAX_VIEWS {
+            unsafe {
+                gl::BeginQuery(
+                    gl::FRAGMENT_SHADER_INVOCATIONS,
+                    self.frame_queries()[current.views],
+                );
+            }
+            self.active = true;
+        }
+    }
+
+    /// Stops counting the invocations of a view with the given size
+    pub fn end(&mut self, depth_prepass: bool, width: f32, height: f32) {
+        let Some(current) = &mut self.current else {
+            return;
+        };
+
+        if !self.active {
+            return;
+        }
+
+        unsafe {
+            gl::EndQuery(gl::FRAGMENT_SHADER_INVOCATIONS);
+        }
+        self.active = false;
+
+        current.depth_prepass = depth_prepass;
+        current.views += 1;
+        current.pixels += (width * height) as u64;
+    }
+
+    /// Finishes the measurement of the frame, the results are collected a few frames later
+    pub fn end_frame(&mut self) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+
+        if current.views > 0 {
+            self.pending[self.index] = Some(current);
+            self.index = (self.index + 1) % QUERY_LATENCY;
+        }
+    }
+
+    /// Queries of the frame at the current position of the ring
+    fn frame_queries(&self) -> &[u32] {
+        &self.queries[self.index * MAX_VIEWS..][..MAX_VIEWS]
+    }
+}
+
+impl Drop for FragmentStats {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(self.queries.len() as i32, self.queries.as_ptr());
+        }
+    }
+}
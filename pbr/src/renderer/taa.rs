@@ -0,0 +1,169 @@
+use cstr::cstr;
+use eyre::Result;
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    config::shader_path,
+    ogl::{shader::Shader, texture::GlTexture},
+};
+
+use super::{post_process, transforms::Transforms};
+
+/// Length of the jitter sequence
+pub const JITTER_PHASES: u32 = 8;
+
+/// The accumulated frames, one texture is read while the other one is written
+struct History {
+    textures: [GlTexture; 2],
+    /// Index of the texture with the latest result
+    current: usize,
+    width: i32,
+    height: i32,
+}
+
+impl History {
+    fn new(width: i32, height: i32) -> Self {
+        let create = |label| {
+            let tex = GlTexture::new(gl::TEXTURE_2D);
+
+            unsafe {
+                gl::TextureStorage2D(tex.id, 1, gl::RGBA16F, width, height);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+                gl::TextureParameteri(tex.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            }
+
+            tex.add_label(label);
+            tex
+        };
+
+        Self {
+            textures: [
+                create(cstr!("TAA history 0")),
+                create(cstr!("TAA history 1")),
+            ],
+            current: 0,
+            width,
+            height,
+        }
+    }
+}
+
+/// Temporal anti-aliasing.
+///
+/// The projection is jittered by a subpixel offset every frame and the frames are accumulated
+/// in a history buffer, which is reprojected with the depth buffer when the camera moves.
+pub struct Taa {
+    shader: Shader,
+    history: Option<History>,
+    /// False when the history doesn't contain a previous frame
+    history_valid: bool,
+    frame: u32,
+    /// Projection of the current frame without the jitter
+    projection: Mat4,
+    /// View-projection of the previous frame without the jitter
+    prev_view_projection: Mat4,
+}
+
+impl Taa {
+    pub fn new() -> Result<Self> {
+        let shader = Shader::comp_with_path(shader_path("taa.comp"))?;
+
+        Ok(Self {
+            shader,
+            history: None,
+            history_valid: false,
+            frame: 0,
+            projection: Mat4::IDENTITY,
+            prev_view_projection: Mat4::IDENTITY,
+        })
+    }
+
+    /// Offsets the projection by a subpixel amount that changes every frame
+    pub fn jitter_projection(&mut self, projection: Mat4, width: f32, height: f32) -> Mat4 {
+        self.projection = projection;
+        self.frame = (self.frame + 1) % JITTER_PHASES;
+
+        // Halton (2, 3) sequence centered around the pixel center
+        let jitter = Vec2::new(halton(self.frame + 1, 2), halton(self.frame + 1, 3)) - 0.5;
+        let offset = jitter * 2. / Vec2::new(width, height);
+
+        Mat4::from_translation(Vec3::new(offset.x, offset.y, 0.)) * projection
+    }
+
+    /// The next frame won't use the history, for example when TAA was disabled for some time
+    pub fn invalidate_history(&mut self) {
+        self.history_valid = false;
+    }
+
+    /// Accumulates the current frame into the history and returns the anti-aliased image
+    pub fn resolve(
+        &mut self,
+        color: &GlTexture,
+        depth: &GlTexture,
+        width: i32,
+        height: i32,
+        transforms: &Transforms,
+    ) -> &GlTexture {
+        let mut history = match self.history.take() {
+            Some(h) if h.width == width && h.height == height => h,
+            _ => {
+                self.history_valid = false;
+                History::new(width, height)
+            }
+        };
+
+        let read = history.current;
+        let write = 1 - read;
+        // Both the reconstruction and the reprojection are unjittered, so a still frame maps onto
+        // the same pixel of the history and stays sharp
+        let view_projection = self.projection * transforms.view;
+        let inverse_view_projection = view_projection.inverse();
+
+        self.shader.use_shader(|| unsafe {
+            self.shader
+                .set_mat4(inverse_view_projection, cstr!("inverseViewProjection"));
+            self.shader
+                .set_mat4(self.prev_view_projection, cstr!("prevViewProjection"));
+            self.shader
+                .set_u32(self.history_valid as u32, cstr!("historyValid"));
+
+            gl::BindTextureUnit(0, color.id);
+            gl::BindTextureUnit(1, depth.id);
+            gl::BindTextureUnit(2, history.textures[read].id);
+            gl::BindImageTexture(
+                0,
+                history.textures[write].id,
+                0,
+                gl::FALSE,
+                0,
+                gl::WRITE_ONLY,
+                gl::RGBA16F,
+            );
+
+            post_process::dispatch_compute_2d(width, height);
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        });
+
+        history.current = write;
+        self.history_valid = true;
+        self.prev_view_projection = view_projection;
+
+        &self.history.insert(history).textures[write]
+    }
+}
+
+/// Element of the Halton low-discrepancy sequence
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.;
+    let mut fraction = 1.;
+
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+
+    result
+}
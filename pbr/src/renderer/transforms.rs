@@ -20,6 +20,14 @@ impl Transforms {
             model: Mat4::IDENTITY,
         }
     }
+
+    /// Extracts the near and far planes from the OpenGL perspective projection matrix
+    pub fn near_far(&self) -> (f32, f32) {
+        let a = self.projection.z_axis.z;
+        let b = self.projection.w_axis.z;
+
+        (b / (a - 1.), b / (a + 1.))
+    }
 }
 
 impl UniformBufferElement for Transforms {
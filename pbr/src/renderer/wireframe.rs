@@ -0,0 +1,162 @@
+use cstr::cstr;
+use eyre::Result;
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    app_settings::ViewportDim,
+    config::shader_path,
+    ogl::{shader::Shader, texture::GlTexture, uniform_buffer::UniformBuffer},
+    scene::{Node, Primitive},
+};
+
+use super::transforms::Transforms;
+
+/// User-configurable parameters of the wireframe overlay
+#[derive(Clone, Copy)]
+pub struct WireframeSettings {
+    pub enabled: bool,
+    pub color: Vec3,
+    /// Width of the lines in pixels
+    pub line_width: f32,
+    pub show_normals: bool,
+    pub show_tangents: bool,
+    pub show_bitangents: bool,
+    /// Length of the vertex vectors in world units
+    pub vector_length: f32,
+}
+
+impl WireframeSettings {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            color: Vec3::splat(0.),
+            line_width: 1.,
+            show_normals: false,
+            show_tangents: false,
+            show_bitangents: false,
+            vector_length: 0.05,
+        }
+    }
+
+    pub fn shows_vectors(&self) -> bool {
+        self.show_normals || self.show_tangents || self.show_bitangents
+    }
+}
+
+/// Draws the edges of the triangles and the normals, tangents and bitangents of the vertices
+/// on top of the shaded scene.
+pub struct Wireframe {
+    /// Solid wireframe, the geometry shader computes the distances to the edges of the triangle
+    wireframe_shader: Shader,
+    /// The geometry shader turns every vertex into lines
+    vectors_shader: Shader,
+}
+
+impl Wireframe {
+    pub fn new() -> Result<Self> {
+        let wireframe_shader = Shader::with_files_geometry(
+            shader_path("basic.vert"),
+            shader_path("wireframe.geom"),
+            shader_path("wireframe.frag"),
+        )?;
+        let vectors_shader = Shader::with_files_geometry(
+            shader_path("basic.vert"),
+            shader_path("vertex_vectors.geom"),
+            shader_path("vertex_vectors.frag"),
+        )?;
+
+        Ok(Self {
+            wireframe_shader,
+            vectors_shader,
+        })
+    }
+
+    /// Draws the overlay of the scene into the output, after the post-processing.
+    ///
+    /// The lines are depth-tested against the depth of the scene, `viewport_dim` is the viewport
+    /// of the scene in the output. The projection of the transforms mustn't be jittered.
+    pub fn render(
+        &self,
+        root: &Node,
+        model: Mat4,
+        transforms: &mut UniformBuffer<Transforms>,
+        settings: &WireframeSettings,
+        scene_depth: &GlTexture,
+        viewport_dim: &ViewportDim,
+    ) {
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::BindTextureUnit(0, scene_depth.id);
+        }
+
+        let viewport_origin = Vec2::new(viewport_dim.min_x, viewport_dim.min_y);
+
+        if settings.enabled {
+            let shader = &self.wireframe_shader;
+            shader.use_shader(|| {
+                let viewport_size = Vec2::new(viewport_dim.width, viewport_dim.height);
+                shader.set_vec2(viewport_size, cstr!("viewportSize"));
+                shader.set_vec2(viewport_origin, cstr!("viewportOrigin"));
+                shader.set_vec3(settings.color, cstr!("lineColor"));
+                shader.set_f32(settings.line_width, cstr!("lineWidth"));
+
+                Self::render_node(root, model, transforms, &|primitive| unsafe {
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        primitive.num_indices as i32,
+                        primitive.indices_type,
+                        0 as _,
+                    );
+                });
+            });
+        }
+
+        if settings.shows_vectors() {
+            let shader = &self.vectors_shader;
+            shader.use_shader(|| {
+                shader.set_vec2(viewport_origin, cstr!("viewportOrigin"));
+                shader.set_f32(settings.vector_length, cstr!("vectorLength"));
+                shader.set_u32(settings.show_normals as u32, cstr!("showNormals"));
+                shader.set_u32(settings.show_tangents as u32, cstr!("showTangents"));
+                shader.set_u32(settings.show_bitangents as u32, cstr!("showBitangents"));
+
+                // Every vertex of the vertex buffer once, regardless of the indices
+                Self::render_node(root, model, transforms, &|primitive| unsafe {
+                    gl::DrawArrays(gl::POINTS, 0, primitive.num_vertices as i32);
+                });
+            });
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+            // The post-processing leaves the blending disabled
+            gl::Disable(gl::BLEND);
+        }
+    }
+
+    fn render_node(
+        node: &Node,
+        outer_transform: Mat4,
+        transforms: &mut UniformBuffer<Transforms>,
+        draw: &dyn Fn(&Primitive),
+    ) {
+        let transform = outer_transform * node.transform;
+
+        if let Some(mesh) = &node.mesh {
+            transforms.inner.model = transform;
+            transforms.update();
+
+            for primitive in &mesh.primitives {
+                unsafe {
+                    gl::BindVertexArray(primitive.vao.id);
+                }
+                draw(primitive);
+            }
+        }
+
+        for child in &node.children {
+            Self::render_node(child, transform, transforms, draw);
+        }
+    }
+}
@@ -1,90 +1,715 @@
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
 use eyre::{eyre, Result};
 use shader_constants::CONSTS;
 
-use crate::{brdf_raw::BrdfRaw, renderer::ibl::IblEnv, scene::Scene, util::timed_scope};
+use crate::{
+    app_settings::AppSettings,
+    brdf_raw::{BrdfData, BrdfRaw},
+    config::ResourceRoots,
+    renderer::ibl::{IblComputation, IblData, IblEnv},
+    scene::{ProceduralScene, Scene, SceneData},
+    util::timed_scope,
+};
+
+mod watcher;
+
+use self::watcher::ResourceWatcher;
 
 pub struct Resources {
-    pub scenes: Vec<LazyResource<Scene>>,
-    pub envmaps: Vec<LazyResource<IblEnv>>,
-    pub merl_brdfs: Vec<LazyResource<BrdfRaw<{ CONSTS.buffer_bindings.brdf_merl }>>>,
-    pub utia_brdfs: Vec<LazyResource<BrdfRaw<{ CONSTS.buffer_bindings.brdf_utia }>>>,
+    pub scenes: ResourceList<Scene>,
+    pub envmaps: ResourceList<IblEnv>,
+    pub merl_brdfs: ResourceList<BrdfRaw<{ CONSTS.buffer_bindings.brdf_merl }>>,
+    pub utia_brdfs: ResourceList<BrdfRaw<{ CONSTS.buffer_bindings.brdf_utia }>>,
+    /// Hot reloading is disabled if the watcher couldn't be created
+    watcher: Option<ResourceWatcher>,
+    /// Least recently used resources are unloaded when their memory usage exceeds the budget (in bytes)
+    pub memory_budget: usize,
 }
 
 impl Resources {
-    /// Adds models to the scene
-    pub fn init() -> Result<Self> {
-        let scenes = Self::add_glob_res("resources/gltf/**/*.{gltf,glb}");
+    /// Finds the resources in the configured directories and files
+    pub fn init(roots: ResourceRoots, memory_budget: usize) -> Result<Self> {
+        let scenes = ResourceList::new(roots.scenes, &["gltf", "glb"]);
+
+        let envmaps = ResourceList::new(roots.envmaps, &["hdr"]);
+        let mut merl_brdfs = ResourceList::new(roots.merl_brdfs, &["binary"]);
+        merl_brdfs.sort_by_name();
 
-        let envmaps = Self::add_glob_res("resources/IBL/**/*.hdr");
-        let mut merl_brdfs = Self::add_glob_res("resources/BRDFDatabase/**/*.binary");
-        merl_brdfs.sort_by(|m1, m2| m1.name().cmp(m2.name()));
+        let mut utia_brdfs = ResourceList::new(roots.utia_brdfs, &["bin"]);
+        utia_brdfs.sort_by_name();
 
-        let mut utia_brdfs = Self::add_glob_res("resources/UTIA/**/*.bin");
-        utia_brdfs.sort_by(|m1, m2| m1.name().cmp(m2.name()));
+        let watched = scenes
+            .watched_dirs()
+            .chain(envmaps.watched_dirs())
+            .chain(merl_brdfs.watched_dirs())
+            .chain(utia_brdfs.watched_dirs());
+
+        let watcher = match ResourceWatcher::new(watched) {
+            Ok(w) => Some(w),
+            Err(e) => {
+                eprintln!("Hot reloading of resources is disabled: {e}");
+                None
+            }
+        };
 
         Ok(Self {
             scenes,
             envmaps,
             merl_brdfs,
             utia_brdfs,
+            watcher,
+            memory_budget,
         })
     }
 
-    fn add_glob_res<T: LoadResource>(glob_path: &str) -> Vec<LazyResource<T>> {
-        let mut resources = Vec::new();
-        for res in globwalk::glob(glob_path).unwrap() {
-            if let Ok(res) = res {
-                if let Some(p) = res.path().to_str().map(|s| s.to_string()) {
-                    let envmap = LazyResource::new(p);
-                    resources.push(envmap);
+    /// Reloads resources that have changed on disk and picks up added or deleted files.
+    ///
+    /// The selected resource indices are updated if the lists have changed.
+    pub fn reload_changed(&mut self, app_settings: &mut AppSettings) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+
+        let changed = watcher.changed_paths();
+        if changed.is_empty() {
+            return;
+        }
+
+        let split_side = &mut app_settings.split.right;
+
+        self.scenes
+            .handle_changes(&changed, [&mut app_settings.selected_scene]);
+        self.envmaps
+            .handle_changes(&changed, [&mut app_settings.selected_envmap]);
+        // Both sides of the split view select from the same lists of BRDFs
+        self.merl_brdfs.handle_changes(
+            &changed,
+            [
+                &mut app_settings.selected_merl_brdf,
+                &mut split_side.selected_merl_brdf,
+            ],
+        );
+        self.utia_brdfs.handle_changes(
+            &changed,
+            [
+                &mut app_settings.selected_utia_brdf,
+                &mut split_side.selected_utia_brdf,
+            ],
+        );
+    }
+
+    pub fn unload(&mut self) {
+        for list in self.lists_mut() {
+            list.unload_all();
+        }
+    }
+
+    /// Approximate memory used by all of the loaded resources in bytes
+    pub fn memory_size(&self) -> usize {
+        self.scenes.memory_size()
+            + self.envmaps.memory_size()
+            + self.merl_brdfs.memory_size()
+            + self.utia_brdfs.memory_size()
+    }
+
+    /// Unloads the least recently used resources until the memory usage fits into the budget.
+    ///
+    /// Resources that are currently displayed are never unloaded.
+    /// Called once per frame, after the frame has been rendered.
+    pub fn enforce_budget(&mut self) {
+        while self.memory_size() > self.memory_budget {
+            let lists = self.lists_mut();
+
+            let lru = lists
+                .iter()
+                .enumerate()
+                .filter_map(|(list, l)| {
+                    l.lru_candidate().map(|(i, last_used)| (list, i, last_used))
+                })
+                .min_by_key(|(_, _, last_used)| *last_used);
+
+            match lru {
+                Some((list, i, _)) => lists[list].evict(i),
+                // Only the displayed resources are left
+                None => break,
+            }
+        }
+
+        for list in self.lists_mut() {
+            list.end_frame();
+        }
+    }
+
+    fn lists_mut(&mut self) -> [&mut dyn EvictableList; 4] {
+        [
+            &mut self.scenes,
+            &mut self.envmaps,
+            &mut self.merl_brdfs,
+            &mut self.utia_brdfs,
+        ]
+    }
+}
+
+/// Type-erased operations for unloading resources across all of the resource kinds
+trait EvictableList {
+    /// Returns the index and the last use of the least recently used resource that can be unloaded
+    fn lru_candidate(&self) -> Option<(usize, Instant)>;
+
+    fn evict(&mut self, index: usize);
+
+    fn unload_all(&mut self);
+
+    /// Forgets which resources were used in the last frame
+    fn end_frame(&mut self);
+}
+
+impl<T: LoadResource> EvictableList for ResourceList<T> {
+    fn lru_candidate(&self) -> Option<(usize, Instant)> {
+        self.resources
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| {
+                Some(*i) != self.displayed && !self.used_this_frame.contains(i) && r.is_loaded()
+            })
+            .map(|(i, r)| (i, r.last_used))
+            .min_by_key(|(_, last_used)| *last_used)
+    }
+
+    fn evict(&mut self, index: usize) {
+        let res = &mut self.resources[index];
+        println!(
+            "Unloading '{}' ({}) to fit into the memory budget",
+            res.path,
+            format_size(res.memory_size())
+        );
+        res.unload();
+    }
+
+    fn unload_all(&mut self) {
+        for res in self.resources.iter_mut() {
+            res.unload();
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.used_this_frame.clear();
+    }
+}
+
+/// A list of lazily loaded resources of the same kind
+pub struct ResourceList<T: LoadResource> {
+    resources: Vec<LazyResource<T>>,
+    /// Index of the resource that is currently displayed.
+    /// The previous resource keeps being displayed until the newly selected one is loaded.
+    displayed: Option<usize>,
+    /// Indices of the resources returned by `get` in the current frame.
+    /// Both sides of the split view can display a different resource of the same list.
+    used_this_frame: Vec<usize>,
+    /// Directories that are searched for the resources (recursively) or individual files
+    roots: Vec<PathBuf>,
+    /// Extensions of the resource files
+    extensions: &'static [&'static str],
+    sorted: bool,
+}
+
+impl<T: LoadResource> ResourceList<T> {
+    fn new(roots: Vec<PathBuf>, extensions: &'static [&'static str]) -> Self {
+        let mut list = Self {
+            resources: Vec::new(),
+            displayed: None,
+            used_this_frame: Vec::new(),
+            roots,
+            extensions,
+            sorted: false,
+        };
+
+        list.resources = list
+            .find_files()
+            .into_iter()
+            .map(LazyResource::new)
+            .collect();
+        // Built-in resources are listed after the files
+        list.resources.extend(
+            T::builtin_names()
+                .into_iter()
+                .map(LazyResource::new_builtin),
+        );
+        list
+    }
+
+    /// Finds all of the resource files in the roots
+    fn find_files(&self) -> Vec<String> {
+        let pattern = format!("**/*.{{{}}}", self.extensions.join(","));
+        let mut files = Vec::new();
+
+        for root in &self.roots {
+            if root.is_file() {
+                match root.to_str() {
+                    Some(path) => files.push(path.to_string()),
+                    None => eprintln!("Resource path '{}' isn't valid UTF-8", root.display()),
                 }
+                continue;
             }
+
+            let walker = match globwalk::GlobWalkerBuilder::from_patterns(root, &[&pattern]).build()
+            {
+                Ok(walker) => walker,
+                Err(e) => {
+                    eprintln!("Can't search '{}': {e}", root.display());
+                    continue;
+                }
+            };
+
+            files.extend(
+                walker
+                    .flatten()
+                    .filter_map(|entry| entry.path().to_str().map(|s| s.to_string())),
+            );
         }
 
-        resources
+        // The same file can be reachable from multiple roots
+        let mut seen = HashSet::new();
+        files.retain(|f| seen.insert(f.clone()));
+        files
     }
 
-    pub fn unload(&mut self) {
-        for scene in &mut self.scenes {
-            scene.unload();
+    /// Directories that need to be watched for changes and whether they should be watched recursively.
+    ///
+    /// The parent directory is watched for single files, because editors usually replace the whole file.
+    fn watched_dirs(&self) -> impl Iterator<Item = (PathBuf, bool)> + '_ {
+        self.roots.iter().filter_map(|root| {
+            let (dir, recursive) = if root.is_dir() {
+                (root.as_path(), true)
+            } else {
+                (root.parent()?, false)
+            };
+
+            // An empty parent means the working directory
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+
+            let dir = fs::canonicalize(dir).ok()?;
+            Some((dir, recursive))
+        })
+    }
+
+    fn sort_by_name(&mut self) {
+        self.sorted = true;
+        self.resources.sort_by(|r1, r2| r1.name().cmp(r2.name()));
+    }
+
+    /// Reloads resources affected by the changed files and updates the list with added or deleted files.
+    ///
+    /// All of the `selected` indices into this list are fixed up if the list has changed.
+    fn handle_changes<const N: usize>(&mut self, changed: &[PathBuf], selected: [&mut usize; N]) {
+        let watched: Vec<(PathBuf, bool)> = self.watched_dirs().collect();
+        let changed: Vec<&PathBuf> = changed
+            .iter()
+            .filter(|p| {
+                watched.iter().any(|(dir, recursive)| {
+                    if *recursive {
+                        p.starts_with(dir)
+                    } else {
+                        p.parent() == Some(dir.as_path())
+                    }
+                })
+            })
+            .collect();
+
+        if changed.is_empty() {
+            return;
+        }
+
+        for res in self.resources.iter_mut().filter(|r| !r.builtin) {
+            // Other files (buffers, textures...) only affect the resources that were loaded from them
+            let affected = changed
+                .iter()
+                .any(|path| res.abs_path == **path || res.dependencies.contains(path));
+
+            if affected {
+                res.reload();
+            }
+        }
+
+        let old_paths = self.refresh();
+        for selected in selected {
+            self.fix_index(&old_paths, selected);
         }
+    }
 
-        for scene in &mut self.merl_brdfs {
-            scene.unload();
+    /// Adds new files to the list and removes deleted ones.
+    ///
+    /// Returns the paths of the resources before the refresh, for fixing up the indices into the list.
+    fn refresh(&mut self) -> Vec<String> {
+        let old_paths: Vec<String> = self.resources.iter().map(|r| r.path.clone()).collect();
+
+        let files = self.find_files();
+
+        self.resources
+            .retain(|r| r.builtin || files.contains(&r.path));
+        for file in files {
+            if !self.resources.iter().any(|r| !r.builtin && r.path == file) {
+                println!("Found a new resource: '{file}'");
+                let first_builtin = self
+                    .resources
+                    .iter()
+                    .position(|r| r.builtin)
+                    .unwrap_or(self.resources.len());
+                self.resources
+                    .insert(first_builtin, LazyResource::new(file));
+            }
         }
+
+        if self.sorted {
+            self.sort_by_name();
+        }
+
+        self.displayed = self.displayed.and_then(|d| self.new_index(&old_paths, d));
+        self.used_this_frame = self
+            .used_this_frame
+            .iter()
+            .filter_map(|&i| self.new_index(&old_paths, i))
+            .collect();
+
+        old_paths
+    }
+
+    /// Finds the index of a resource in the refreshed list by its index in the old list
+    fn new_index(&self, old_paths: &[String], old_index: usize) -> Option<usize> {
+        let path = old_paths.get(old_index)?;
+        self.resources.iter().position(|r| r.path == *path)
+    }
+
+    /// Updates a selected index after the list was refreshed.
+    ///
+    /// Falls back to the displayed resource if the selected one was deleted.
+    fn fix_index(&self, old_paths: &[String], selected: &mut usize) {
+        *selected = self
+            .new_index(old_paths, *selected)
+            .or(self.displayed)
+            .unwrap_or(0);
+    }
+
+    /// Returns the selected resource if it's loaded, otherwise starts loading it
+    /// and returns the previously displayed resource (if there is one).
+    pub fn get(&mut self, selected: usize) -> Option<&mut T> {
+        if let Some(resource) = self.resources.get_mut(selected) {
+            resource.request_load();
+
+            if resource.poll().is_some() {
+                self.displayed = Some(selected);
+            }
+        }
+
+        let displayed = self.displayed?;
+        if !self.used_this_frame.contains(&displayed) {
+            self.used_this_frame.push(displayed);
+        }
+
+        let resource = &mut self.resources[displayed];
+        resource.last_used = Instant::now();
+        // Polling the selected resource again would continue its upload twice
+        match displayed == selected {
+            true => resource.resource(),
+            false => resource.poll(),
+        }
+    }
+
+    /// Approximate memory used by the loaded resources in bytes
+    pub fn memory_size(&self) -> usize {
+        self.resources.iter().map(|r| r.memory_size()).sum()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut LazyResource<T>> {
+        self.resources.iter_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Finds a resource by its path or by its name
+    pub fn find(&self, resource: &str) -> Option<usize> {
+        let abs_path = fs::canonicalize(resource).ok();
+
+        self.resources.iter().position(|r| {
+            r.path == resource || Some(&r.abs_path) == abs_path.as_ref() || r.name() == resource
+        })
+    }
+
+    /// Loads the resource and waits until it's uploaded, for when there is no render loop
+    pub fn load_blocking(&mut self, selected: usize) -> Result<()> {
+        let resource = self
+            .resources
+            .get_mut(selected)
+            .ok_or_else(|| eyre!("Resource index {selected} is out of bounds"))?;
+
+        resource.request_load();
+
+        loop {
+            resource.poll();
+
+            match resource.status() {
+                LoadStatus::Loaded => break,
+                LoadStatus::Failed(e) => {
+                    return Err(eyre!("Couldn't load '{}': {e}", resource.path));
+                }
+                // The split uploads continue right away, only the loader thread is waited for
+                LoadStatus::Unloaded | LoadStatus::Loading(_) => {
+                    if !matches!(resource.state, ResourceState::Uploading { .. }) {
+                        thread::sleep(Duration::from_millis(10))
+                    }
+                }
+            }
+        }
+
+        self.displayed = Some(selected);
+        Ok(())
     }
 }
 
-/// Loading models takes a long time, load them lazily
+/// Loading models takes a long time, load them lazily.
+///
+/// Files are parsed and decoded on a loader thread, only the GPU upload happens on the render thread.
 pub struct LazyResource<T: LoadResource> {
+    /// Name of the resource if it's built-in
     path: String,
-    resource: Option<T>,
+    /// Built-in resources are generated instead of loaded from a file
+    builtin: bool,
+    /// Canonical path for matching the file change events
+    abs_path: PathBuf,
+    /// Other files that the resource was loaded from, canonical as well
+    dependencies: Vec<PathBuf>,
+    state: ResourceState<T>,
+    /// Used for unloading the least recently used resources
+    last_used: Instant,
+}
+
+enum ResourceState<T: LoadResource> {
+    Unloaded,
+    /// The CPU-side data is being loaded on a loader thread
+    Loading {
+        receiver: Receiver<Result<T::Data>>,
+        progress: LoadProgress,
+        /// The old version of a resource that is being reloaded, used until the new one is ready
+        stale: Option<T>,
+    },
+    /// The GPU upload is split over multiple frames
+    Uploading {
+        pending: T::Pending,
+        progress: LoadProgress,
+        stale: Option<T>,
+    },
+    Loaded(T),
+    Failed(String),
+}
+
+/// Loading state of a resource that can be displayed in the GUI
+pub enum LoadStatus<'a> {
+    Unloaded,
+    /// Loading progress in the range <0, 1>
+    Loading(f32),
+    Loaded,
+    Failed(&'a str),
 }
 
 impl<T: LoadResource> LazyResource<T> {
     fn new(path: String) -> Self {
+        let abs_path = fs::canonicalize(&path).unwrap_or_else(|_| PathBuf::from(&path));
+
         Self {
             path,
-            resource: None,
+            builtin: false,
+            abs_path,
+            dependencies: Vec::new(),
+            state: ResourceState::Unloaded,
+            last_used: Instant::now(),
+        }
+    }
+
+    fn new_builtin(name: &str) -> Self {
+        Self {
+            path: name.to_string(),
+            builtin: true,
+            abs_path: PathBuf::new(),
+            dependencies: Vec::new(),
+            state: ResourceState::Unloaded,
+            last_used: Instant::now(),
         }
     }
 
-    pub fn load(&mut self) -> Result<&mut T> {
-        // Can't use if let Some(...) because the borrow checker is angry
-        // Can't use get_or_insert_with(...) because I need error handling
-        if self.resource.is_some() {
-            Ok(self.resource.as_mut().unwrap())
-        } else {
-            let path = &self.path;
-            let resource = timed_scope(&format!("Loading '{path}'"), || T::load(&path))?;
+    /// Starts loading the resource on a loader thread (if it isn't already loading or loaded)
+    pub fn request_load(&mut self) {
+        if matches!(self.state, ResourceState::Unloaded) {
+            self.state = self.spawn_loader(None);
+        }
+    }
+
+    /// Loads the resource again if it was loaded before, the old version is used until then
+    fn reload(&mut self) {
+        let stale = match std::mem::replace(&mut self.state, ResourceState::Unloaded) {
+            ResourceState::Loaded(resource) => Some(resource),
+            // The old loader thread may have read the file before the change finished
+            ResourceState::Loading { stale, .. } | ResourceState::Uploading { stale, .. } => stale,
+            // Resources that failed to load are loaded again when they're selected
+            ResourceState::Unloaded | ResourceState::Failed(_) => return,
+        };
+
+        println!("Reloading '{}'", self.path);
+        self.state = self.spawn_loader(stale);
+    }
 
-            self.resource = Some(resource);
+    fn spawn_loader(&self, stale: Option<T>) -> ResourceState<T> {
+        let (sender, receiver) = mpsc::channel();
+        let progress = LoadProgress::default();
 
-            Ok(self.resource.as_mut().unwrap())
+        let path = self.path.clone();
+        let builtin = self.builtin;
+        let thread_progress = progress.clone();
+
+        let spawned = thread::Builder::new()
+            .name(format!("loader: {}", self.name()))
+            .spawn(move || {
+                let data = timed_scope(&format!("Loading '{path}'"), || {
+                    if builtin {
+                        T::generate_data(&path, &thread_progress)
+                    } else {
+                        T::load_data(&path, &thread_progress)
+                    }
+                });
+
+                // The receiver is gone if the resource was unloaded in the meantime
+                let _ = sender.send(data);
+            });
+
+        match spawned {
+            Ok(_) => ResourceState::Loading {
+                receiver,
+                progress,
+                stale,
+            },
+            Err(e) => ResourceState::Failed(format!("Couldn't spawn a loader thread: {e}")),
+        }
+    }
+
+    /// Checks if the loader thread has finished and uploads the data if it has,
+    /// an upload that is split over multiple frames is continued.
+    ///
+    /// Returns the resource if it's loaded (or the old version if it's being reloaded).
+    pub fn poll(&mut self) -> Option<&mut T> {
+        self.state = match std::mem::replace(&mut self.state, ResourceState::Unloaded) {
+            ResourceState::Loading {
+                receiver,
+                progress,
+                stale,
+            } => match receiver.try_recv() {
+                Ok(data) => self.finish_loading(data, progress, stale),
+                Err(TryRecvError::Empty) => ResourceState::Loading {
+                    receiver,
+                    progress,
+                    stale,
+                },
+                Err(TryRecvError::Disconnected) => {
+                    ResourceState::Failed("The loader thread panicked".to_string())
+                }
+            },
+            ResourceState::Uploading {
+                pending,
+                progress,
+                stale,
+            } => {
+                let upload = T::continue_upload(pending, &progress);
+                self.upload_state(upload, progress, stale)
+            }
+            state => state,
+        };
+
+        self.resource()
+    }
+
+    /// The resource if it's loaded (or the old version if it's being reloaded)
+    fn resource(&mut self) -> Option<&mut T> {
+        match &mut self.state {
+            ResourceState::Loaded(resource) => Some(resource),
+            ResourceState::Loading { stale, .. } | ResourceState::Uploading { stale, .. } => {
+                stale.as_mut()
+            }
+            _ => None,
+        }
+    }
+
+    fn finish_loading(
+        &mut self,
+        data: Result<T::Data>,
+        progress: LoadProgress,
+        stale: Option<T>,
+    ) -> ResourceState<T> {
+        if let Ok(data) = &data {
+            self.dependencies = T::dependencies(data);
+        }
+
+        let path = &self.path;
+        let upload =
+            data.and_then(|data| timed_scope(&format!("Uploading '{path}'"), || T::upload(data)));
+
+        self.upload_state(upload, progress, stale)
+    }
+
+    /// The state after a step of the GPU upload
+    fn upload_state(
+        &mut self,
+        upload: Result<Upload<T>>,
+        progress: LoadProgress,
+        stale: Option<T>,
+    ) -> ResourceState<T> {
+        self.last_used = Instant::now();
+
+        match upload {
+            Ok(Upload::Done(resource)) => ResourceState::Loaded(resource),
+            Ok(Upload::Pending(pending)) => ResourceState::Uploading {
+                pending,
+                progress,
+                stale,
+            },
+            Err(e) => {
+                eprintln!("Error loading '{}': {e:?}", self.path);
+                ResourceState::Failed(format!("{e}"))
+            }
+        }
+    }
+
+    pub fn status(&self) -> LoadStatus<'_> {
+        match &self.state {
+            ResourceState::Unloaded => LoadStatus::Unloaded,
+            ResourceState::Loading { progress, .. } | ResourceState::Uploading { progress, .. } => {
+                LoadStatus::Loading(progress.get())
+            }
+            ResourceState::Loaded(_) => LoadStatus::Loaded,
+            ResourceState::Failed(e) => LoadStatus::Failed(e),
         }
     }
 
     pub fn name(&self) -> &str {
+        if self.builtin {
+            return &self.path;
+        }
+
         // Find the index where the filename starts (if any)
         let start = self
             .path
@@ -97,38 +722,173 @@ impl<T: LoadResource> LazyResource<T> {
         &self.path[start..end]
     }
 
+    /// Also resets the error of a resource that failed to load, so it can be loaded again
     pub fn unload(&mut self) {
-        self.resource = None;
+        self.state = ResourceState::Unloaded;
+    }
+
+    /// Approximate memory used by the resource in bytes (0 if it isn't loaded)
+    pub fn memory_size(&self) -> usize {
+        match &self.state {
+            ResourceState::Loaded(resource) => resource.memory_size(),
+            ResourceState::Loading {
+                stale: Some(resource),
+                ..
+            }
+            | ResourceState::Uploading {
+                stale: Some(resource),
+                ..
+            } => resource.memory_size(),
+            _ => 0,
+        }
+    }
+
+    fn is_loaded(&self) -> bool {
+        matches!(self.state, ResourceState::Loaded(_))
     }
 }
 
+/// Formats a size in bytes as MiB
+pub fn format_size(bytes: usize) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024. * 1024.))
+}
+
+/// Shared progress of a resource that is being loaded on a loader thread
+#[derive(Clone, Default)]
+pub struct LoadProgress(Arc<AtomicU32>);
+
+impl LoadProgress {
+    /// Sets the progress, `fraction` should be in the range <0, 1>
+    pub fn set(&self, fraction: f32) {
+        self.0.store(fraction.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Result of a step of the GPU upload
+pub enum Upload<T: LoadResource> {
+    Done(T),
+    /// The upload continues in the next frame
+    Pending(T::Pending),
+}
+
 pub trait LoadResource: Sized {
-    fn load(path: &str) -> Result<Self>;
+    /// CPU-side data of the resource
+    type Data: Send + 'static;
+
+    /// Reads and decodes the file, runs on a loader thread
+    fn load_data(path: &str, progress: &LoadProgress) -> Result<Self::Data>;
+
+    /// Names of the resources that are generated instead of loaded from files
+    fn builtin_names() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Generates a built-in resource, runs on a loader thread
+    fn generate_data(name: &str, _progress: &LoadProgress) -> Result<Self::Data> {
+        Err(eyre!("'{name}' isn't a built-in resource"))
+    }
+
+    /// Other files that the resource was loaded from, changes to them reload the resource
+    fn dependencies(_data: &Self::Data) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Partially uploaded resource, for the uploads that are split over multiple frames
+    type Pending;
+
+    /// Creates the GPU resources, runs on the render thread
+    fn upload(data: Self::Data) -> Result<Upload<Self>>;
+
+    /// Continues a pending upload with a bounded amount of work, runs on the render thread once per frame
+    fn continue_upload(_pending: Self::Pending, _progress: &LoadProgress) -> Result<Upload<Self>> {
+        Err(eyre!("The resource isn't uploaded over multiple frames"))
+    }
+
+    /// Approximate GPU memory used by the resource in bytes
+    fn memory_size(&self) -> usize;
 }
 
 impl LoadResource for Scene {
-    fn load(path: &str) -> Result<Self> {
-        Self::from_gltf(path)
+    type Data = SceneData;
+
+    fn load_data(path: &str, progress: &LoadProgress) -> Result<Self::Data> {
+        SceneData::from_gltf(path, progress)
+    }
+
+    fn builtin_names() -> Vec<&'static str> {
+        ProceduralScene::ALL.iter().map(|s| s.to_str()).collect()
+    }
+
+    fn generate_data(name: &str, progress: &LoadProgress) -> Result<Self::Data> {
+        let data = ProceduralScene::from_name(name)?.generate();
+        progress.set(1.);
+        Ok(data)
+    }
+
+    fn dependencies(data: &Self::Data) -> Vec<PathBuf> {
+        data.dependencies.clone()
+    }
+
+    type Pending = Infallible;
+
+    fn upload(data: Self::Data) -> Result<Upload<Self>> {
+        Ok(Upload::Done(Self::from_data(data)))
+    }
+
+    fn memory_size(&self) -> usize {
+        self.memory_size
     }
 }
 
 impl<const BINDING: u32> LoadResource for BrdfRaw<BINDING> {
-    fn load(path: &str) -> Result<Self> {
+    type Data = BrdfData;
+
+    fn load_data(path: &str, progress: &LoadProgress) -> Result<Self::Data> {
         let ext = path
             .rsplit_once(".")
             .ok_or(eyre!("BRDF file has no extension name, cannot infer type"))?
             .1;
 
         match ext {
-            "bin" => Self::utia_from_path(path),
-            "binary" => Self::merl_from_path(path),
+            "bin" => BrdfData::utia_from_path(path, progress),
+            "binary" => BrdfData::merl_from_path(path, progress),
             _ => Err(eyre!("BRDF file has no extension name, cannot infer type")),
         }
     }
+
+    type Pending = Infallible;
+
+    fn upload(data: Self::Data) -> Result<Upload<Self>> {
+        Ok(Upload::Done(Self::from_data(data)))
+    }
+
+    fn memory_size(&self) -> usize {
+        self.memory_size
+    }
 }
 
 impl LoadResource for IblEnv {
-    fn load(path: &str) -> Result<Self> {
-        Self::from_equimap_path(path)
+    type Data = IblData;
+
+    fn load_data(path: &str, progress: &LoadProgress) -> Result<Self::Data> {
+        IblData::load(path, progress)
+    }
+
+    type Pending = IblComputation;
+
+    fn upload(data: Self::Data) -> Result<Upload<Self>> {
+        Self::from_data(data)
+    }
+
+    fn continue_upload(pending: Self::Pending, progress: &LoadProgress) -> Result<Upload<Self>> {
+        Ok(pending.advance(progress))
+    }
+
+    fn memory_size(&self) -> usize {
+        self.memory_size()
     }
 }
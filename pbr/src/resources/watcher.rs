@@ -0,0 +1,83 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Editors and exporters often write a file in multiple steps.
+/// A path is only reported after it hasn't changed for this long.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the resource directories for changes
+pub struct ResourceWatcher {
+    /// Has to be kept alive, the watching stops when it's dropped
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    /// Changed paths and the time of their last change
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl ResourceWatcher {
+    /// Watches the directories, the bool specifies if a directory should be watched recursively
+    pub fn new(dirs: impl Iterator<Item = (PathBuf, bool)>) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver is only gone when the app is shutting down
+            let _ = sender.send(event);
+        })?;
+
+        for (dir, recursive) in dirs {
+            let mode = if recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+
+            if let Err(e) = watcher.watch(&dir, mode) {
+                eprintln!("Can't watch '{}' for changes: {e}", dir.display());
+            }
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            receiver,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Returns the paths that have changed since the last call (and have settled down)
+    pub fn changed_paths(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+
+        for event in self.receiver.try_iter() {
+            match event {
+                Ok(event) => {
+                    if matches!(event.kind, EventKind::Access(_)) {
+                        continue;
+                    }
+
+                    for path in event.paths {
+                        self.pending.insert(path, now);
+                    }
+                }
+                Err(e) => eprintln!("File watcher error: {e}"),
+            }
+        }
+
+        let mut changed = Vec::new();
+        self.pending.retain(|path, last_change| {
+            if now.duration_since(*last_change) >= DEBOUNCE {
+                changed.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        changed
+    }
+}
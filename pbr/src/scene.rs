@@ -1,55 +1,85 @@
-use std::{path::Path, rc::Rc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use eyre::{eyre, Result};
 use glam::{Mat4, Quat, Vec3};
 use gltf::scene::Transform as GTransform;
 
 mod mesh;
+mod procedural;
+mod shared_geometry;
 
-use crate::ogl::texture::GlTexture;
+use crate::{
+    ogl::{sampler::GlSampler, texture::GlTexture, SamplerId},
+    resources::LoadProgress,
+};
 
-pub use self::mesh::{Mesh, Primitive};
+pub use self::{
+    mesh::{Aabb, BoundingSphere, Mesh, MeshData, Primitive, SampledTexture},
+    procedural::ProceduralScene,
+    shared_geometry::SharedGeometry,
+};
 
-/// Image and vertex data of the asset.
+/// Image data of the asset that is needed when uploading the scene to the GPU.
 pub struct DataBundle {
-    /// Vertex data
-    buffers: Vec<gltf::buffer::Data>,
     /// Texture data
     images: Vec<gltf::image::Data>,
     /// To keep track if which textures were already sent to the GPU
     pub gl_textures: Vec<Option<Rc<GlTexture>>>,
+    /// Samplers by the gltf sampler index (None is the default sampler)
+    pub gl_samplers: HashMap<Option<usize>, Rc<GlSampler>>,
+    /// Approximate size of the uploaded buffers and textures in bytes
+    pub memory_size: usize,
+    /// Number of the uploaded primitives, used for indexing them
+    pub num_primitives: usize,
 }
 
 impl DataBundle {
-    fn new(buffers: Vec<gltf::buffer::Data>, images: Vec<gltf::image::Data>) -> Self {
+    fn new(images: Vec<gltf::image::Data>) -> Self {
         Self {
-            buffers,
             gl_textures: vec![Option::None; images.len()],
             images,
+            gl_samplers: HashMap::new(),
+            memory_size: 0,
+            num_primitives: 0,
         }
     }
 }
 
-/// This represents a gltf sacene and contains necessary data for rendering.
-pub struct Scene {
+/// CPU-side data of a scene.
+///
+/// It is loaded on a loader thread and then uploaded to the GPU on the render thread.
+pub struct SceneData {
     /// An artifical root node
-    pub root: Node,
+    pub root: NodeData,
     /// Name of the scene
     pub name: String,
-    /// Model transforms of the whole object
-    pub transform: Mat4,
+    /// Decoded images referenced by the materials
+    pub images: Vec<gltf::image::Data>,
+    /// External files (buffers and images) that the scene was loaded from
+    pub dependencies: Vec<PathBuf>,
 }
 
-impl Scene {
-    /// Load the scene from a path to a gltf file
-    pub fn from_gltf(path: &str) -> Result<Scene> {
+impl SceneData {
+    /// Load the scene data from a path to a gltf file
+    pub fn from_gltf(path: &str, progress: &LoadProgress) -> Result<Self> {
         let (gltf, buffers, images) = gltf::import(path)?;
+        let dependencies = external_files(&gltf, path);
         let name = Path::new(path)
             .file_name()
             .map(|osstr| osstr.to_string_lossy().to_string())
             .unwrap_or_else(|| "N/A".to_string());
 
-        let mut bundle = DataBundle::new(buffers, images);
+        // Parsing the file and decoding the images is the bulk of the work
+        progress.set(0.7);
+
+        for image in &images {
+            mesh::validate_image(image)?;
+        }
 
         if gltf.scenes().len() != 1 {
             return Err(eyre!("GLTF file contains more than 1 scene"));
@@ -59,12 +89,12 @@ impl Scene {
         let mut id = 1;
         let mut nodes = Vec::new();
         for node in scene.nodes() {
-            let node = Node::from_gltf(&node, &mut bundle, &mut id, &scene)?;
+            let node = NodeData::from_gltf(&node, &buffers, &mut id, &scene)?;
             id += 1;
             nodes.push(node);
         }
 
-        let root = Node {
+        let root = NodeData {
             index: usize::MAX,
             name: scene.name().unwrap_or("Root").to_string(),
             children: nodes,
@@ -72,34 +102,137 @@ impl Scene {
             transform: Mat4::IDENTITY,
         };
 
-        Ok(Scene {
+        progress.set(1.);
+
+        Ok(SceneData {
             root,
             name,
-            transform: Mat4::IDENTITY,
+            images,
+            dependencies,
         })
     }
 }
 
-/// A Node represents a subset of a gltf scene
-/// Nodes form a tree hierarchy
-pub struct Node {
+/// Canonical paths of the buffers and images that aren't embedded in the glTF file
+fn external_files(gltf: &gltf::Document, path: &str) -> Vec<PathBuf> {
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    let buffer_uris = gltf.buffers().filter_map(|buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) => Some(uri),
+        gltf::buffer::Source::Bin => None,
+    });
+    let image_uris = gltf.images().filter_map(|image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None,
+    });
+
+    buffer_uris
+        .chain(image_uris)
+        .filter(|uri| !uri.starts_with("data:"))
+        .filter_map(|uri| fs::canonicalize(dir.join(uri)).ok())
+        .collect()
+}
+
+/// This represents a gltf sacene and contains necessary data for rendering.
+pub struct Scene {
+    /// An artifical root node
+    pub root: Node,
+    /// Name of the scene
+    pub name: String,
+    /// Model transforms of the whole object
+    pub transform: Mat4,
+    /// Approximate GPU memory used by the scene in bytes
+    pub memory_size: usize,
+    /// All of the samplers used by the scene
+    samplers: Vec<Rc<GlSampler>>,
+    /// Anisotropy that is currently set on the samplers
+    anisotropy: f32,
+    num_primitives: usize,
+    /// Geometry for the multi-draw path, created when it's first used
+    shared_geometry: Option<SharedGeometry>,
+}
+
+impl Scene {
+    /// Upload the scene to the GPU
+    pub fn from_data(data: SceneData) -> Scene {
+        let mut bundle = DataBundle::new(data.images);
+        let root = Node::from_data(data.root, &mut bundle);
+
+        Scene {
+            root,
+            name: data.name,
+            transform: Mat4::IDENTITY,
+            memory_size: bundle.memory_size,
+            samplers: bundle.gl_samplers.into_values().collect(),
+            anisotropy: 1.,
+            num_primitives: bundle.num_primitives,
+            shared_geometry: None,
+        }
+    }
+
+    /// Copies the geometry of all primitives into shared buffers, only the first call does anything
+    pub fn prepare_shared_geometry(&mut self) {
+        if self.shared_geometry.is_none() {
+            let geometry = SharedGeometry::new(&self.root, self.num_primitives);
+            self.memory_size += geometry.memory_size;
+            self.shared_geometry = Some(geometry);
+        }
+    }
+
+    pub fn shared_geometry(&self) -> Option<&SharedGeometry> {
+        self.shared_geometry.as_ref()
+    }
+
+    /// Sets the anisotropic filtering of all the samplers, the samplers are only updated on change
+    pub fn set_anisotropy(&mut self, anisotropy: f32) {
+        if self.anisotropy == anisotropy {
+            return;
+        }
+
+        // The multi-draw path uses bindless handles, which make the samplers immutable
+        if self.shared_geometry.is_some() {
+            self.recreate_samplers(anisotropy);
+        } else {
+            for sampler in &self.samplers {
+                sampler.set_anisotropy(anisotropy);
+            }
+        }
+
+        self.anisotropy = anisotropy;
+    }
+
+    /// Replaces all of the samplers by new ones, the old samplers and their handles are deleted
+    fn recreate_samplers(&mut self, anisotropy: f32) {
+        let samplers: HashMap<SamplerId, Rc<GlSampler>> = self
+            .samplers
+            .iter()
+            .map(|sampler| (sampler.id, Rc::new(sampler.with_anisotropy(anisotropy))))
+            .collect();
+
+        self.root.replace_samplers(&samplers);
+        self.samplers = samplers.into_values().collect();
+    }
+}
+
+/// CPU-side data of a Node
+pub struct NodeData {
     /// The same index as in the gltf file
     pub index: usize,
     /// Name of the node
     pub name: String,
     /// Children nodes
-    pub children: Vec<Node>,
+    pub children: Vec<NodeData>,
     /// Optional mesh data of the node (can contain multiple primitives)
-    pub mesh: Option<Mesh>,
+    pub mesh: Option<MeshData>,
     /// Transform of the node in the hierarchy
     pub transform: Mat4,
 }
 
-impl Node {
+impl NodeData {
     /// Crate a node from a gltf::Node structure
     fn from_gltf(
         node: &gltf::Node,
-        bundle: &mut DataBundle,
+        buffers: &[gltf::buffer::Data],
         id: &mut u32,
         scene: &gltf::Scene,
     ) -> Result<Self> {
@@ -109,12 +242,12 @@ impl Node {
 
         for child_node in node.children() {
             *id += 1;
-            let node = Node::from_gltf(&child_node, bundle, id, scene)?;
+            let node = NodeData::from_gltf(&child_node, buffers, id, scene)?;
             children.push(node);
         }
 
         let mesh = match node.mesh() {
-            Some(m) => Some(Mesh::from_gltf(&m, bundle)?),
+            Some(m) => Some(MeshData::from_gltf(&m, buffers)?),
             None => None,
         };
 
@@ -145,3 +278,51 @@ impl Node {
         })
     }
 }
+
+/// A Node represents a subset of a gltf scene
+/// Nodes form a tree hierarchy
+pub struct Node {
+    /// The same index as in the gltf file
+    pub index: usize,
+    /// Name of the node
+    pub name: String,
+    /// Children nodes
+    pub children: Vec<Node>,
+    /// Optional mesh data of the node (can contain multiple primitives)
+    pub mesh: Option<Mesh>,
+    /// Transform of the node in the hierarchy
+    pub transform: Mat4,
+}
+
+impl Node {
+    /// Upload the node hierarchy to the GPU
+    fn from_data(data: NodeData, bundle: &mut DataBundle) -> Self {
+        let children = data
+            .children
+            .into_iter()
+            .map(|child| Node::from_data(child, bundle))
+            .collect();
+
+        let mesh = data.mesh.map(|m| Mesh::from_data(m, bundle));
+
+        Self {
+            index: data.index,
+            name: data.name,
+            children,
+            mesh,
+            transform: data.transform,
+        }
+    }
+
+    fn replace_samplers(&mut self, samplers: &HashMap<SamplerId, Rc<GlSampler>>) {
+        if let Some(mesh) = &mut self.mesh {
+            for primitive in &mut mesh.primitives {
+                primitive.replace_samplers(samplers);
+            }
+        }
+
+        for child in &mut self.children {
+            child.replace_samplers(samplers);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{mem::size_of, rc::Rc};
+use std::{cell::Cell, collections::HashMap, mem::size_of, rc::Rc};
 
 use eyre::{eyre, Result};
 use gl::types::GLenum;
@@ -8,78 +8,95 @@ use gltf::{
     texture::{MagFilter, MinFilter, WrappingMode},
 };
 
-use crate::ogl::{gl_buffer::GlBuffer, texture::GlTexture, vao::Vao};
+use crate::ogl::{
+    bindless, gl_buffer::GlBuffer, sampler::GlSampler, texture::GlTexture, vao::Vao, SamplerId,
+};
 
+mod bounds;
 mod material;
 mod tangents;
 mod vertex;
 
-use self::{
+pub use self::{
+    bounds::{Aabb, BoundingSphere},
     material::{Anisotropy, Clearcoat, StdPbrMaterial},
     vertex::Vertex,
 };
 
 use super::DataBundle;
 
-/// Gltf terminology is needlessly confusing.
-/// A gltf 'Mesh' contains multiple real sub-meshes (called Primitives in the gltf parlance)
-pub struct Mesh {
-    /// 'Primitives' of the 'mesh'
-    pub primitives: Vec<Primitive>,
-    /// Name of the 'Mesh'
+/// CPU-side data of a Mesh
+pub struct MeshData {
+    pub primitives: Vec<PrimitiveData>,
     pub name: Option<String>,
 }
 
-impl Mesh {
-    /// Create a mesh from the gltf::Mesh struct and the DataBundle
-    pub fn from_gltf(mesh: &gltf::Mesh, bundle: &mut DataBundle) -> Result<Self> {
+impl MeshData {
+    /// Load the mesh data from the gltf::Mesh struct and the vertex buffers
+    pub fn from_gltf(mesh: &gltf::Mesh, buffers: &[gltf::buffer::Data]) -> Result<Self> {
         let name = mesh.name().map(|n| n.to_owned());
 
         let mut primitives = Vec::new();
         for primitive in mesh.primitives() {
-            let primitive = Primitive::from_gltf(&primitive, bundle)?;
+            let primitive = PrimitiveData::from_gltf(&primitive, buffers)?;
             primitives.push(primitive);
         }
 
-        Ok(Mesh { primitives, name })
+        Ok(MeshData { primitives, name })
     }
 }
 
-/// A Primitive represents a single 'mesh' in the normal meaning of that word
-/// (a collection of vertices with a specific topology like Triangles or Lines).
-pub struct Primitive {
-    /// OpenGL VAO identifier
-    pub vao: Vao,
+/// Gltf terminology is needlessly confusing.
+/// A gltf 'Mesh' contains multiple real sub-meshes (called Primitives in the gltf parlance)
+pub struct Mesh {
+    /// 'Primitives' of the 'mesh'
+    pub primitives: Vec<Primitive>,
+    /// Name of the 'Mesh'
+    pub name: Option<String>,
+}
 
-    pub vertex_buffer: GlBuffer,
+impl Mesh {
+    /// Upload the mesh data to the GPU
+    pub fn from_data(mesh: MeshData, bundle: &mut DataBundle) -> Self {
+        let primitives = mesh
+            .primitives
+            .into_iter()
+            .map(|p| Primitive::from_data(p, bundle))
+            .collect();
+
+        Mesh {
+            primitives,
+            name: mesh.name,
+        }
+    }
+}
 
-    pub index_buffer: GlBuffer,
-    pub num_indices: usize,
-    pub indices_type: GLenum,
+/// CPU-side data of a Primitive, textures are only referenced by their image index
+pub struct PrimitiveData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
 
-    pub pbr_material: StdPbrMaterial,
-    pub clearcoat: Option<Clearcoat>,
+    pub pbr_material: StdPbrMaterial<TextureRef>,
+    pub clearcoat: Option<Clearcoat<TextureRef>>,
     pub anisotropy: Option<Anisotropy>,
 }
 
-impl Primitive {
-    /// Creates the primitive from the gltf::Primitive struct and the DataBundle
-    pub fn from_gltf(primitive: &gltf::Primitive, bundle: &mut DataBundle) -> Result<Self> {
+impl PrimitiveData {
+    /// Loads the primitive from the gltf::Primitive struct and the vertex buffers
+    pub fn from_gltf(primitive: &gltf::Primitive, buffers: &[gltf::buffer::Data]) -> Result<Self> {
         let mode = primitive.mode();
         if mode != gltf::mesh::Mode::Triangles {
             return Err(eyre!("primitive mode: '{mode:?}' is not impelemnted"));
         }
 
-        let mut vertex_buf = Self::load_vertex_atrrib_buf(primitive, bundle)?;
-        let index_buf = Self::load_indices_buf(primitive, bundle)?;
+        let mut vertices = Self::load_vertex_atrrib_buf(primitive, buffers)?;
+        let indices = Self::load_indices_buf(primitive, buffers)?;
 
-        let index_buffer = GlBuffer::new(&index_buf);
-
-        let pbr_material = StdPbrMaterial::from_gtlf(&primitive.material(), bundle);
+        let pbr_material = StdPbrMaterial::from_gtlf(&primitive.material());
         let clearcoat = primitive
             .material()
             .clearcoat()
-            .and_then(|cc| Clearcoat::from_gltf(&cc, bundle));
+            .and_then(|cc| Clearcoat::from_gltf(&cc));
 
         // Placeholder until anisotropy extension is stabilized
         let anisotropy = Some(Anisotropy::new());
@@ -89,52 +106,25 @@ impl Primitive {
                 &pbr_material,
                 &clearcoat,
                 &anisotropy,
-                &mut vertex_buf,
-                &index_buf,
+                &mut vertices,
+                &indices,
             );
         }
 
-        let vertex_buffer = GlBuffer::new(&vertex_buf);
-        let vao = Self::create_vao(&vertex_buffer, &index_buffer);
-
-        let prim = Self {
-            vao,
-            vertex_buffer,
-            index_buffer,
-            num_indices: index_buf.len(),
-            // The type is fixed for now, maybe I'll revert it back to a flexible type in the future
-            indices_type: gl::UNSIGNED_INT,
+        Ok(Self {
+            vertices,
+            indices,
             pbr_material,
             clearcoat,
             anisotropy,
-        };
-
-        Ok(prim)
-    }
-
-    /// Creates OpenGL buffers from the loaded vertex data
-    fn create_vao(vertex_buffer: &GlBuffer, index_buffer: &GlBuffer) -> Vao {
-        let vao = Vao::new();
-
-        vao.attach_index_buffer(index_buffer);
-        vao.attach_vertex_buf_multiple_attribs(
-            vertex_buffer,
-            &Vertex::ATTRIB_SIZES,
-            &Vertex::ATTRIB_INDICES,
-            &Vertex::ATTRIB_TYPES,
-            size_of::<Vertex>(),
-            &Vertex::ATTRIB_OFFSETS,
-        );
-
-        vao
+        })
     }
 
     fn load_vertex_atrrib_buf(
         primitive: &gltf::Primitive,
-        bundle: &DataBundle,
+        buffers: &[gltf::buffer::Data],
     ) -> Result<Vec<Vertex>> {
-        let reader = primitive.reader(|buffer| Some(&bundle.buffers[buffer.index()]));
-
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
         let position_iter = reader
             .read_positions()
             .ok_or(eyre!("primitive doesn't containt positions"))?;
@@ -191,8 +181,11 @@ impl Primitive {
         Ok(buf)
     }
 
-    fn load_indices_buf(primitive: &gltf::Primitive, bundle: &DataBundle) -> Result<Vec<u32>> {
-        let reader = primitive.reader(|buffer| Some(&bundle.buffers[buffer.index()]));
+    fn load_indices_buf(
+        primitive: &gltf::Primitive,
+        buffers: &[gltf::buffer::Data],
+    ) -> Result<Vec<u32>> {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
         let indices: Vec<u32> = match reader
             .read_indices()
@@ -207,8 +200,8 @@ impl Primitive {
     }
 
     fn check_calculate_tangents(
-        pbr_material: &StdPbrMaterial,
-        clearcoat: &Option<Clearcoat>,
+        pbr_material: &StdPbrMaterial<TextureRef>,
+        clearcoat: &Option<Clearcoat<TextureRef>>,
         anisotropy: &Option<Anisotropy>,
         vertex_buf: &mut Vec<Vertex>,
         index_buf: &[u32],
@@ -226,37 +219,256 @@ impl Primitive {
     }
 }
 
+/// A Primitive represents a single 'mesh' in the normal meaning of that word
+/// (a collection of vertices with a specific topology like Triangles or Lines).
+pub struct Primitive {
+    /// OpenGL VAO identifier
+    pub vao: Vao,
+
+    pub vertex_buffer: GlBuffer,
+
+    pub index_buffer: GlBuffer,
+    pub num_indices: usize,
+    pub num_vertices: usize,
+    pub indices_type: GLenum,
+
+    pub pbr_material: StdPbrMaterial,
+    pub clearcoat: Option<Clearcoat>,
+    pub anisotropy: Option<Anisotropy>,
+
+    /// Bounds of the vertex positions
+    pub aabb: Aabb,
+    /// Index of the primitive in the scene
+    pub index: usize,
+}
+
+impl Primitive {
+    /// Creates the GL buffers and textures of the primitive
+    pub fn from_data(data: PrimitiveData, bundle: &mut DataBundle) -> Self {
+        let index_buffer = GlBuffer::new(&data.indices);
+        let vertex_buffer = GlBuffer::new(&data.vertices);
+        bundle.memory_size +=
+            data.indices.len() * size_of::<u32>() + data.vertices.len() * size_of::<Vertex>();
+        let vao = Self::create_vao(&vertex_buffer, &index_buffer);
+        let aabb = Aabb::from_vertices(&data.vertices);
+        let index = bundle.num_primitives;
+        bundle.num_primitives += 1;
+
+        let pbr_material = data
+            .pbr_material
+            .map_textures(|tex| SampledTexture::new(&tex, bundle));
+        let clearcoat = data
+            .clearcoat
+            .map(|cc| cc.map_textures(|tex| SampledTexture::new(&tex, bundle)));
+
+        Self {
+            vao,
+            vertex_buffer,
+            index_buffer,
+            num_indices: data.indices.len(),
+            num_vertices: data.vertices.len(),
+            // The type is fixed for now, maybe I'll revert it back to a flexible type in the future
+            indices_type: gl::UNSIGNED_INT,
+            pbr_material,
+            clearcoat,
+            anisotropy: data.anisotropy,
+            aabb,
+            index,
+        }
+    }
+
+    /// Replaces the samplers of the textures, `samplers` maps the ids of the old samplers to the new ones
+    pub(super) fn replace_samplers(&mut self, samplers: &HashMap<SamplerId, Rc<GlSampler>>) {
+        let pbr = &mut self.pbr_material;
+        let pbr_textures = [
+            &mut pbr.base_color_texture,
+            &mut pbr.mr_texture,
+            &mut pbr.normal_texture,
+            &mut pbr.occlusion_texture,
+            &mut pbr.emissive_texture,
+        ];
+        let cc_textures = self.clearcoat.iter_mut().flat_map(|cc| {
+            [
+                &mut cc.intensity_texture,
+                &mut cc.roughness_texture,
+                &mut cc.normal_texture,
+            ]
+        });
+
+        for tex in pbr_textures.into_iter().chain(cc_textures).flatten() {
+            if let Some(sampler) = samplers.get(&tex.sampler.id) {
+                tex.set_sampler(Rc::clone(sampler));
+            }
+        }
+    }
+
+    /// Creates OpenGL buffers from the loaded vertex data
+    pub(super) fn create_vao(vertex_buffer: &GlBuffer, index_buffer: &GlBuffer) -> Vao {
+        let vao = Vao::new();
+
+        vao.attach_index_buffer(index_buffer);
+        vao.attach_vertex_buf_multiple_attribs(
+            vertex_buffer,
+            &Vertex::ATTRIB_SIZES,
+            &Vertex::ATTRIB_INDICES,
+            &Vertex::ATTRIB_TYPES,
+            size_of::<Vertex>(),
+            &Vertex::ATTRIB_OFFSETS,
+        );
+
+        vao
+    }
+}
+
+/// Reference to a texture of a material that hasn't been uploaded yet
+pub struct TextureRef {
+    /// Index of the image in the DataBundle
+    pub image_index: usize,
+    /// Index of the gltf sampler, None for the default sampler
+    pub sampler_index: Option<usize>,
+    pub sampler: SamplerParams,
+}
+
+impl TextureRef {
+    pub fn from_gltf(tex: &gltf::Texture) -> Self {
+        let sampler = tex.sampler();
+
+        Self {
+            image_index: tex.source().index(),
+            sampler_index: sampler.index(),
+            sampler: SamplerParams::from_gltf(&sampler),
+        }
+    }
+}
+
+/// An uploaded texture image together with the sampler it's used with.
+///
+/// Both images and samplers can be shared by multiple gltf textures.
+pub struct SampledTexture {
+    pub texture: Rc<GlTexture>,
+    pub sampler: Rc<GlSampler>,
+    /// Bindless handle, created when it's first needed
+    handle: Cell<Option<u64>>,
+}
+
+impl SampledTexture {
+    fn new(tex: &TextureRef, bundle: &mut DataBundle) -> Self {
+        Self {
+            texture: create_texture(tex, bundle),
+            sampler: create_sampler(tex, bundle),
+            handle: Cell::new(None),
+        }
+    }
+
+    /// Resident bindless handle of the texture, None if bindless textures aren't supported
+    pub fn bindless_handle(&self) -> Option<u64> {
+        if self.handle.get().is_none() {
+            self.handle.set(bindless::texture_sampler_handle(
+                self.texture.id,
+                self.sampler.id,
+            ));
+        }
+
+        self.handle.get()
+    }
+
+    /// The bindless handle is created again with the new sampler when it's needed
+    fn set_sampler(&mut self, sampler: Rc<GlSampler>) {
+        self.sampler = sampler;
+        self.handle.set(None);
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindTextureUnit(unit, self.texture.id);
+        }
+
+        self.sampler.bind(unit);
+    }
+}
+
+/// OpenGL sampler parameters of a gltf texture
+pub struct SamplerParams {
+    pub min_filter: GLenum,
+    pub mag_filter: GLenum,
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+}
+
+impl SamplerParams {
+    fn from_gltf(sampler: &gltf::texture::Sampler) -> Self {
+        let min_filter = match sampler.min_filter() {
+            Some(min_filter) => match min_filter {
+                MinFilter::Nearest => gl::NEAREST,
+                MinFilter::Linear => gl::LINEAR,
+                MinFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+                MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+                MinFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+                MinFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+            },
+            None => gl::LINEAR_MIPMAP_LINEAR,
+        };
+
+        let mag_filter = match sampler.mag_filter() {
+            Some(mag_filter) => match mag_filter {
+                MagFilter::Nearest => gl::NEAREST,
+                MagFilter::Linear => gl::LINEAR,
+            },
+            None => gl::LINEAR,
+        };
+
+        let wrap_s = match sampler.wrap_s() {
+            WrappingMode::ClampToEdge => gl::CLAMP_TO_EDGE,
+            WrappingMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+            WrappingMode::Repeat => gl::REPEAT,
+        };
+
+        let wrap_t = match sampler.wrap_t() {
+            WrappingMode::ClampToEdge => gl::CLAMP_TO_EDGE,
+            WrappingMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+            WrappingMode::Repeat => gl::REPEAT,
+        };
+
+        Self {
+            min_filter,
+            mag_filter,
+            wrap_s,
+            wrap_t,
+        }
+    }
+}
+
 /// Creates a new OpenGL texture.
 ///
 /// If the texture already exists (bundle.gl_textures\[texture_index\] == Some(...)),
 /// no new texture is created, only the Texture struct is cloned.
-fn create_texture(tex: &gltf::Texture, bundle: &mut DataBundle) -> Rc<GlTexture> {
-    let tex_index = tex.source().index();
+/// The sampling parameters are stored separately in sampler objects.
+fn create_texture(tex: &TextureRef, bundle: &mut DataBundle) -> Rc<GlTexture> {
+    let tex_index = tex.image_index;
     if let Some(texture) = &bundle.gl_textures[tex_index] {
         return texture.clone();
     }
 
     let gl_tex = GlTexture::new(gl::TEXTURE_2D);
-    set_texture_sampler(gl_tex.id, &tex.sampler());
-
     let image = &bundle.images[tex_index];
 
-    assert!(image.width.is_power_of_two());
-    assert!(image.height.is_power_of_two());
-
-    let (internal_format, format) = match image.format {
-        Format::R8 => (gl::R8, gl::RED),
-        Format::R8G8 => (gl::RG8, gl::RG),
-        Format::R8G8B8 => (gl::RGB8, gl::RGB),
-        Format::R8G8B8A8 => (gl::RGBA8, gl::RGBA),
-        f => unimplemented!("Unimplemented image format: '{f:?}'"),
-    };
+    let (internal_format, format) =
+        image_formats(image.format).expect("The images are validated on the loader thread");
 
     let w = image.width as i32;
     let h = image.height as i32;
 
     let levels = 1 + f32::floor(f32::log2(i32::max(w, h) as f32)) as i32;
 
+    let pixel_size = match internal_format {
+        gl::R8 => 1,
+        gl::RG8 => 2,
+        gl::RGB8 => 3,
+        _ => 4,
+    };
+    // The whole mip chain adds about a third of the base level
+    bundle.memory_size += (w * h) as usize * pixel_size * 4 / 3;
+
     unsafe {
         gl::TextureStorage2D(gl_tex.id, levels, internal_format, w, h);
         gl::TextureSubImage2D(
@@ -280,47 +492,48 @@ fn create_texture(tex: &gltf::Texture, bundle: &mut DataBundle) -> Rc<GlTexture>
     rc_tex
 }
 
-/// Sets the appropriate sampler functions for the currently created texture.
-fn set_texture_sampler(texture: u32, sampler: &gltf::texture::Sampler) {
-    let min_filter = match sampler.min_filter() {
-        Some(min_filter) => match min_filter {
-            MinFilter::Nearest => gl::NEAREST,
-            MinFilter::Linear => gl::LINEAR,
-            MinFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
-            MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
-            MinFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
-            MinFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
-        },
-        None => gl::LINEAR_MIPMAP_LINEAR,
-    };
-
-    let mag_filter = match sampler.mag_filter() {
-        Some(mag_filter) => match mag_filter {
-            MagFilter::Nearest => gl::NEAREST,
-            MagFilter::Linear => gl::LINEAR,
-        },
-        None => gl::LINEAR,
-    };
-
-    unsafe {
-        gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, min_filter as i32);
-        gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
+/// Checks that the image can be uploaded into a texture, it's done on the loader thread
+pub fn validate_image(image: &gltf::image::Data) -> Result<()> {
+    if !image.width.is_power_of_two() || !image.height.is_power_of_two() {
+        return Err(eyre!(
+            "Image dimensions aren't a power of two: {}x{}",
+            image.width,
+            image.height
+        ));
     }
 
-    let wrap_s = match sampler.wrap_s() {
-        WrappingMode::ClampToEdge => gl::CLAMP_TO_EDGE,
-        WrappingMode::MirroredRepeat => gl::MIRRORED_REPEAT,
-        WrappingMode::Repeat => gl::REPEAT,
-    };
+    image_formats(image.format).map(|_| ())
+}
 
-    let wrap_t = match sampler.wrap_t() {
-        WrappingMode::ClampToEdge => gl::CLAMP_TO_EDGE,
-        WrappingMode::MirroredRepeat => gl::MIRRORED_REPEAT,
-        WrappingMode::Repeat => gl::REPEAT,
-    };
+/// Internal format and pixel format of the texture of the image
+fn image_formats(format: Format) -> Result<(GLenum, GLenum)> {
+    match format {
+        Format::R8 => Ok((gl::R8, gl::RED)),
+        Format::R8G8 => Ok((gl::RG8, gl::RG)),
+        Format::R8G8B8 => Ok((gl::RGB8, gl::RGB)),
+        Format::R8G8B8A8 => Ok((gl::RGBA8, gl::RGBA)),
+        f => Err(eyre!("Unimplemented image format: '{f:?}'")),
+    }
+}
 
-    unsafe {
-        gl::TextureParameteri(texture, gl::TEXTURE_WRAP_S, wrap_s as i32);
-        gl::TextureParameteri(texture, gl::TEXTURE_WRAP_T, wrap_t as i32);
+/// Creates a new OpenGL sampler object.
+///
+/// Samplers are cached by the gltf sampler index, same as textures are cached by the image index.
+fn create_sampler(tex: &TextureRef, bundle: &mut DataBundle) -> Rc<GlSampler> {
+    if let Some(sampler) = bundle.gl_samplers.get(&tex.sampler_index) {
+        return sampler.clone();
     }
+
+    let params = &tex.sampler;
+    let sampler = Rc::new(GlSampler::new(
+        params.min_filter,
+        params.mag_filter,
+        params.wrap_s,
+        params.wrap_t,
+    ));
+
+    bundle
+        .gl_samplers
+        .insert(tex.sampler_index, sampler.clone());
+    sampler
 }
@@ -0,0 +1,71 @@
+use glam::{Mat4, Vec3};
+
+use super::Vertex;
+
+/// Axis-aligned bounding box, in the local space of the primitive unless it's transformed
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        // Primitives without vertices are collapsed into the origin
+        if vertices.is_empty() {
+            return Self {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+            };
+        }
+
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| {
+                let pos = Vec3::from(v.pos);
+                (min.min(pos), max.max(pos))
+            },
+        );
+
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        0.5 * (self.max - self.min)
+    }
+
+    /// The smallest AABB that contains the transformed box.
+    ///
+    /// Based on: Arvo, “Transforming Axis-Aligned Bounding Boxes.” Graphics Gems.
+    pub fn transformed(&self, transform: Mat4) -> Self {
+        let center = transform.transform_point3(self.center());
+        let half_extents = self.half_extents();
+
+        let abs_x = transform.x_axis.truncate().abs();
+        let abs_y = transform.y_axis.truncate().abs();
+        let abs_z = transform.z_axis.truncate().abs();
+        let extents = abs_x * half_extents.x + abs_y * half_extents.y + abs_z * half_extents.z;
+
+        Self {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere {
+            center: self.center(),
+            radius: self.half_extents().length(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
@@ -1,42 +1,43 @@
-use std::rc::Rc;
-
-use crate::{ogl::texture::GlTexture, scene::DataBundle};
-
-use super::create_texture;
+use super::{SampledTexture, TextureRef};
 
 /// Standard PBR material parameters
-pub struct StdPbrMaterial {
-    pub base_color_texture: Option<Rc<GlTexture>>,
+///
+/// The textures are `TextureRef`s while the material is being loaded and GL textures with samplers after upload.
+pub struct StdPbrMaterial<T = SampledTexture> {
+    pub base_color_texture: Option<T>,
     pub base_color_factor: [f32; 4],
 
-    pub mr_texture: Option<Rc<GlTexture>>,
+    pub mr_texture: Option<T>,
     pub metallic_factor: f32,
     pub roughness_factor: f32,
 
-    pub normal_texture: Option<Rc<GlTexture>>,
+    pub normal_texture: Option<T>,
     pub normal_scale: f32,
 
-    pub occlusion_texture: Option<Rc<GlTexture>>,
+    pub occlusion_texture: Option<T>,
     pub occlusion_strength: f32,
 
-    pub emissive_texture: Option<Rc<GlTexture>>,
+    pub emissive_texture: Option<T>,
     pub emissive_factor: [f32; 3],
+
+    /// The glTF alpha mode is BLEND
+    pub blend: bool,
 }
 
-impl StdPbrMaterial {
-    pub fn from_gtlf(material: &gltf::Material, bundle: &mut DataBundle) -> Self {
+impl StdPbrMaterial<TextureRef> {
+    pub fn from_gtlf(material: &gltf::Material) -> Self {
         let pbr = material.pbr_metallic_roughness();
 
         let base_color_factor = pbr.base_color_factor();
         let base_color_texture = pbr
             .base_color_texture()
-            .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+            .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
 
         let metallic_factor = pbr.metallic_factor();
         let roughness_factor = pbr.roughness_factor();
         let mr_texture = pbr
             .metallic_roughness_texture()
-            .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+            .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
 
         let normal_scale = material
             .normal_texture()
@@ -45,7 +46,7 @@ impl StdPbrMaterial {
 
         let normal_texture = material
             .normal_texture()
-            .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+            .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
 
         let occlusion_strength = material
             .occlusion_texture()
@@ -54,12 +55,14 @@ impl StdPbrMaterial {
 
         let occlusion_texture = material
             .occlusion_texture()
-            .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+            .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
 
         let emissive_factor = material.emissive_factor();
         let emissive_texture = material
             .emissive_texture()
-            .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+            .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
+
+        let blend = material.alpha_mode() == gltf::material::AlphaMode::Blend;
 
         Self {
             base_color_texture,
@@ -73,35 +76,78 @@ impl StdPbrMaterial {
             occlusion_strength,
             emissive_texture,
             emissive_factor,
+            blend,
+        }
+    }
+
+    /// Material without textures, used by the procedural scenes
+    pub fn from_factors(
+        base_color_factor: [f32; 4],
+        metallic_factor: f32,
+        roughness_factor: f32,
+    ) -> Self {
+        Self {
+            base_color_texture: None,
+            base_color_factor,
+            mr_texture: None,
+            metallic_factor,
+            roughness_factor,
+            normal_texture: None,
+            normal_scale: 1.,
+            occlusion_texture: None,
+            occlusion_strength: 1.,
+            emissive_texture: None,
+            emissive_factor: [0.; 3],
+            blend: false,
+        }
+    }
+}
+
+impl<T> StdPbrMaterial<T> {
+    /// Converts the texture handles, used when uploading the material
+    pub fn map_textures<U>(self, mut f: impl FnMut(T) -> U) -> StdPbrMaterial<U> {
+        StdPbrMaterial {
+            base_color_texture: self.base_color_texture.map(&mut f),
+            base_color_factor: self.base_color_factor,
+            mr_texture: self.mr_texture.map(&mut f),
+            metallic_factor: self.metallic_factor,
+            roughness_factor: self.roughness_factor,
+            normal_texture: self.normal_texture.map(&mut f),
+            normal_scale: self.normal_scale,
+            occlusion_texture: self.occlusion_texture.map(&mut f),
+            occlusion_strength: self.occlusion_strength,
+            emissive_texture: self.emissive_texture.map(&mut f),
+            emissive_factor: self.emissive_factor,
+            blend: self.blend,
         }
     }
 }
 
 /// Clearcoat extension parameters
-pub struct Clearcoat {
+pub struct Clearcoat<T = SampledTexture> {
     pub intensity_factor: f32,
-    pub intensity_texture: Option<Rc<GlTexture>>,
+    pub intensity_texture: Option<T>,
 
     pub roughness_factor: f32,
-    pub roughness_texture: Option<Rc<GlTexture>>,
+    pub roughness_texture: Option<T>,
 
-    pub normal_texture: Option<Rc<GlTexture>>,
+    pub normal_texture: Option<T>,
     pub normal_scale: f32,
 }
 
-impl Clearcoat {
-    pub fn from_gltf(cc: &gltf::material::Clearcoat, bundle: &mut DataBundle) -> Option<Self> {
+impl Clearcoat<TextureRef> {
+    pub fn from_gltf(cc: &gltf::material::Clearcoat) -> Option<Self> {
         let intensity_factor = cc.clearcoat_factor();
         // The clearcoat layer is disabled if clearcoat == 0.0
         if intensity_factor != 0. {
             let intensity_texture = cc
                 .clearcoat_texture()
-                .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+                .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
 
             let roughness_factor = cc.clearcoat_roughness_factor();
             let roughness_texture = cc
                 .clearcoat_roughness_texture()
-                .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+                .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
 
             let normal_scale = cc
                 .clearcoat_normal_texture()
@@ -110,7 +156,7 @@ impl Clearcoat {
 
             let normal_texture = cc
                 .clearcoat_normal_texture()
-                .map(|tex_info| create_texture(&tex_info.texture(), bundle));
+                .map(|tex_info| TextureRef::from_gltf(&tex_info.texture()));
 
             return Some(Self {
                 intensity_factor,
@@ -126,6 +172,20 @@ impl Clearcoat {
     }
 }
 
+impl<T> Clearcoat<T> {
+    /// Converts the texture handles, used when uploading the material
+    pub fn map_textures<U>(self, mut f: impl FnMut(T) -> U) -> Clearcoat<U> {
+        Clearcoat {
+            intensity_factor: self.intensity_factor,
+            intensity_texture: self.intensity_texture.map(&mut f),
+            roughness_factor: self.roughness_factor,
+            roughness_texture: self.roughness_texture.map(&mut f),
+            normal_texture: self.normal_texture.map(&mut f),
+            normal_scale: self.normal_scale,
+        }
+    }
+}
+
 /// Simple anisotropy parameter.
 /// This is a placeholder until real anisotropy extension is stabilized in gltf 2.0.
 pub struct Anisotropy {
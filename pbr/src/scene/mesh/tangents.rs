@@ -1,8 +1,8 @@
 use glam::{Vec2, Vec3};
 
-use super::{Primitive, Vertex};
+use super::{PrimitiveData, Vertex};
 
-impl Primitive {
+impl PrimitiveData {
     /// Inspired by http://foundationsofgameenginedev.com/FGED2-sample.pdf
     pub(super) fn calculate_tangents(vertex_buf: &mut Vec<Vertex>, index_buf: &[u32]) {
         // Tangents are already stored in the vertex buffer
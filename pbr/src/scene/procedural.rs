@@ -0,0 +1,639 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use eyre::{eyre, Result};
+use glam::{Mat4, Quat, Vec2, Vec3};
+
+use super::{
+    mesh::{Anisotropy, MeshData, PrimitiveData, StdPbrMaterial, TextureRef, Vertex},
+    NodeData, SceneData,
+};
+
+/// Scenes that are generated instead of being loaded from gltf files.
+///
+/// Useful for comparing BRDFs on simple shapes.
+#[derive(Clone, Copy)]
+pub enum ProceduralScene {
+    UvSphere,
+    IcoSphere,
+    Plane,
+    Cube,
+    TorusKnot,
+    ShaderBall,
+    MaterialGrid,
+}
+
+impl ProceduralScene {
+    pub const ALL: [Self; 7] = [
+        Self::UvSphere,
+        Self::IcoSphere,
+        Self::Plane,
+        Self::Cube,
+        Self::TorusKnot,
+        Self::ShaderBall,
+        Self::MaterialGrid,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ProceduralScene::UvSphere => "UV sphere",
+            ProceduralScene::IcoSphere => "Ico sphere",
+            ProceduralScene::Plane => "Plane",
+            ProceduralScene::Cube => "Cube",
+            ProceduralScene::TorusKnot => "Torus knot",
+            ProceduralScene::ShaderBall => "Shader ball",
+            ProceduralScene::MaterialGrid => "Roughness x metallic grid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|s| s.to_str() == name)
+            .ok_or(eyre!("Unknown procedural scene '{name}'"))
+    }
+
+    pub fn generate(self) -> SceneData {
+        let default_material = || StdPbrMaterial::from_factors([1.; 4], 0., 0.5);
+
+        let nodes = match self {
+            ProceduralScene::UvSphere => {
+                vec![mesh_node(
+                    uv_sphere(64, 32),
+                    default_material(),
+                    Mat4::IDENTITY,
+                )]
+            }
+            ProceduralScene::IcoSphere => {
+                vec![mesh_node(ico_sphere(4), default_material(), Mat4::IDENTITY)]
+            }
+            ProceduralScene::Plane => {
+                vec![mesh_node(plane(), default_material(), Mat4::IDENTITY)]
+            }
+            ProceduralScene::Cube => {
+                vec![mesh_node(cube(), default_material(), Mat4::IDENTITY)]
+            }
+            ProceduralScene::TorusKnot => {
+                vec![mesh_node(
+                    torus_knot(256, 32),
+                    default_material(),
+                    Mat4::IDENTITY,
+                )]
+            }
+            ProceduralScene::ShaderBall => {
+                let pedestal_material = StdPbrMaterial::from_factors([0.1, 0.1, 0.1, 1.], 0., 0.6);
+                // Center the whole object around the origin
+                let transform = Mat4::from_translation(Vec3::new(0., -1.2, 0.));
+
+                vec![
+                    mesh_node(shader_ball(128), default_material(), transform),
+                    mesh_node(pedestal(128), pedestal_material, transform),
+                ]
+            }
+            ProceduralScene::MaterialGrid => material_grid(),
+        };
+
+        let children = nodes
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut node)| {
+                node.index = index;
+                node
+            })
+            .collect();
+
+        let root = NodeData {
+            index: usize::MAX,
+            name: "Root".to_string(),
+            children,
+            mesh: None,
+            transform: Mat4::IDENTITY,
+        };
+
+        SceneData {
+            root,
+            name: self.to_str().to_string(),
+            images: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+/// Spheres with roughness increasing along the X axis and metalness increasing along the Y axis
+fn material_grid() -> Vec<NodeData> {
+    const COUNT: u32 = 7;
+    const RADIUS: f32 = 0.12;
+    const SPACING: f32 = 0.3;
+
+    let mut nodes = Vec::new();
+    let offset = (COUNT - 1) as f32 * SPACING / 2.;
+
+    for metallic_i in 0..COUNT {
+        for roughness_i in 0..COUNT {
+            let metallic = metallic_i as f32 / (COUNT - 1) as f32;
+            let roughness = roughness_i as f32 / (COUNT - 1) as f32;
+
+            let material = StdPbrMaterial::from_factors([1.; 4], metallic, roughness);
+            let translation = Vec3::new(
+                roughness_i as f32 * SPACING - offset,
+                metallic_i as f32 * SPACING - offset,
+                0.,
+            );
+            let transform = Mat4::from_scale_rotation_translation(
+                Vec3::splat(RADIUS),
+                Quat::IDENTITY,
+                translation,
+            );
+
+            let mut node = mesh_node(uv_sphere(32, 16), material, transform);
+            node.name = format!("Metallic {metallic:.2}, roughness {roughness:.2}");
+            nodes.push(node);
+        }
+    }
+
+    nodes
+}
+
+fn mesh_node(
+    geometry: Geometry,
+    material: StdPbrMaterial<TextureRef>,
+    transform: Mat4,
+) -> NodeData {
+    let primitive = PrimitiveData {
+        vertices: geometry.vertices,
+        indices: geometry.indices,
+        pbr_material: material,
+        clearcoat: None,
+        // Same placeholder as for the gltf scenes
+        anisotropy: Some(Anisotropy::new()),
+    };
+
+    NodeData {
+        index: 0,
+        name: "Mesh".to_string(),
+        children: Vec::new(),
+        mesh: Some(MeshData {
+            primitives: vec![primitive],
+            name: None,
+        }),
+        transform,
+    }
+}
+
+/// A point on a parametric surface with the partial derivatives, so the tangent frame can be computed
+struct SurfacePoint {
+    pos: Vec3,
+    normal: Vec3,
+    /// Direction in which the U texture coordinate increases
+    dpdu: Vec3,
+    /// Direction in which the V texture coordinate increases
+    dpdv: Vec3,
+    uv: Vec2,
+}
+
+impl SurfacePoint {
+    fn to_vertex(&self) -> Vertex {
+        let n = self.normal.normalize();
+
+        let mut t = (self.dpdu - n * n.dot(self.dpdu)).normalize();
+        if !t.is_finite() {
+            t = n.any_orthonormal_vector();
+        }
+
+        // The same convention as in the tangent calculation of the gltf scenes
+        let handedness = if n.cross(t).dot(self.dpdv) >= 0. {
+            1.
+        } else {
+            -1.
+        };
+
+        Vertex {
+            pos: self.pos.to_array(),
+            normal: n.to_array(),
+            texcoords: self.uv.to_array(),
+            tangent: [t.x, t.y, t.z, handedness],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Geometry {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl Geometry {
+    /// Samples a surface on a grid of (cols + 1) x (rows + 1) vertices
+    fn grid(cols: u32, rows: u32, point: impl Fn(u32, u32) -> SurfacePoint) -> Self {
+        let mut vertices = Vec::with_capacity(((cols + 1) * (rows + 1)) as usize);
+        for row in 0..=rows {
+            for col in 0..=cols {
+                vertices.push(point(col, row).to_vertex());
+            }
+        }
+
+        let mut indices = Vec::with_capacity((cols * rows * 6) as usize);
+        for row in 0..rows {
+            for col in 0..cols {
+                // a b
+                // c d
+                let a = row * (cols + 1) + col;
+                let b = a + 1;
+                let c = a + cols + 1;
+                let d = c + 1;
+
+                let pos = |i: u32| Vec3::from(vertices[i as usize].pos);
+                let normal = |i: u32| Vec3::from(vertices[i as usize].normal);
+
+                // The cross product of the diagonals doesn't degenerate at the poles
+                let face_normal = (pos(d) - pos(a)).cross(pos(c) - pos(b));
+                let vertex_normal = normal(a) + normal(b) + normal(c) + normal(d);
+
+                // Front faces are counter-clockwise
+                if face_normal.dot(vertex_normal) >= 0. {
+                    indices.extend_from_slice(&[a, b, d, a, d, c]);
+                } else {
+                    indices.extend_from_slice(&[a, d, b, a, c, d]);
+                }
+            }
+        }
+
+        Self { vertices, indices }
+    }
+
+    fn append(&mut self, other: Geometry) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|i| i + offset));
+    }
+
+    /// The transform must only contain rotations and translations
+    fn transform(mut self, transform: Mat4) -> Self {
+        for v in &mut self.vertices {
+            v.pos = transform.transform_point3(v.pos.into()).to_array();
+            v.normal = transform.transform_vector3(v.normal.into()).to_array();
+
+            let t = transform.transform_vector3(Vec3::from_slice(&v.tangent[0..3]));
+            v.tangent[0..3].copy_from_slice(&t.to_array());
+        }
+
+        self
+    }
+}
+
+/// A point on the unit sphere, the seam is at u = 0 and the poles at v = 0 and v = 1
+fn sphere_point(u: f32, v: f32) -> SurfacePoint {
+    let phi = u * 2. * PI;
+    let theta = v * PI;
+
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let (sin_theta, cos_theta) = theta.sin_cos();
+
+    let pos = Vec3::new(-cos_phi * sin_theta, cos_theta, sin_phi * sin_theta);
+
+    SurfacePoint {
+        pos,
+        normal: pos,
+        // Derivatives without the sin(theta) factor, so they don't vanish at the poles
+        dpdu: Vec3::new(sin_phi, 0., cos_phi),
+        dpdv: Vec3::new(-cos_phi * cos_theta, -sin_theta, sin_phi * cos_theta),
+        uv: Vec2::new(u, v),
+    }
+}
+
+fn uv_sphere(segments: u32, rings: u32) -> Geometry {
+    Geometry::grid(segments, rings, |col, row| {
+        sphere_point(col as f32 / segments as f32, row as f32 / rings as f32)
+    })
+}
+
+/// Subdivided icosahedron, vertices on the UV seam and on the poles are duplicated
+fn ico_sphere(subdivisions: u32) -> Geometry {
+    let t = (1. + 5f32.sqrt()) / 2.;
+    let mut positions: Vec<Vec3> = [
+        [-1., t, 0.],
+        [1., t, 0.],
+        [-1., -t, 0.],
+        [1., -t, 0.],
+        [0., -1., t],
+        [0., 1., t],
+        [0., -1., -t],
+        [0., 1., -t],
+        [t, 0., -1.],
+        [t, 0., 1.],
+        [-t, 0., -1.],
+        [-t, 0., 1.],
+    ]
+    .into_iter()
+    .map(|p| Vec3::from(p).normalize())
+    .collect();
+
+    let mut faces: Vec<[usize; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |i0: usize, i1: usize| {
+            *midpoints
+                .entry((i0.min(i1), i0.max(i1)))
+                .or_insert_with(|| {
+                    positions.push(((positions[i0] + positions[i1]) / 2.).normalize());
+                    positions.len() - 1
+                })
+        };
+
+        faces = faces
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let ab = midpoint(a, b);
+                let bc = midpoint(b, c);
+                let ca = midpoint(c, a);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut geometry = Geometry::default();
+    // Vertices are shared by the position and the U coordinate
+    let mut vertex_indices: HashMap<(usize, u32), u32> = HashMap::new();
+
+    for face in faces {
+        let p = face.map(|i| positions[i]);
+
+        let v = p.map(|p| p.y.clamp(-1., 1.).acos() / PI);
+        let mut u = p.map(|p| {
+            let phi = p.z.atan2(-p.x);
+            phi.rem_euclid(2. * PI) / (2. * PI)
+        });
+        let is_pole = p.map(|p| p.x.abs() < 1e-6 && p.z.abs() < 1e-6);
+
+        // Faces crossing the seam would interpolate U across the whole texture
+        let seam_u = || (0..3).filter(|&i| !is_pole[i]).map(|i| u[i]);
+        let max_u = seam_u().fold(f32::MIN, f32::max);
+        let min_u = seam_u().fold(f32::MAX, f32::min);
+        if max_u - min_u > 0.5 {
+            for u in &mut u {
+                if *u < 0.5 {
+                    *u += 1.;
+                }
+            }
+        }
+
+        // U is undefined on the poles, use the average of the other vertices
+        for i in 0..3 {
+            if is_pole[i] {
+                u[i] = (u[(i + 1) % 3] + u[(i + 2) % 3]) / 2.;
+            }
+        }
+
+        let mut corners = [0; 3];
+        for i in 0..3 {
+            corners[i] = *vertex_indices
+                .entry((face[i], u[i].to_bits()))
+                .or_insert_with(|| {
+                    let mut point = sphere_point(u[i], v[i]);
+                    // Keep the exact position, so the shared edges stay watertight
+                    point.pos = p[i];
+                    point.normal = p[i];
+                    geometry.vertices.push(point.to_vertex());
+                    geometry.vertices.len() as u32 - 1
+                });
+        }
+
+        // Front faces are counter-clockwise when looking from the outside
+        if (p[1] - p[0]).cross(p[2] - p[0]).dot(p[0]) >= 0. {
+            geometry.indices.extend_from_slice(&corners);
+        } else {
+            geometry
+                .indices
+                .extend_from_slice(&[corners[0], corners[2], corners[1]]);
+        }
+    }
+
+    geometry
+}
+
+/// A square in the XY plane facing +Z, the size is 2 x 2
+fn quad() -> Geometry {
+    Geometry::grid(1, 1, |col, row| {
+        let u = col as f32;
+        let v = row as f32;
+
+        SurfacePoint {
+            pos: Vec3::new(2. * u - 1., 1. - 2. * v, 0.),
+            normal: Vec3::Z,
+            dpdu: Vec3::X,
+            dpdv: Vec3::NEG_Y,
+            uv: Vec2::new(u, v),
+        }
+    })
+}
+
+/// A plane facing +Y, the size is 4 x 4
+fn plane() -> Geometry {
+    Geometry::grid(1, 1, |col, row| {
+        let u = col as f32;
+        let v = row as f32;
+
+        SurfacePoint {
+            pos: Vec3::new(4. * u - 2., 0., 4. * v - 2.),
+            normal: Vec3::Y,
+            dpdu: Vec3::X,
+            dpdv: Vec3::Z,
+            uv: Vec2::new(u, v) * 4.,
+        }
+    })
+}
+
+fn cube() -> Geometry {
+    let face = || quad().transform(Mat4::from_translation(Vec3::Z));
+
+    let rotations = [
+        Mat4::IDENTITY,
+        Mat4::from_rotation_y(PI / 2.),
+        Mat4::from_rotation_y(PI),
+        Mat4::from_rotation_y(-PI / 2.),
+        Mat4::from_rotation_x(-PI / 2.),
+        Mat4::from_rotation_x(PI / 2.),
+    ];
+
+    let mut geometry = Geometry::default();
+    for rotation in rotations {
+        geometry.append(face().transform(rotation));
+    }
+
+    geometry
+}
+
+/// A (2, 3) torus knot, U goes along the knot and V around the tube
+fn torus_knot(segments: u32, tube_segments: u32) -> Geometry {
+    const P: f32 = 2.;
+    const Q: f32 = 3.;
+    const RADIUS: f32 = 1.;
+    const TUBE_RADIUS: f32 = 0.25;
+
+    let curve = |t: f32| {
+        let qt = t * Q / P;
+        let r = RADIUS * (2. + qt.cos()) * 0.5;
+        Vec3::new(r * t.cos(), r * t.sin(), RADIUS * qt.sin() * 0.5)
+    };
+
+    let surface = |u: f32, v: f32| {
+        let t = u * P * 2. * PI;
+        let center = curve(t);
+        let next = curve(t + 0.01);
+
+        // Frame that rotates with the curve
+        let tangent = next - center;
+        let mut normal = next + center;
+        let binormal = tangent.cross(normal).normalize();
+        normal = binormal.cross(tangent).normalize();
+
+        let angle = v * 2. * PI;
+        let offset = normal * angle.cos() + binormal * angle.sin();
+        (center + offset * TUBE_RADIUS, offset)
+    };
+
+    Geometry::grid(segments, tube_segments, |col, row| {
+        let u = col as f32 / segments as f32;
+        let v = row as f32 / tube_segments as f32;
+        let (pos, normal) = surface(u, v);
+
+        let eps = 1e-3;
+        let dpdu = surface(u + eps, v).0 - surface(u - eps, v).0;
+        let dpdv = surface(u, v + eps).0 - surface(u, v - eps).0;
+
+        SurfacePoint {
+            pos,
+            normal,
+            dpdu,
+            dpdv,
+            uv: Vec2::new(u * 16., v),
+        }
+    })
+}
+
+/// A point of a profile curve that is rotated around the Y axis
+struct ProfilePoint {
+    /// Distance from the axis and height
+    pos: Vec2,
+    /// Outward-facing normal of the profile
+    normal: Vec2,
+}
+
+/// Rotates the profile around the Y axis.
+///
+/// The profile must go from top to bottom, points of hard edges are duplicated with different normals.
+fn lathe(profile: &[ProfilePoint], segments: u32) -> Geometry {
+    // V is the distance along the profile
+    let mut distances = vec![0.];
+    for w in profile.windows(2) {
+        let last = distances[distances.len() - 1];
+        distances.push(last + w[0].pos.distance(w[1].pos));
+    }
+    let length = distances[distances.len() - 1];
+
+    Geometry::grid(segments, profile.len() as u32 - 1, |col, row| {
+        let point = &profile[row as usize];
+        let u = col as f32 / segments as f32;
+        let (sin_phi, cos_phi) = (u * 2. * PI).sin_cos();
+
+        let revolve = |v: Vec2| Vec3::new(v.x * cos_phi, v.y, v.x * sin_phi);
+        // The profile goes clockwise from the top to the bottom
+        let profile_dir = Vec2::new(point.normal.y, -point.normal.x);
+
+        SurfacePoint {
+            pos: revolve(point.pos),
+            normal: revolve(point.normal),
+            dpdu: Vec3::new(-sin_phi, 0., cos_phi),
+            dpdv: revolve(profile_dir),
+            uv: Vec2::new(u * 4., distances[row as usize] / length),
+        }
+    })
+}
+
+/// Pushes points of a circle arc, the angles are measured from the top
+fn arc(profile: &mut Vec<ProfilePoint>, center: Vec2, radius: f32, from: f32, to: f32, steps: u32) {
+    for i in 0..=steps {
+        let angle = from + (to - from) * i as f32 / steps as f32;
+        let normal = Vec2::new(angle.sin(), angle.cos());
+
+        profile.push(ProfilePoint {
+            pos: center + normal * radius,
+            normal,
+        });
+    }
+}
+
+/// Pushes a straight segment with a hard edge on both ends
+fn segment(profile: &mut Vec<ProfilePoint>, from: Vec2, to: Vec2) {
+    let dir = (to - from).normalize();
+    let normal = Vec2::new(-dir.y, dir.x);
+
+    profile.push(ProfilePoint { pos: from, normal });
+    profile.push(ProfilePoint { pos: to, normal });
+}
+
+/// A material preview ball with a groove around it
+fn shader_ball(segments: u32) -> Geometry {
+    const CENTER: Vec2 = Vec2::new(0., 1.35);
+    const GROOVE_HALF_ANGLE: f32 = 0.12;
+    const GROOVE_RADIUS: f32 = 0.92;
+
+    let top = PI / 2. - GROOVE_HALF_ANGLE;
+    let bottom = PI / 2. + GROOVE_HALF_ANGLE;
+    let groove_top = CENTER + Vec2::new(top.sin(), top.cos());
+    let groove_bottom = CENTER + Vec2::new(bottom.sin(), bottom.cos());
+
+    let mut profile = Vec::new();
+    arc(&mut profile, CENTER, 1., 0., top, 32);
+    segment(
+        &mut profile,
+        groove_top,
+        Vec2::new(GROOVE_RADIUS, groove_top.y),
+    );
+    segment(
+        &mut profile,
+        Vec2::new(GROOVE_RADIUS, groove_top.y),
+        Vec2::new(GROOVE_RADIUS, groove_bottom.y),
+    );
+    segment(
+        &mut profile,
+        Vec2::new(GROOVE_RADIUS, groove_bottom.y),
+        groove_bottom,
+    );
+    arc(&mut profile, CENTER, 1., bottom, PI, 32);
+
+    lathe(&profile, segments)
+}
+
+/// A pedestal for the shader ball
+fn pedestal(segments: u32) -> Geometry {
+    let mut profile = Vec::new();
+    segment(&mut profile, Vec2::new(0., 0.5), Vec2::new(0.6, 0.5));
+    segment(&mut profile, Vec2::new(0.6, 0.5), Vec2::new(1., 0.1));
+    segment(&mut profile, Vec2::new(1., 0.1), Vec2::new(1., 0.));
+    segment(&mut profile, Vec2::new(1., 0.), Vec2::new(0., 0.));
+
+    lathe(&profile, segments)
+}
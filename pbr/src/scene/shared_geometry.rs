@@ -0,0 +1,95 @@
+use std::mem::size_of;
+
+use crate::ogl::{gl_buffer::GlBuffer, vao::Vao};
+
+use super::{mesh::Vertex, Node, Primitive};
+
+/// Location of a primitive in the shared buffers
+#[derive(Clone, Copy, Default)]
+pub struct GeometryRange {
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub num_indices: u32,
+}
+
+/// Vertices and indices of all primitives of a scene in a single pair of buffers.
+///
+/// The buffers are copied from the buffers of the primitives on the GPU.
+pub struct SharedGeometry {
+    pub vao: Vao,
+    _vertex_buffer: GlBuffer,
+    _index_buffer: GlBuffer,
+    /// Indexed by the index of the primitive
+    pub ranges: Vec<GeometryRange>,
+    /// Size of the buffers in bytes
+    pub memory_size: usize,
+}
+
+impl SharedGeometry {
+    pub fn new(root: &Node, num_primitives: usize) -> Self {
+        let mut primitives = Vec::with_capacity(num_primitives);
+        Self::gather_primitives(root, &mut primitives);
+
+        let num_vertices: usize = primitives.iter().map(|p| p.num_vertices).sum();
+        let num_indices: usize = primitives.iter().map(|p| p.num_indices).sum();
+        let vertices_size = num_vertices * size_of::<Vertex>();
+        let indices_size = num_indices * size_of::<u32>();
+
+        let vertex_buffer = GlBuffer::with_size(vertices_size);
+        let index_buffer = GlBuffer::with_size(indices_size);
+
+        let mut ranges = vec![GeometryRange::default(); num_primitives];
+        let (mut base_vertex, mut first_index) = (0, 0);
+
+        for primitive in primitives {
+            let vertices_len = primitive.num_vertices * size_of::<Vertex>();
+            let indices_len = primitive.num_indices * size_of::<u32>();
+
+            unsafe {
+                gl::CopyNamedBufferSubData(
+                    primitive.vertex_buffer.id,
+                    vertex_buffer.id,
+                    0,
+                    (base_vertex * size_of::<Vertex>()) as isize,
+                    vertices_len as isize,
+                );
+                gl::CopyNamedBufferSubData(
+                    primitive.index_buffer.id,
+                    index_buffer.id,
+                    0,
+                    (first_index * size_of::<u32>()) as isize,
+                    indices_len as isize,
+                );
+            }
+
+            ranges[primitive.index] = GeometryRange {
+                first_index: first_index as u32,
+                base_vertex: base_vertex as i32,
+                num_indices: primitive.num_indices as u32,
+            };
+
+            base_vertex += primitive.num_vertices;
+            first_index += primitive.num_indices;
+        }
+
+        let vao = Primitive::create_vao(&vertex_buffer, &index_buffer);
+
+        Self {
+            vao,
+            _vertex_buffer: vertex_buffer,
+            _index_buffer: index_buffer,
+            ranges,
+            memory_size: vertices_size + indices_size,
+        }
+    }
+
+    fn gather_primitives<'n>(node: &'n Node, primitives: &mut Vec<&'n Primitive>) {
+        if let Some(mesh) = &node.mesh {
+            primitives.extend(&mesh.primitives);
+        }
+
+        for child in &node.children {
+            Self::gather_primitives(child, primitives);
+        }
+    }
+}
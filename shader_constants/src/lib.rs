@@ -4,6 +4,10 @@ use serde::Serialize;
 pub struct Consts {
     pub vertex_attrib_indices: VertexAttribIndices,
     pub ibl: IblConsts,
+    pub shadows: ShadowConsts,
+    pub clusters: ClusterConsts,
+    pub ltc: LtcConsts,
+    pub post_process: PostProcessConsts,
     pub texture_ports: TexturePorts,
     pub buffer_bindings: BufferBindings,
 }
@@ -24,6 +28,43 @@ pub struct IblConsts {
     pub local_size_z: u32,
 }
 
+#[derive(Serialize)]
+pub struct ShadowConsts {
+    /// Number of cascades of the directional lights
+    pub cascades: u32,
+    /// Maximum number of layers of the 2D shadow maps
+    pub max_layers: u32,
+    /// Maximum number of cubemaps of the point and area lights, also limited by the GL array layer limit
+    pub max_cube_layers: u32,
+    pub map_size: i32,
+    pub cube_map_size: i32,
+}
+
+#[derive(Serialize)]
+pub struct ClusterConsts {
+    /// Number of clusters along the screen X axis, also the compute shader local size
+    pub x: u32,
+    /// Number of clusters along the screen Y axis, also the compute shader local size
+    pub y: u32,
+    /// Number of exponential depth slices
+    pub z: u32,
+    pub max_lights: u32,
+}
+
+#[derive(Serialize)]
+pub struct LtcConsts {
+    /// Size of the LTC lookup table
+    pub size: i32,
+    pub local_size_xy: u32,
+}
+
+#[derive(Serialize)]
+pub struct PostProcessConsts {
+    pub local_size_xy: u32,
+    /// Maximum number of levels of the bloom mip chain
+    pub bloom_levels: i32,
+}
+
 #[derive(Serialize)]
 pub struct TexturePorts {
     pub albedo: u32,
@@ -39,6 +80,12 @@ pub struct TexturePorts {
     pub irradiance: u32,
     pub prefilter: u32,
     pub brdf: u32,
+
+    pub shadow_maps: u32,
+    pub shadow_cube_maps: u32,
+    pub ltc: u32,
+    pub ssao: u32,
+    pub ssr: u32,
 }
 
 #[derive(Serialize)]
@@ -47,6 +94,12 @@ pub struct BufferBindings {
     pub pbr_material: u32,
     pub lighting: u32,
     pub settings: u32,
+    pub shadows: u32,
+    pub lights: u32,
+    pub clusters: u32,
+    pub cluster_lights: u32,
+    pub draw_transforms: u32,
+    pub draw_materials: u32,
     pub brdf_merl: u32,
     pub brdf_utia: u32,
 }
@@ -67,6 +120,28 @@ pub const CONSTS: Consts = Consts {
         local_size_xy: 4,
         local_size_z: 1,
     },
+    shadows: ShadowConsts {
+        // The cascade splits are stored in a vec4 in the shaders
+        cascades: 4,
+        max_layers: 16,
+        max_cube_layers: 16,
+        map_size: 2048,
+        cube_map_size: 1024,
+    },
+    clusters: ClusterConsts {
+        x: 16,
+        y: 9,
+        z: 24,
+        max_lights: 128,
+    },
+    ltc: LtcConsts {
+        size: 64,
+        local_size_xy: 8,
+    },
+    post_process: PostProcessConsts {
+        local_size_xy: 8,
+        bloom_levels: 6,
+    },
     texture_ports: TexturePorts {
         albedo: 0,
         mr: 1,
@@ -79,12 +154,23 @@ pub const CONSTS: Consts = Consts {
         irradiance: 8,
         prefilter: 9,
         brdf: 10,
+        shadow_maps: 11,
+        shadow_cube_maps: 12,
+        ltc: 13,
+        ssao: 14,
+        ssr: 15,
     },
     buffer_bindings: BufferBindings {
         transforms: 0,
         pbr_material: 1,
         lighting: 2,
         settings: 3,
+        shadows: 4,
+        lights: 5,
+        clusters: 6,
+        cluster_lights: 7,
+        draw_transforms: 8,
+        draw_materials: 9,
         brdf_merl: 10,
         brdf_utia: 11,
     },
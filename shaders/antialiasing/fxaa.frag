@@ -0,0 +1,52 @@
+#version 460 core
+
+// Fast approximate anti-aliasing of the tonemapped image, based on:
+// Lottes, “FXAA.” NVIDIA whitepaper.
+
+in vec2 texCoords;
+
+out vec4 FragColor;
+
+layout(binding = 0) uniform sampler2D ldrTex;
+
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_SPAN_MAX = 8.0;
+
+float luma(vec3 color) { return dot(color, vec3(0.299, 0.587, 0.114)); }
+
+void main()
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(ldrTex, 0));
+
+    vec3 rgbM = textureLod(ldrTex, texCoords, 0.0).rgb;
+    float lumaM = luma(rgbM);
+    float lumaTl = luma(textureLod(ldrTex, texCoords + vec2(-1.0, 1.0) * texelSize, 0.0).rgb);
+    float lumaTr = luma(textureLod(ldrTex, texCoords + vec2(1.0, 1.0) * texelSize, 0.0).rgb);
+    float lumaBl = luma(textureLod(ldrTex, texCoords + vec2(-1.0, -1.0) * texelSize, 0.0).rgb);
+    float lumaBr = luma(textureLod(ldrTex, texCoords + vec2(1.0, -1.0) * texelSize, 0.0).rgb);
+
+    float lumaMin = min(lumaM, min(min(lumaTl, lumaTr), min(lumaBl, lumaBr)));
+    float lumaMax = max(lumaM, max(max(lumaTl, lumaTr), max(lumaBl, lumaBr)));
+
+    // The direction of the edge is perpendicular to the luma gradient
+    vec2 dir = vec2((lumaTl + lumaTr) - (lumaBl + lumaBr), (lumaBl + lumaTl) - (lumaBr + lumaTr));
+
+    float dirReduce = max((lumaTl + lumaTr + lumaBl + lumaBr) * (0.25 * FXAA_REDUCE_MUL), FXAA_REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texelSize;
+
+    // Blend along the edge, the wider blend is used unless it leaves the local luma range
+    vec3 rgbA = 0.5
+        * (textureLod(ldrTex, texCoords + dir * (1.0 / 3.0 - 0.5), 0.0).rgb
+            + textureLod(ldrTex, texCoords + dir * (2.0 / 3.0 - 0.5), 0.0).rgb);
+    vec3 rgbB = rgbA * 0.5
+        + 0.25
+            * (textureLod(ldrTex, texCoords + dir * -0.5, 0.0).rgb
+                + textureLod(ldrTex, texCoords + dir * 0.5, 0.0).rgb);
+
+    float lumaB = luma(rgbB);
+    vec3 color = (lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB;
+
+    FragColor = vec4(color, 1.0);
+}
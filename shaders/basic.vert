@@ -1,5 +1,7 @@
 #version 460 core
 
+//#defines
+
 // clang-format off
 layout(location = {{ consts.vertex_attrib_indices.position }}) in vec3 inPos;
 layout(location = {{ consts.vertex_attrib_indices.normals }}) in vec3 inNormal;
@@ -24,8 +26,29 @@ out VsOut
 }
 vsOut;
 
+#ifdef MULTI_DRAW
+// clang-format off
+layout(std430, binding = {{ consts.buffer_bindings.draw_transforms }}) readonly buffer DrawTransforms
+// clang-format on
+{
+    mat4 drawTransforms[];
+};
+
+// The base instance of the indirect draw command is the index of the draw
+flat out uint drawIndex;
+#endif
+
+// The depth prepass must produce exactly the same depth as the shading pass
+invariant gl_Position;
+
 void main()
 {
+#ifdef MULTI_DRAW
+    // Hides the model matrix of the uniform buffer
+    mat4 model = drawTransforms[gl_BaseInstance];
+    drawIndex = uint(gl_BaseInstance);
+#endif
+
     gl_Position = projection * view * model * vec4(inPos, 1.0);
 
     vsOut.texCoords = inTexcoords;
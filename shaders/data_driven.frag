@@ -3,6 +3,10 @@
 
 //#defines
 
+#ifdef MULTI_DRAW
+#extension GL_ARB_bindless_texture : require
+#endif
+
 {% include "consts.glsl" %}
 
 {% include "structs/pbrVsOut.glsl" %}
@@ -10,10 +14,14 @@
 {% include "structs/pbrTextures.glsl" %}
 {% include "structs/lighting.glsl" %}
 {% include "structs/settings.glsl" %}
+{% include "structs/shadows.glsl" %}
+{% include "structs/clusters.glsl" %}
 {% include "structs/brdf_bufs.glsl" %}
 
-{% include "tools/tonemap.glsl" %}
 {% include "tools/normal_map.glsl" %}
+{% include "tools/shadows.glsl" %}
+{% include "tools/ambient_occlusion.glsl" %}
+{% include "tools/debug_view.glsl" %}
 
 #ifdef MERL_BRDF
 {% include "measured_brdf/brdf_merl.glsl" %}
@@ -37,12 +45,16 @@ vec3 calculateDirectLighting(ShadingParams sp)
 {
     vec3 totalRadiance = vec3(0.);
 
-    for (int i = 0; i < lights; i++) {
-        vec3 light = lightColors[i].xyz;
-        float dist = distance(lightPositions[i].xyz, vsOut.fragPos);
-        light *= 1. / (dist * dist + 0.00001);
+    // Only the lights that affect the cluster of the fragment
+    uint cluster = clusterIndex(gl_FragCoord);
+    uint clusterLights = clusterLightCounts[cluster];
+
+    for (uint c = 0; c < clusterLights; c++) {
+        Light clusterLight = lights[clusterLightIndices[cluster * CLUSTER_MAX_LIGHTS + c]];
 
-        vec3 lightDir = normalize(lightPositions[i].xyz - vsOut.fragPos);
+        vec3 lightDir;
+        vec3 light = lightRadiance(clusterLight, vsOut.fragPos, lightDir);
+        light *= shadowVisibility(clusterLight, vsOut.fragPos, normalize(vsOut.normal), lightDir);
 
         float NoL = dot(sp.tb.normal, lightDir);
 
@@ -82,22 +94,31 @@ ShadingParams initShadingParams()
 
 void main()
 {
+#ifdef MULTI_DRAW
+    loadDrawMaterial();
+#endif
+
     ShadingParams sp = initShadingParams();
 
     vec3 color = vec3(0.);
+    if (geometryDebugView(sp.tb, sp.NoV, color)) {
+        FragColor = vec4(color, 1.0);
+        return;
+    }
 
+    if (debugView == DEBUG_VIEW_OCCLUSION) {
+        FragColor = vec4(debugValue(vec3(occlusionMap())), 1.0);
+        return;
+    }
+
+    // The other views aren't available for the measured BRDFs, only the whole direct light is left
     if (directLightEnabled) {
         color += calculateDirectLighting(sp);
     }
 
-#ifdef OCCLUSION_MAP
-    color *= texture(occlusionTex, vsOut.texCoords).x * occlusionStrength;
-#endif
-
-    tonemap(color);
-
-    // gamma correction
-    color = pow(color, vec3(1.0 / GAMMA));
+    // The measured BRDFs have no ambient light, only the occlusion texture is applied
+    color *= occlusionMap();
 
+    // Tonemapping is done in the post-processing pass
     FragColor = vec4(color, 1.0);
 }
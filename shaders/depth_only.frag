@@ -0,0 +1,4 @@
+#version 460 core
+
+// The depth prepass only writes the depth, the vertex shader is shared with the shading pass
+void main() { }
@@ -0,0 +1,12 @@
+#version 460 core
+
+out vec2 texCoords;
+
+// A single triangle that covers the whole viewport, drawn without any vertex buffers
+void main()
+{
+    vec2 pos = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    texCoords = pos;
+
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
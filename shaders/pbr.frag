@@ -2,6 +2,10 @@
 // clang-format off
 //#defines
 
+#ifdef MULTI_DRAW
+#extension GL_ARB_bindless_texture : require
+#endif
+
 {% include "consts.glsl" %}
 
 {% include "structs/pbrVsOut.glsl" %}
@@ -9,19 +13,30 @@
 {% include "structs/pbrTextures.glsl" %}
 {% include "structs/lighting.glsl" %}
 {% include "structs/settings.glsl" %}
+{% include "structs/shadows.glsl" %}
+{% include "structs/clusters.glsl" %}
 
 {% include "ibl/brdf_sampling.glsl" %}
 
-{% include "tools/tonemap.glsl" %}
 {% include "tools/normal_map.glsl" %}
+{% include "tools/shadows.glsl" %}
+{% include "tools/area_lights.glsl" %}
+{% include "tools/ambient_occlusion.glsl" %}
+{% include "tools/ssr.glsl" %}
+{% include "tools/debug_view.glsl" %}
 
 {% include "brdf.glsl" %}
 
 // clang-format on
 
-#line 22
+#line 32
 out vec4 FragColor;
 
+// Weights of the individual lobes, the debug views only show some of them
+float diffuseWeight = 1.;
+float specularWeight = 1.;
+float clearcoatWeight = 1.;
+
 // Parameters that stay same for the whole pixel
 struct ShadingParams {
     vec4 albedo;
@@ -91,18 +106,38 @@ vec3 calculateDirectLighting(ShadingParams sp)
 {
     vec3 totalRadiance = vec3(0.);
 
-    for (int i = 0; i < lights; i++) {
-        vec3 lightDir = normalize(lightPositions[i].xyz - vsOut.fragPos);
+    // Only the lights that affect the cluster of the fragment
+    uint cluster = clusterIndex(gl_FragCoord);
+    uint clusterLights = clusterLightCounts[cluster];
+
+    for (uint c = 0; c < clusterLights; c++) {
+        Light clusterLight = lights[clusterLightIndices[cluster * CLUSTER_MAX_LIGHTS + c]];
+
+        // Area lights ignore the clearcoat layer and the anisotropy
+        if (isAreaLight(clusterLight)) {
+            vec3 lightDir = normalize(clusterLight.position.xyz - vsOut.fragPos);
+            float shadow = shadowVisibility(clusterLight, vsOut.fragPos, normalize(vsOut.normal), lightDir);
+            vec3 diffuseColor = sp.albedo.rgb * (1.0 - sp.metalness);
+
+            vec3 areaDiffuse;
+            vec3 areaSpecular;
+            areaLightRadiance(clusterLight, vsOut.fragPos, sp.tb.normal, sp.viewDir, sp.roughness, sp.f0,
+                diffuseColor, areaDiffuse, areaSpecular);
+
+            totalRadiance += shadow * (areaDiffuse * diffuseWeight + areaSpecular * specularWeight);
+            continue;
+        }
+
+        vec3 lightDir;
+        vec3 light = lightRadiance(clusterLight, vsOut.fragPos, lightDir);
+        light *= shadowVisibility(clusterLight, vsOut.fragPos, normalize(vsOut.normal), lightDir);
+
         vec3 halfway = normalize(sp.viewDir + lightDir);
         float VoH = max(dot(halfway, sp.viewDir), 0.0);
         float NoH = max(dot(sp.tb.normal, halfway), 0.0);
         float LoH = max(dot(lightDir, halfway), 0.0);
         float NoL = max(dot(sp.tb.normal, lightDir), 0.0);
 
-        vec3 light = lightColors[i].xyz;
-        float dist = distance(lightPositions[i].xyz, vsOut.fragPos);
-        light *= 1. / (dist * dist + 0.00001);
-
         vec3 fresnel;
         vec3 specular;
 #ifdef ANISOTROPY
@@ -136,7 +171,7 @@ vec3 calculateDirectLighting(ShadingParams sp)
 
         diffuse /= PI;
 
-        vec3 brdf = diffuse + specular;
+        vec3 brdf = diffuse * diffuseWeight + specular * specularWeight;
 
 #ifdef CLEARCOAT
         if (clearcoatEnabled) {
@@ -144,7 +179,7 @@ vec3 calculateDirectLighting(ShadingParams sp)
             vec3 clearcoatColor = clearcoatBrdf(sp, clearcoatFresnel, halfway, lightDir, VoH);
 
             // Energy loss due to the clearcoat layer is given by 1 - clearcoatFresnel
-            brdf = brdf * (1. - clearcoatFresnel) + clearcoatColor;
+            brdf = brdf * (1. - clearcoatFresnel) + clearcoatColor * clearcoatWeight;
         }
 #endif
 
@@ -174,7 +209,7 @@ vec3 calcClearcoatIBL(ShadingParams sp, inout vec3 baseLayerEnvLight)
     // base layer attenuation for energy compensation
     baseLayerEnvLight *= 1.0 - clearcoatFresnel;
 
-    return clearcoatIblSpecular;
+    return clearcoatIblSpecular * clearcoatWeight;
 }
 #endif
 
@@ -206,6 +241,7 @@ vec3 calculateIBL(ShadingParams sp)
     const float MAX_REFLECTION_LOD = float({{ consts.ibl.cubemap_roughnes_levels - 1 }});
     // clang-format on
     vec3 prefilteredRadiance = textureLod(prefilterMap, reflectDir, sqrt(sp.roughness) * MAX_REFLECTION_LOD).rgb;
+    prefilteredRadiance = screenSpaceReflections(prefilteredRadiance, sp.roughness);
     vec3 irradiance = texture(irradianceMap, sp.tb.normal).rgb;
     vec2 dfg = texture(brdfLut, vec2(sp.NoV, sqrt(sp.roughness))).rg;
 
@@ -225,14 +261,16 @@ vec3 calculateIBL(ShadingParams sp)
         vec3 Edss = 1. - (FssEss + Fms * Ems);
         vec3 kD = sp.albedo.rgb * Edss * (1. - sp.metalness);
 
-        baseLayerEnvLight = FssEss * prefilteredRadiance + (Fms * Ems + kD) * irradiance;
+        baseLayerEnvLight = (FssEss * prefilteredRadiance + Fms * Ems * irradiance) * specularWeight
+            + kD * irradiance * diffuseWeight;
     } else {
         // Specular
         vec3 FssEss = fresnel * dfg.x + dfg.y;
         // Diffuse
         vec3 kD = (1.0 - fresnel) * (1. - sp.metalness);
 
-        baseLayerEnvLight = (FssEss * prefilteredRadiance) + (irradiance * sp.albedo.rgb * kD);
+        baseLayerEnvLight
+            = (FssEss * prefilteredRadiance) * specularWeight + (irradiance * sp.albedo.rgb * kD) * diffuseWeight;
     }
 
 #ifdef CLEARCOAT
@@ -242,9 +280,7 @@ vec3 calculateIBL(ShadingParams sp)
     }
 #endif
 
-#ifdef OCCLUSION_MAP
-    baseLayerEnvLight *= texture(occlusionTex, vsOut.texCoords).x * occlusionStrength;
-#endif
+    baseLayerEnvLight *= ambientOcclusion();
 
     return baseLayerEnvLight;
 }
@@ -335,30 +371,88 @@ ShadingParams initShadingParams()
     return sp;
 }
 
+// The material inputs and the geometry, returns false for the lighting views
+bool debugMaterialView(ShadingParams sp, vec3 emissive, out vec3 color)
+{
+    if (geometryDebugView(sp.tb, sp.NoV, color)) {
+        return true;
+    }
+
+    switch (debugView) {
+    case DEBUG_VIEW_BASE_COLOR:
+        color = debugValue(sp.albedo.rgb);
+        return true;
+    case DEBUG_VIEW_METALLIC:
+        color = debugValue(vec3(sp.metalness));
+        return true;
+    case DEBUG_VIEW_ROUGHNESS:
+        // Perceptual roughness, as it's stored in the material
+        color = debugValue(vec3(sqrt(sp.roughness)));
+        return true;
+    case DEBUG_VIEW_OCCLUSION:
+        color = debugValue(vec3(ambientOcclusion()));
+        return true;
+    case DEBUG_VIEW_EMISSIVE:
+        color = debugValue(emissive);
+        return true;
+    }
+
+    return false;
+}
+
+void setDebugLobeWeights()
+{
+    switch (debugView) {
+    case DEBUG_VIEW_DIFFUSE_ONLY:
+        specularWeight = 0.;
+        clearcoatWeight = 0.;
+        break;
+    case DEBUG_VIEW_SPECULAR_ONLY:
+        diffuseWeight = 0.;
+        break;
+    case DEBUG_VIEW_CLEARCOAT:
+        diffuseWeight = 0.;
+        specularWeight = 0.;
+        break;
+    }
+}
+
 void main()
 {
+#ifdef MULTI_DRAW
+    loadDrawMaterial();
+#endif
+
     ShadingParams sp = initShadingParams();
 
-    vec3 color = vec3(0.);
+    vec3 emissive = vec3(0.);
+#ifdef EMISSIVE_MAP
+    vec4 emissiveTexel = texture(emissiveTex, vsOut.texCoords);
+    emissive = pow(emissiveTexel.rgb, vec3(GAMMA)) * emissiveFactor.xyz;
+#endif
+
+    vec3 color;
+    if (debugMaterialView(sp, emissive, color)) {
+        FragColor = vec4(color, 1.0);
+        return;
+    }
+
+    setDebugLobeWeights();
 
-    if (IBLEnabled) {
+    color = vec3(0.);
+
+    if (IBLEnabled && debugView != DEBUG_VIEW_DIRECT_ONLY) {
         color += calculateIBL(sp);
     }
 
-    if (directLightEnabled) {
+    if (directLightEnabled && debugView != DEBUG_VIEW_IBL_ONLY) {
         color += calculateDirectLighting(sp);
     }
 
-#ifdef EMISSIVE_MAP
-    vec4 emissive = texture(emissiveTex, vsOut.texCoords);
-    emissive.rgb = pow(emissive.rgb, vec3(GAMMA));
-    color += emissive.rgb * emissiveFactor.xyz;
-#endif
-
-    tonemap(color);
-
-    // gamma correction
-    color = pow(color, vec3(1.0 / GAMMA));
+    if (debugView == DEBUG_VIEW_NONE) {
+        color += emissive;
+    }
 
+    // Tonemapping is done in the post-processing pass
     FragColor = vec4(color, sp.albedo.a);
 }
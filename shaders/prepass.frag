@@ -0,0 +1,8 @@
+#version 460 core
+
+in vec3 viewNormal;
+
+out vec4 FragColor;
+
+// Geometric view-space normals for the screen-space effects
+void main() { FragColor = vec4(normalize(viewNormal), 1.0); }
@@ -0,0 +1,26 @@
+#version 460 core
+
+// clang-format off
+layout(location = {{ consts.vertex_attrib_indices.position }}) in vec3 inPos;
+layout(location = {{ consts.vertex_attrib_indices.normals }}) in vec3 inNormal;
+
+layout(std140, binding = {{ consts.buffer_bindings.transforms }}) uniform Transforms
+// clang-format on
+{
+    mat4 projection;
+    mat4 view;
+    // The prepass sets the model matrix as a uniform
+    mat4 _model;
+};
+
+uniform mat4 model;
+
+out vec3 viewNormal;
+
+void main()
+{
+    gl_Position = projection * view * model * vec4(inPos, 1.0);
+
+    mat3 normalMat = mat3(transpose(inverse(view * model)));
+    viewNormal = normalMat * inNormal;
+}
@@ -0,0 +1,17 @@
+#version 460 core
+
+in vec3 fragPos;
+
+// Point light shadow cubemaps store the linear distance, so they can be sampled in any direction
+uniform bool linearDepth;
+uniform vec3 lightPos;
+uniform float farPlane;
+
+void main()
+{
+    if (linearDepth) {
+        gl_FragDepth = distance(fragPos, lightPos) / farPlane;
+    } else {
+        gl_FragDepth = gl_FragCoord.z;
+    }
+}
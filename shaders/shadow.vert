@@ -0,0 +1,17 @@
+#version 460 core
+
+// clang-format off
+layout(location = {{ consts.vertex_attrib_indices.position }}) in vec3 inPos;
+// clang-format on
+
+uniform mat4 lightViewProjection;
+uniform mat4 model;
+
+out vec3 fragPos;
+
+void main()
+{
+    vec4 worldPos = model * vec4(inPos, 1.0);
+    fragPos = worldPos.xyz;
+    gl_Position = lightViewProjection * worldPos;
+}
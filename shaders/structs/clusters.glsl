@@ -0,0 +1,51 @@
+// clang-format off
+layout(std140, binding = {{ consts.buffer_bindings.clusters }}) uniform Clusters
+// clang-format on
+{
+    uniform mat4 clusterView;
+    uniform mat4 clusterInverseProjection;
+    // Offset and size of the viewport in window coordinates
+    uniform vec4 clusterViewport;
+    uniform float clusterNear;
+    uniform float clusterFar;
+};
+
+// clang-format off
+layout(std430, binding = {{ consts.buffer_bindings.cluster_lights }}) buffer ClusterLightsData
+{
+    uint clusterLightCounts[{{ consts.clusters.x * consts.clusters.y * consts.clusters.z }}];
+    // Each cluster has space for max_lights indices into the lights array
+    uint clusterLightIndices[];
+};
+
+const uvec3 CLUSTER_GRID = uvec3({{ consts.clusters.x }}, {{ consts.clusters.y }}, {{ consts.clusters.z }});
+const uint CLUSTER_MAX_LIGHTS = {{ consts.clusters.max_lights }};
+// clang-format on
+
+uint flattenClusterIndex(uvec3 cluster)
+{
+    return cluster.x + CLUSTER_GRID.x * (cluster.y + CLUSTER_GRID.y * cluster.z);
+}
+
+// View-space depth where the depth slice starts, the slices are distributed exponentially
+float clusterSliceDepth(uint slice)
+{
+    return clusterNear * pow(clusterFar / clusterNear, float(slice) / float(CLUSTER_GRID.z));
+}
+
+// Index of the cluster that contains the fragment
+uint clusterIndex(vec4 fragCoord)
+{
+    vec2 screenPos = (fragCoord.xy - clusterViewport.xy) / clusterViewport.zw;
+    uvec2 tile = uvec2(clamp(screenPos, 0.0, 0.9999) * vec2(CLUSTER_GRID.xy));
+
+    // Linearize the OpenGL depth
+    float ndcDepth = fragCoord.z * 2.0 - 1.0;
+    float viewDepth = 2.0 * clusterNear * clusterFar
+        / (clusterFar + clusterNear - ndcDepth * (clusterFar - clusterNear));
+
+    float slice = log(viewDepth / clusterNear) / log(clusterFar / clusterNear) * float(CLUSTER_GRID.z);
+    uint z = uint(clamp(slice, 0.0, float(CLUSTER_GRID.z - 1)));
+
+    return flattenClusterIndex(uvec3(tile, z));
+}
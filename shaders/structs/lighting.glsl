@@ -1,8 +1,133 @@
 
+const uint LIGHT_TYPE_POINT = 0;
+const uint LIGHT_TYPE_DIRECTIONAL = 1;
+const uint LIGHT_TYPE_SPOT = 2;
+// Area lights
+const uint LIGHT_TYPE_RECT = 3;
+const uint LIGHT_TYPE_DISK = 4;
+const uint LIGHT_TYPE_TUBE = 5;
+
+struct Light {
+    vec4 position;
+    // Direction the light is shining in (directional, spot, rect and disk lights), axis of tube lights
+    vec4 direction;
+    vec3 color;
+    float intensity;
+    uint typ;
+    // 0 means infinite range
+    float range;
+    float innerConeCos;
+    float outerConeCos;
+    // First layer of the shadow map, -1 if the light doesn't cast shadows
+    int shadowIndex;
+    uint castsShadows;
+    // Width of rect lights, diameter of disk lights and length of tube lights
+    float width;
+    // Height of rect lights and diameter of tube lights
+    float height;
+};
+
+// clang-format off
+layout(std430, binding = {{ consts.buffer_bindings.lights }}) readonly buffer LightsData { Light lights[]; };
+// clang-format on
+
 layout(std140, binding = {{ consts.buffer_bindings.lighting }}) uniform Lighting
 {
-    uniform vec4 lightPositions[4];
-    uniform vec4 lightColors[4];
     uniform vec4 camPos;
-    uniform uint lights;
+    uniform uint numLights;
 };
+
+// Smoothly fades out the light towards its range, from the glTF KHR_lights_punctual specification
+float rangeAttenuation(float dist, float range)
+{
+    if (range <= 0.0) {
+        return 1.0;
+    }
+
+    float ratio = dist / range;
+    float ratio4 = ratio * ratio * ratio * ratio;
+    return pow(clamp(1.0 - ratio4, 0.0, 1.0), 2.0);
+}
+
+bool isAreaLight(Light light) { return light.typ >= LIGHT_TYPE_RECT; }
+
+// Largest area of the light's surface that can be seen from a single direction
+float areaLightArea(Light light)
+{
+    switch (light.typ) {
+    case LIGHT_TYPE_RECT:
+        return light.width * light.height;
+    case LIGHT_TYPE_DISK:
+        return 0.25 * PI * light.width * light.width;
+    case LIGHT_TYPE_TUBE:
+        return light.width * light.height + 0.25 * PI * light.height * light.height;
+    }
+
+    return 0.0;
+}
+
+// Lights with infinite range are culled where their radiance drops below this value
+const float LIGHT_CUTOFF = 0.005;
+
+// Radius of the sphere around the light where it has a visible effect.
+// The clusters are culled with it and the light fades out towards it, so there are no seams between them.
+float lightInfluenceRadius(Light light)
+{
+    if (light.range > 0.0) {
+        return light.range;
+    }
+
+    float maxRadiance = max(light.color.r, max(light.color.g, light.color.b)) * light.intensity;
+
+    if (isAreaLight(light)) {
+        // Area lights are further from the center by half of their size
+        float extent = 0.5 * length(vec2(light.width, light.height));
+        return sqrt(maxRadiance * areaLightArea(light) / LIGHT_CUTOFF) + extent;
+    }
+
+    return sqrt(maxRadiance / LIGHT_CUTOFF);
+}
+
+// Area of the light's surface that is visible from the direction, lightDir points towards the light
+float areaLightProjectedArea(Light light, vec3 lightDir)
+{
+    vec3 axis = normalize(light.direction.xyz);
+    float cosAxis = dot(-lightDir, axis);
+
+    if (light.typ == LIGHT_TYPE_TUBE) {
+        float sinAxis = sqrt(max(1.0 - cosAxis * cosAxis, 0.0));
+        return light.width * light.height * sinAxis + 0.25 * PI * light.height * light.height * abs(cosAxis);
+    }
+
+    // Rect and disk lights only emit light from their front side
+    return areaLightArea(light) * max(cosAxis, 0.0);
+}
+
+// Returns the radiance arriving from the light and the direction towards the light.
+// Area lights are treated as point lights, the shaders that support them evaluate them separately.
+vec3 lightRadiance(Light light, vec3 fragPos, out vec3 lightDir)
+{
+    if (light.typ == LIGHT_TYPE_DIRECTIONAL) {
+        lightDir = -normalize(light.direction.xyz);
+        return light.color * light.intensity;
+    }
+
+    vec3 toLight = light.position.xyz - fragPos;
+    float dist = length(toLight);
+    lightDir = toLight / dist;
+
+    vec3 radiance = light.color * light.intensity / (dist * dist + 0.00001);
+    radiance *= rangeAttenuation(dist, lightInfluenceRadius(light));
+
+    if (light.typ == LIGHT_TYPE_SPOT) {
+        float cosAngle = dot(-lightDir, normalize(light.direction.xyz));
+        radiance *= smoothstep(light.outerConeCos, light.innerConeCos, cosAngle);
+    }
+
+    if (isAreaLight(light)) {
+        // The intensity of a distant area light is given by its luminance and its projected area
+        radiance *= areaLightProjectedArea(light, lightDir);
+    }
+
+    return radiance;
+}
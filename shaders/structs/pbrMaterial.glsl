@@ -1,4 +1,70 @@
+#ifdef MULTI_DRAW
+// The material of every draw of the multi-draw call together with the bindless texture handles
+struct DrawMaterial {
+    vec4 baseColorFactor;
+    vec4 emissiveFactor;
+    float metallicFactor;
+    float roughnessFactor;
+    float normalScale;
+    float occlusionStrength;
 
+    float clearcoatIntensityFactor;
+    float clearcoatRoughnessFactor;
+    float clearcoatNormalScale;
+
+    float anisotropy;
+
+    uvec2 albedoTex;
+    uvec2 mrTex;
+    uvec2 normalTex;
+    uvec2 occlusionTex;
+    uvec2 emissiveTex;
+    uvec2 clearcoatIntensityTex;
+    uvec2 clearcoatRoughnessTex;
+    uvec2 clearcoatNormalTex;
+};
+
+// clang-format off
+layout(std430, binding = {{ consts.buffer_bindings.draw_materials }}) readonly buffer DrawMaterials
+// clang-format on
+{
+    DrawMaterial drawMaterials[];
+};
+
+flat in uint drawIndex;
+
+// The shaders use the same names for both paths, the values are loaded at the start of main()
+vec4 baseColorFactor;
+vec4 emissiveFactor;
+float metallicFactor;
+float roughnessFactor;
+float normalScale;
+float occlusionStrength;
+
+float clearcoatIntensityFactor;
+float clearcoatRoughnessFactor;
+float clearcoatNormalScale;
+
+float anisotropy;
+
+void loadDrawMaterial()
+{
+    DrawMaterial material = drawMaterials[drawIndex];
+
+    baseColorFactor = material.baseColorFactor;
+    emissiveFactor = material.emissiveFactor;
+    metallicFactor = material.metallicFactor;
+    roughnessFactor = material.roughnessFactor;
+    normalScale = material.normalScale;
+    occlusionStrength = material.occlusionStrength;
+
+    clearcoatIntensityFactor = material.clearcoatIntensityFactor;
+    clearcoatRoughnessFactor = material.clearcoatRoughnessFactor;
+    clearcoatNormalScale = material.clearcoatNormalScale;
+
+    anisotropy = material.anisotropy;
+}
+#else
 // clang-format off
 layout(std140, binding = {{ consts.buffer_bindings.pbr_material }}) uniform PbrMaterial
 // clang-format on
@@ -16,3 +82,4 @@ layout(std140, binding = {{ consts.buffer_bindings.pbr_material }}) uniform PbrM
 
     uniform float anisotropy;
 };
+#endif
@@ -1,5 +1,15 @@
-
 // clang-format off
+#ifdef MULTI_DRAW
+// Bindless handles of the current draw
+#define abledoTex sampler2D(drawMaterials[drawIndex].albedoTex)
+#define mrTex sampler2D(drawMaterials[drawIndex].mrTex)
+#define normalTex sampler2D(drawMaterials[drawIndex].normalTex)
+#define occlusionTex sampler2D(drawMaterials[drawIndex].occlusionTex)
+#define emissiveTex sampler2D(drawMaterials[drawIndex].emissiveTex)
+#define clearcoatIntensityTex sampler2D(drawMaterials[drawIndex].clearcoatIntensityTex)
+#define clearcoatRoughnessTex sampler2D(drawMaterials[drawIndex].clearcoatRoughnessTex)
+#define clearcoatNormalTex sampler2D(drawMaterials[drawIndex].clearcoatNormalTex)
+#else
 #ifdef ALBEDO_MAP
 layout(binding = {{consts.texture_ports.albedo}}) uniform sampler2D abledoTex;
 #endif
@@ -31,8 +41,12 @@ layout(binding = {{consts.texture_ports.clearcoat_roughness}}) uniform sampler2D
 #ifdef CLEARCOAT_NORMAL_MAP
 layout(binding = {{consts.texture_ports.clearcoat_normal}}) uniform sampler2D clearcoatNormalTex;
 #endif
+#endif
 
 layout(binding = {{consts.texture_ports.irradiance}}) uniform samplerCube irradianceMap;
 layout(binding = {{consts.texture_ports.prefilter}}) uniform samplerCube prefilterMap;
 layout(binding = {{consts.texture_ports.brdf}}) uniform sampler2D brdfLut;
+layout(binding = {{consts.texture_ports.ltc}}) uniform sampler2D ltcLut;
+layout(binding = {{consts.texture_ports.ssao}}) uniform sampler2D ssaoTex;
+layout(binding = {{consts.texture_ports.ssr}}) uniform sampler2D ssrTex;
 // clang-format on
@@ -7,5 +7,16 @@ layout(std140, binding = {{ consts.buffer_bindings.settings }}) uniform Settings
     uniform bool IBLEnabled;
     uniform uint diffuseType;
     uniform bool energyCompEnabled;
+    uniform bool shadowsEnabled;
+    uniform uint pcfRadius;
+    uniform float shadowBias;
+    uniform float normalBias;
+    uniform bool ssaoEnabled;
+    uniform float ssaoRadius;
+    uniform float ssaoIntensity;
+    uniform bool ssrEnabled;
+    uniform float ssrMaxDistance;
+    uniform float ssrThickness;
+    uniform uint debugView;
 };
 
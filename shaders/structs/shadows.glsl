@@ -0,0 +1,20 @@
+// clang-format off
+layout(std140, binding = {{ consts.buffer_bindings.shadows }}) uniform Shadows
+// clang-format on
+{
+    // Light-space transforms of the layers of the 2D shadow maps
+    uniform mat4 shadowMatrices[{{ consts.shadows.max_layers }}];
+    // View matrix of the camera, used for selecting the cascade
+    uniform mat4 shadowCameraView;
+    // View-space depth where each cascade ends
+    uniform vec4 cascadeSplits;
+    // Far plane of the point light shadow cubemaps
+    uniform float pointShadowFar;
+};
+
+// clang-format off
+// Spot lights use a single layer, directional lights use a layer for each cascade
+layout(binding = {{ consts.texture_ports.shadow_maps }}) uniform sampler2DArrayShadow shadowMaps;
+// Point lights store the linear distance to the light
+layout(binding = {{ consts.texture_ports.shadow_cube_maps }}) uniform samplerCubeArrayShadow shadowCubeMaps;
+// clang-format on
@@ -0,0 +1,66 @@
+#version 460 core
+// clang-format off
+{% include "consts.glsl" %}
+{% include "tools/tonemap.glsl" %}
+// clang-format on
+
+in vec2 texCoords;
+
+out vec4 FragColor;
+
+layout(binding = 0) uniform sampler2D hdrTex;
+layout(binding = 1) uniform sampler2D bloomTex;
+
+uniform uint tonemapOperator;
+uniform float exposure;
+// 0 if the bloom is disabled
+uniform float bloomIntensity;
+// The debug views that aren't radiance are only gamma corrected, separately for both sides of the split view
+uniform bool passthroughLeft;
+uniform bool passthroughRight;
+// Texture coordinate of the split view divider
+uniform float divider;
+// Shows the deviation from 1 instead of the tonemapped image
+uniform bool furnaceTest;
+// Deviation with the fully saturated false color
+uniform float furnaceRange;
+
+// Energy loss is blue, energy gain is red
+vec3 furnaceFalseColor(vec3 color)
+{
+    float deviation = (color.r + color.g + color.b) / 3. - 1.;
+    vec3 falseColor = deviation < 0. ? vec3(0., 0.2, 1.) : vec3(1., 0.1, 0.);
+    float weight = clamp(abs(deviation) / furnaceRange, 0., 1.);
+
+    return mix(clamp(color, 0., 1.), falseColor, weight);
+}
+
+void main()
+{
+    vec3 color = texture(hdrTex, texCoords).rgb;
+
+    if (furnaceTest) {
+        FragColor = vec4(pow(furnaceFalseColor(color), vec3(1.0 / GAMMA)), 1.0);
+        return;
+    }
+
+    bool passthrough = texCoords.x < divider ? passthroughLeft : passthroughRight;
+    if (passthrough) {
+        FragColor = vec4(pow(color, vec3(1.0 / GAMMA)), 1.0);
+        return;
+    }
+
+    if (bloomIntensity > 0.0) {
+        vec3 bloom = textureLod(bloomTex, texCoords, 0.0).rgb;
+        color = mix(color, bloom, bloomIntensity);
+    }
+
+    color *= exposure;
+
+    tonemap(color, tonemapOperator);
+
+    // gamma correction
+    color = pow(color, vec3(1.0 / GAMMA));
+
+    FragColor = vec4(color, 1.0);
+}
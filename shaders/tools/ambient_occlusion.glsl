@@ -0,0 +1,24 @@
+// Requires structs/pbrVsOut.glsl, structs/pbrMaterial.glsl, structs/pbrTextures.glsl and structs/settings.glsl
+
+// Occlusion from the occlusion texture of the material
+float occlusionMap()
+{
+#ifdef OCCLUSION_MAP
+    return texture(occlusionTex, vsOut.texCoords).x * occlusionStrength;
+#else
+    return 1.0;
+#endif
+}
+
+// Occlusion of the ambient light, the minimum of the occlusion texture and the screen-space occlusion
+float ambientOcclusion()
+{
+    float occlusion = occlusionMap();
+
+    if (ssaoEnabled) {
+        // The SSAO texture has the same size as the render target
+        occlusion = min(occlusion, texelFetch(ssaoTex, ivec2(gl_FragCoord.xy), 0).r);
+    }
+
+    return occlusion;
+}
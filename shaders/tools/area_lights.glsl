@@ -0,0 +1,152 @@
+// Requires structs/lighting.glsl and structs/pbrTextures.glsl
+
+// Area lights evaluated with linearly transformed cosines, based on:
+// Heitz et al., “Real-Time Polygonal-Light Shading with Linearly Transformed Cosines.”
+// Hill and Heitz, “Real-Time Area Lighting: a Journey from Research to Production.”
+
+// Disks are approximated by regular polygons
+const uint AREA_LIGHT_MAX_VERTICES = 12;
+
+// clang-format off
+const float LTC_LUT_SIZE = float({{ consts.ltc.size }});
+// clang-format on
+const float LTC_LUT_SCALE = (LTC_LUT_SIZE - 1.0) / LTC_LUT_SIZE;
+const float LTC_LUT_BIAS = 0.5 / LTC_LUT_SIZE;
+
+// Vector form factor of an edge of the polygon,
+// uses a rational polynomial approximation of theta / sin(theta) / 2PI
+vec3 ltcEdgeVectorFormFactor(vec3 v1, vec3 v2)
+{
+    float x = dot(v1, v2);
+    float y = abs(x);
+
+    float a = 0.8543985 + (0.4965155 + 0.0145206 * y) * y;
+    float b = 3.4175940 + (4.1616724 + y) * y;
+    float v = a / b;
+
+    float thetaSinTheta = (x > 0.0) ? v : 0.5 * inversesqrt(max(1.0 - x * x, 1e-7)) - v;
+
+    return cross(v1, v2) * thetaSinTheta;
+}
+
+// Integral of the LTC over the polygon, the polygon is wound counter-clockwise when seen from its front side
+float ltcEvaluate(
+    vec3 N, vec3 V, vec3 P, mat3 Minv, vec3 points[AREA_LIGHT_MAX_VERTICES], uint count)
+{
+    // Orthonormal basis around the normal, the view direction lies in the XZ plane
+    vec3 T1 = V - N * dot(V, N);
+    T1 = dot(T1, T1) > 1e-8 ? normalize(T1) : normalize(cross(N, abs(N.y) < 0.99 ? vec3(0., 1., 0.) : vec3(1., 0., 0.)));
+    vec3 T2 = cross(N, T1);
+
+    mat3 toLtc = Minv * transpose(mat3(T1, T2, N));
+
+    vec3 first = normalize(toLtc * (points[0] - P));
+    vec3 previous = first;
+    vec3 formFactor = vec3(0.);
+
+    for (uint i = 1; i < count; i++) {
+        vec3 current = normalize(toLtc * (points[i] - P));
+        formFactor += ltcEdgeVectorFormFactor(previous, current);
+        previous = current;
+    }
+    formFactor += ltcEdgeVectorFormFactor(previous, first);
+
+    // The winding of the polygon makes the vector point away from the polygon
+    formFactor = -formFactor;
+
+    // Approximation of the form factor of the polygon clipped by the horizon,
+    // the polygon is replaced with a sphere that has the same vector form factor
+    float len = length(formFactor);
+    return max((len * len + formFactor.z) / (len + 1.0), 0.0);
+}
+
+// Vertices of the light's shape, returns 0 if the point is behind the light
+uint areaLightPolygon(Light light, vec3 fragPos, out vec3 points[AREA_LIGHT_MAX_VERTICES])
+{
+    vec3 center = light.position.xyz;
+    vec3 axis = normalize(light.direction.xyz);
+
+    vec3 right;
+    vec3 up;
+
+    if (light.typ == LIGHT_TYPE_TUBE) {
+        // Tubes are approximated by a rectangle that faces the shaded point
+        vec3 toPoint = fragPos - center;
+        vec3 facing = toPoint - axis * dot(toPoint, axis);
+        facing = dot(facing, facing) > 1e-8
+            ? normalize(facing)
+            : normalize(cross(axis, abs(axis.y) < 0.99 ? vec3(0., 1., 0.) : vec3(1., 0., 0.)));
+
+        right = axis * (0.5 * light.width);
+        up = cross(facing, axis) * (0.5 * light.height);
+    } else {
+        if (dot(fragPos - center, axis) <= 0.0) {
+            return 0;
+        }
+
+        // Must match the gizmos
+        vec3 worldUp = abs(axis.y) < 0.99 ? vec3(0., 1., 0.) : vec3(1., 0., 0.);
+        right = normalize(cross(worldUp, axis));
+        up = cross(axis, right);
+    }
+
+    if (light.typ == LIGHT_TYPE_DISK) {
+        float n = float(AREA_LIGHT_MAX_VERTICES);
+        // The polygon has the same area as the disk
+        float radius = 0.5 * light.width * sqrt(2.0 * PI / (n * sin(2.0 * PI / n)));
+
+        for (uint i = 0; i < AREA_LIGHT_MAX_VERTICES; i++) {
+            float phi = 2.0 * PI * float(i) / n;
+            points[i] = center + radius * (cos(phi) * right + sin(phi) * up);
+        }
+
+        return AREA_LIGHT_MAX_VERTICES;
+    }
+
+    if (light.typ == LIGHT_TYPE_RECT) {
+        right *= 0.5 * light.width;
+        up *= 0.5 * light.height;
+    }
+
+    points[0] = center - right - up;
+    points[1] = center + right - up;
+    points[2] = center + right + up;
+    points[3] = center - right + up;
+
+    return 4;
+}
+
+// GGX specular and Lambertian diffuse reflection of an area light, the lobes are returned separately
+void areaLightRadiance(Light light, vec3 fragPos, vec3 N, vec3 V, float roughness, vec3 f0, vec3 diffuseColor,
+    out vec3 diffuseRadiance, out vec3 specularRadiance)
+{
+    diffuseRadiance = vec3(0.);
+    specularRadiance = vec3(0.);
+
+    vec3 points[AREA_LIGHT_MAX_VERTICES];
+    uint count = areaLightPolygon(light, fragPos, points);
+
+    if (count == 0) {
+        return;
+    }
+
+    float NoV = clamp(dot(N, V), 0.0001, 1.0);
+    float linearRoughness = sqrt(roughness);
+
+    vec2 ltcCoords = vec2(linearRoughness, sqrt(1.0 - NoV)) * LTC_LUT_SCALE + LTC_LUT_BIAS;
+    vec4 t = texture(ltcLut, ltcCoords);
+    mat3 Minv = mat3(vec3(t.x, 0., t.y), vec3(0., 1., 0.), vec3(t.z, 0., t.w));
+
+    float specular = ltcEvaluate(N, V, fragPos, Minv, points, count);
+    float diffuse = ltcEvaluate(N, V, fragPos, mat3(1.), points, count);
+
+    // The LTCs are normalized, the magnitude and the Fresnel term come from the DFG LUT
+    vec2 dfg = texture(brdfLut, vec2(NoV, linearRoughness)).rg;
+    vec3 specularColor = f0 * dfg.x + dfg.y;
+
+    vec3 radiance = light.color * light.intensity;
+    radiance *= rangeAttenuation(distance(light.position.xyz, fragPos), lightInfluenceRadius(light));
+
+    specularRadiance = radiance * specularColor * specular;
+    diffuseRadiance = radiance * (1.0 - specularColor) * diffuseColor * diffuse;
+}
@@ -0,0 +1,60 @@
+// Requires structs/pbrVsOut.glsl, structs/settings.glsl and tools/normal_map.glsl
+
+// Must match DebugView in app_settings.rs
+const uint DEBUG_VIEW_NONE = 0;
+const uint DEBUG_VIEW_BASE_COLOR = 1;
+const uint DEBUG_VIEW_METALLIC = 2;
+const uint DEBUG_VIEW_ROUGHNESS = 3;
+const uint DEBUG_VIEW_OCCLUSION = 4;
+const uint DEBUG_VIEW_EMISSIVE = 5;
+const uint DEBUG_VIEW_SHADING_NORMAL = 6;
+const uint DEBUG_VIEW_GEOMETRIC_NORMAL = 7;
+const uint DEBUG_VIEW_TANGENT = 8;
+const uint DEBUG_VIEW_BITANGENT = 9;
+const uint DEBUG_VIEW_UV = 10;
+const uint DEBUG_VIEW_NOV = 11;
+const uint DEBUG_VIEW_DIFFUSE_ONLY = 12;
+const uint DEBUG_VIEW_SPECULAR_ONLY = 13;
+const uint DEBUG_VIEW_IBL_ONLY = 14;
+const uint DEBUG_VIEW_DIRECT_ONLY = 15;
+const uint DEBUG_VIEW_CLEARCOAT = 16;
+
+// The non-radiance views skip the tonemapping, but not the gamma correction,
+// so the raw values are encoded in a way that the gamma correction reverses
+vec3 debugValue(vec3 value)
+{
+    return pow(clamp(value, 0., 1.), vec3(GAMMA));
+}
+
+vec3 debugDirection(vec3 dir)
+{
+    return debugValue(dir * 0.5 + 0.5);
+}
+
+// The views that only depend on the geometry, returns false for the other views
+bool geometryDebugView(NormalBasis tb, float NoV, out vec3 color)
+{
+    switch (debugView) {
+    case DEBUG_VIEW_SHADING_NORMAL:
+        color = debugDirection(tb.normal);
+        return true;
+    case DEBUG_VIEW_GEOMETRIC_NORMAL:
+        color = debugDirection(normalize(vsOut.normal));
+        return true;
+    case DEBUG_VIEW_TANGENT:
+        color = debugDirection(normalize(tb.tangent));
+        return true;
+    case DEBUG_VIEW_BITANGENT:
+        color = debugDirection(normalize(tb.bitangent));
+        return true;
+    case DEBUG_VIEW_UV:
+        color = debugValue(vec3(fract(vsOut.texCoords), 0.));
+        return true;
+    case DEBUG_VIEW_NOV:
+        color = debugValue(vec3(NoV));
+        return true;
+    }
+
+    color = vec3(0.);
+    return false;
+}
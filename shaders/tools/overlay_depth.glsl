@@ -0,0 +1,15 @@
+// The overlays are drawn after the post-processing, when the depth buffer of the scene isn't bound anymore,
+// so they are depth-tested against the depth texture of the scene manually
+
+layout(binding = 0) uniform sampler2D sceneDepth;
+// Lower left corner of the viewport in the output framebuffer
+uniform vec2 viewportOrigin;
+
+bool hiddenByScene()
+{
+    float sceneDepthValue = texelFetch(sceneDepth, ivec2(gl_FragCoord.xy - viewportOrigin), 0).r;
+    // The scene was rendered with a jittered projection, the slope bias covers the subpixel offset
+    float bias = 2. * fwidth(gl_FragCoord.z) + 1e-6;
+
+    return gl_FragCoord.z > sceneDepthValue + bias;
+}
@@ -0,0 +1,100 @@
+// Requires structs/lighting.glsl, structs/settings.glsl and structs/shadows.glsl
+
+// Percentage-closer filtering of a layer of the 2D shadow maps
+float sampleShadowMap(vec3 worldPos, int layer, float bias)
+{
+    vec4 lightSpacePos = shadowMatrices[layer] * vec4(worldPos, 1.0);
+    vec3 coords = lightSpacePos.xyz / lightSpacePos.w * 0.5 + 0.5;
+
+    // Beyond the far plane of the light
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMaps, 0).xy);
+    int radius = int(pcfRadius);
+
+    float visibility = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec2 uv = coords.xy + vec2(x, y) * texelSize;
+            visibility += texture(shadowMaps, vec4(uv, float(layer), coords.z - bias));
+        }
+    }
+
+    float kernelWidth = float(2 * radius + 1);
+    return visibility / (kernelWidth * kernelWidth);
+}
+
+// Percentage-closer filtering of a point light cubemap, the kernel is perpendicular to the sampling direction
+float sampleShadowCubeMap(vec3 worldPos, Light light, float bias)
+{
+    vec3 lightToFrag = worldPos - light.position.xyz;
+    float depth = length(lightToFrag) / pointShadowFar;
+
+    if (depth > 1.0) {
+        return 1.0;
+    }
+
+    vec3 dir = normalize(lightToFrag);
+    vec3 up = abs(dir.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, dir));
+    vec3 bitangent = cross(dir, tangent);
+
+    // A cubemap face covers the <-1, 1> range at distance 1
+    float texelSize = 2.0 / float(textureSize(shadowCubeMaps, 0).x);
+    int radius = int(pcfRadius);
+
+    float visibility = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec3 sampleDir = dir + (tangent * float(x) + bitangent * float(y)) * texelSize;
+            visibility += texture(shadowCubeMaps, vec4(sampleDir, float(light.shadowIndex)), depth - bias);
+        }
+    }
+
+    float kernelWidth = float(2 * radius + 1);
+    return visibility / (kernelWidth * kernelWidth);
+}
+
+// Returns the fraction of the light that isn't occluded
+float shadowVisibility(Light light, vec3 worldPos, vec3 normal, vec3 lightDir)
+{
+    if (!shadowsEnabled || light.shadowIndex < 0) {
+        return 1.0;
+    }
+
+    float NoL = clamp(dot(normal, lightDir), 0.0, 1.0);
+    // Slope-scaled bias, surfaces at grazing angles cover more texels
+    float slope = sqrt(1.0 - NoL * NoL) / max(NoL, 0.1);
+    float bias = shadowBias * (1.0 + slope);
+    // Offsetting the position along the normal helps with the acne at grazing angles too
+    vec3 offsetPos = worldPos + normal * normalBias * (1.0 - NoL);
+
+    switch (light.typ) {
+    // Area lights cast the shadows from their center
+    case LIGHT_TYPE_POINT:
+    case LIGHT_TYPE_RECT:
+    case LIGHT_TYPE_DISK:
+    case LIGHT_TYPE_TUBE:
+        return sampleShadowCubeMap(offsetPos, light, bias);
+    case LIGHT_TYPE_SPOT:
+        return sampleShadowMap(offsetPos, light.shadowIndex, bias);
+    case LIGHT_TYPE_DIRECTIONAL: {
+        float viewDepth = -(shadowCameraView * vec4(worldPos, 1.0)).z;
+
+        // clang-format off
+        for (int cascade = 0; cascade < {{ consts.shadows.cascades }}; cascade++) {
+            // clang-format on
+            if (viewDepth < cascadeSplits[cascade]) {
+                return sampleShadowMap(offsetPos, light.shadowIndex + cascade, bias);
+            }
+        }
+
+        // Beyond the shadow distance
+        return 1.0;
+    }
+    }
+
+    return 1.0;
+}
@@ -0,0 +1,27 @@
+// Requires structs/pbrTextures.glsl and structs/settings.glsl
+
+// Perceptual roughness where the screen-space reflections are replaced by the prefilter map
+const float SSR_MAX_ROUGHNESS = 0.6;
+
+// Replaces the prefiltered radiance with the screen-space reflections where the rays hit
+vec3 screenSpaceReflections(vec3 prefilteredRadiance, float roughness)
+{
+    if (!ssrEnabled) {
+        return prefilteredRadiance;
+    }
+
+    float perceptualRoughness = sqrt(roughness);
+    if (perceptualRoughness >= SSR_MAX_ROUGHNESS) {
+        return prefilteredRadiance;
+    }
+
+    // Rougher surfaces use the blurrier levels of the reflections
+    vec2 uv = gl_FragCoord.xy / vec2(textureSize(ssrTex, 0));
+    float maxLod = float(textureQueryLevels(ssrTex) - 1);
+    vec4 ssr = textureLod(ssrTex, uv, perceptualRoughness / SSR_MAX_ROUGHNESS * maxLod);
+
+    float confidence = ssr.a * (1.0 - smoothstep(0.5 * SSR_MAX_ROUGHNESS, SSR_MAX_ROUGHNESS, perceptualRoughness));
+    vec3 reflection = ssr.rgb / max(ssr.a, 0.0001);
+
+    return mix(prefilteredRadiance, reflection, confidence);
+}
@@ -1,8 +1,11 @@
 
-#define TONEMAP_REINHARD 0
-#define TONEMAP_UNCHARTED 1
-
-#define TONEMAP_OPERATOR TONEMAP_UNCHARTED
+// Must match the Tonemapping enum
+const uint TONEMAP_REINHARD = 0;
+const uint TONEMAP_UNCHARTED = 1;
+const uint TONEMAP_ACES_FITTED = 2;
+const uint TONEMAP_ACES_RRT_ODT = 3;
+const uint TONEMAP_AGX = 4;
+const uint TONEMAP_PBR_NEUTRAL = 5;
 
 // This code is based on: https://64.github.io/tonemapping/
 
@@ -31,11 +34,130 @@ void uncharted2Filmic(inout vec3 v)
     v = curr * whiteScale;
 }
 
-void tonemap(inout vec3 color)
+// Krzysztof Narkowicz, "ACES Filmic Tone Mapping Curve"
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+void acesFitted(inout vec3 v)
+{
+    // The curve is fitted to the ACES curve with an exposure of 0.6
+    v *= 0.6f;
+
+    float a = 2.51f;
+    float b = 0.03f;
+    float c = 2.43f;
+    float d = 0.59f;
+    float e = 0.14f;
+    v = clamp((v * (a * v + b)) / (v * (c * v + d) + e), 0.0f, 1.0f);
+}
+
+vec3 rrtAndOdtFit(vec3 v)
+{
+    vec3 a = v * (v + 0.0245786f) - 0.000090537f;
+    vec3 b = v * (0.983729f * v + 0.4329510f) + 0.238081f;
+    return a / b;
+}
+
+// Stephen Hill's fit of the ACES RRT and sRGB ODT
+// https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl
+void acesRrtOdt(inout vec3 v)
+{
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const mat3 acesInput = mat3(
+        0.59719, 0.07600, 0.02840,
+        0.35458, 0.90834, 0.13383,
+        0.04823, 0.01566, 0.83777);
+
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const mat3 acesOutput = mat3(
+        1.60475, -0.10208, -0.00327,
+        -0.53108, 1.10813, -0.07276,
+        -0.07367, -0.00605, 1.07602);
+
+    v = acesInput * v;
+    v = rrtAndOdtFit(v);
+    v = clamp(acesOutput * v, 0.0f, 1.0f);
+}
+
+// 6th order polynomial approximation of the default AgX contrast curve
+vec3 agxDefaultContrastApprox(vec3 x)
+{
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232;
+}
+
+// Benjamin Wrensch, "Minimal AgX Implementation"
+// https://iolite-engine.com/blog_posts/minimal_agx_implementation
+void agx(inout vec3 v)
+{
+    const mat3 agxInset = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104);
+
+    const mat3 agxOutset = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116);
+
+    const float minEv = -12.47393f;
+    const float maxEv = 4.026069f;
+
+    v = agxInset * v;
+    v = clamp(log2(max(v, 1e-10)), minEv, maxEv);
+    v = (v - minEv) / (maxEv - minEv);
+    v = agxDefaultContrastApprox(v);
+    v = agxOutset * v;
+
+    // The curve outputs display-encoded values, the gamma correction is done afterwards
+    v = pow(clamp(v, 0.0f, 1.0f), vec3(GAMMA));
+}
+
+// Khronos PBR Neutral tone mapper
+// https://github.com/KhronosGroup/ToneMapping/tree/main/PBR_Neutral
+void pbrNeutral(inout vec3 v)
+{
+    const float startCompression = 0.8 - 0.04;
+    const float desaturation = 0.15;
+
+    float x = min(v.r, min(v.g, v.b));
+    float offset = x < 0.08 ? x - 6.25 * x * x : 0.04;
+    v -= offset;
+
+    float peak = max(v.r, max(v.g, v.b));
+    if (peak < startCompression) {
+        return;
+    }
+
+    const float d = 1. - startCompression;
+    float newPeak = 1. - d * d / (peak + d - startCompression);
+    v *= newPeak / peak;
+
+    float g = 1. - 1. / (desaturation * (peak - newPeak) + 1.);
+    v = mix(v, vec3(newPeak), g);
+}
+
+void tonemap(inout vec3 color, uint tonemapOperator)
 {
-#if TONEMAP_OPERATOR == TONEMAP_REINHARD
-    reinhard(color);
-#elif TONEMAP_OPERATOR == TONEMAP_UNCHARTED
-    uncharted2Filmic(color);
-#endif
+    switch (tonemapOperator) {
+    case TONEMAP_REINHARD:
+        reinhard(color);
+        break;
+    case TONEMAP_UNCHARTED:
+        uncharted2Filmic(color);
+        break;
+    case TONEMAP_ACES_FITTED:
+        acesFitted(color);
+        break;
+    case TONEMAP_ACES_RRT_ODT:
+        acesRrtOdt(color);
+        break;
+    case TONEMAP_AGX:
+        agx(color);
+        break;
+    case TONEMAP_PBR_NEUTRAL:
+        pbrNeutral(color);
+        break;
+    }
 }
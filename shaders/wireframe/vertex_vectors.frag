@@ -0,0 +1,17 @@
+#version 460 core
+// clang-format off
+{% include "tools/overlay_depth.glsl" %}
+// clang-format on
+
+in vec3 lineColor;
+
+out vec4 FragColor;
+
+void main()
+{
+    if (hiddenByScene()) {
+        discard;
+    }
+
+    FragColor = vec4(lineColor, 1.0);
+}
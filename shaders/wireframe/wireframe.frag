@@ -0,0 +1,28 @@
+#version 460 core
+// clang-format off
+{% include "tools/overlay_depth.glsl" %}
+// clang-format on
+
+noperspective in vec3 edgeDistance;
+
+uniform vec3 lineColor;
+// Width of the lines in pixels
+uniform float lineWidth;
+
+out vec4 FragColor;
+
+void main()
+{
+    float distance = min(edgeDistance.x, min(edgeDistance.y, edgeDistance.z));
+    // One pixel wide falloff for antialiasing
+    float coverage = 1. - smoothstep(lineWidth * 0.5 - 0.5, lineWidth * 0.5 + 0.5, distance);
+
+    // Evaluated before any discard, it uses derivatives
+    bool hidden = hiddenByScene();
+
+    if (coverage <= 0. || hidden) {
+        discard;
+    }
+
+    FragColor = vec4(lineColor, coverage);
+}